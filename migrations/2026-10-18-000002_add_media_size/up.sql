@@ -0,0 +1 @@
+ALTER TABLE media ADD COLUMN size_bytes BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,18 @@
+CREATE TABLE tweet_media (
+    tweet_id UUID NOT NULL REFERENCES tweets(id) ON DELETE CASCADE,
+    media_id UUID NOT NULL UNIQUE REFERENCES media(id) ON DELETE CASCADE,
+    position SMALLINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (tweet_id, media_id)
+);
+
+-- Storage objects whose deletion failed and must be retried
+CREATE TABLE storage_deletions (
+    id UUID PRIMARY KEY,
+    storage_key TEXT NOT NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX storage_deletions_next_attempt_at_idx ON storage_deletions (next_attempt_at);
@@ -0,0 +1,6 @@
+ALTER TABLE media
+    DROP COLUMN large_key,
+    DROP COLUMN small_key,
+    DROP COLUMN thumb_key,
+    DROP COLUMN height,
+    DROP COLUMN width;
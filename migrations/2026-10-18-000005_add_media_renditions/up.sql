@@ -0,0 +1,6 @@
+ALTER TABLE media
+    ADD COLUMN width INTEGER,
+    ADD COLUMN height INTEGER,
+    ADD COLUMN thumb_key TEXT,
+    ADD COLUMN small_key TEXT,
+    ADD COLUMN large_key TEXT;
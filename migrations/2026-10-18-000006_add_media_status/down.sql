@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS media_pending_expires_at_idx;
+
+ALTER TABLE media
+    DROP COLUMN expires_at,
+    DROP COLUMN status;
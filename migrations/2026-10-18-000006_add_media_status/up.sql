@@ -0,0 +1,5 @@
+ALTER TABLE media
+    ADD COLUMN status TEXT NOT NULL DEFAULT 'ready',
+    ADD COLUMN expires_at TIMESTAMP;
+
+CREATE INDEX media_pending_expires_at_idx ON media (expires_at) WHERE status = 'pending';
@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN is_protected BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS media_content_hash_idx;
+
+ALTER TABLE media DROP COLUMN content_hash;
+
+DROP TABLE media_blobs;
@@ -0,0 +1,19 @@
+-- Content-addressed files shared by all media rows with the same upload hash
+CREATE TABLE media_blobs (
+    sha256 TEXT PRIMARY KEY,
+    storage_key TEXT NOT NULL,
+    file_type TEXT NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    width INTEGER,
+    height INTEGER,
+    thumb_key TEXT,
+    small_key TEXT,
+    large_key TEXT,
+    ref_count INTEGER NOT NULL DEFAULT 1,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Rows uploaded before deduplication keep their own keys and have no hash
+ALTER TABLE media ADD COLUMN content_hash TEXT REFERENCES media_blobs(sha256);
+
+CREATE INDEX media_content_hash_idx ON media (content_hash);
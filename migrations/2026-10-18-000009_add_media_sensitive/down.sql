@@ -0,0 +1,2 @@
+ALTER TABLE users DROP COLUMN sensitive_media;
+ALTER TABLE media DROP COLUMN sensitive;
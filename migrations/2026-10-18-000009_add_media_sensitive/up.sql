@@ -0,0 +1,5 @@
+ALTER TABLE media ADD COLUMN sensitive BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- How other users' sensitive media appears in tweet responses: show, blur or hide
+ALTER TABLE users ADD COLUMN sensitive_media TEXT NOT NULL DEFAULT 'blur'
+    CHECK (sensitive_media IN ('show', 'blur', 'hide'));
@@ -0,0 +1,3 @@
+ALTER TABLE users DROP COLUMN banner_url;
+ALTER TABLE users DROP COLUMN banner_key;
+ALTER TABLE users DROP COLUMN avatar_key;
@@ -0,0 +1,4 @@
+-- Storage keys of the uploaded avatar and banner; avatar_url/banner_url point at them
+ALTER TABLE users ADD COLUMN avatar_key TEXT;
+ALTER TABLE users ADD COLUMN banner_key TEXT;
+ALTER TABLE users ADD COLUMN banner_url TEXT;
@@ -0,0 +1,2 @@
+DROP INDEX tweets_search_vector_idx;
+ALTER TABLE tweets DROP COLUMN search_vector;
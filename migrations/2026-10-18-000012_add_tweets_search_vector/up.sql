@@ -0,0 +1,5 @@
+-- Kept up to date by PostgreSQL; not part of the Diesel schema
+ALTER TABLE tweets ADD COLUMN search_vector TSVECTOR
+    GENERATED ALWAYS AS (to_tsvector('english', content)) STORED;
+
+CREATE INDEX tweets_search_vector_idx ON tweets USING GIN (search_vector);
@@ -0,0 +1,4 @@
+-- ISO 639-1 language code of the tweet, as sent by the client
+ALTER TABLE tweets ADD COLUMN lang TEXT;
+
+CREATE INDEX tweets_lang_idx ON tweets (lang);
@@ -0,0 +1,3 @@
+DROP TABLE trends;
+DROP TABLE tweet_hashtags;
+DROP TABLE hashtags;
@@ -0,0 +1,27 @@
+-- Tags are stored lowercased, so `#Rust` and `#rust` are the same hashtag
+CREATE TABLE hashtags (
+    id UUID PRIMARY KEY,
+    tag TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- `created_at` copies the tweet's, so trend windows can be scanned without the tweets table
+CREATE TABLE tweet_hashtags (
+    tweet_id UUID NOT NULL REFERENCES tweets(id) ON DELETE CASCADE,
+    hashtag_id UUID NOT NULL REFERENCES hashtags(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (tweet_id, hashtag_id)
+);
+
+CREATE INDEX tweet_hashtags_hashtag_id_idx ON tweet_hashtags (hashtag_id, created_at DESC);
+CREATE INDEX tweet_hashtags_created_at_idx ON tweet_hashtags (created_at);
+
+-- Latest trends, replaced as a whole by the trends job
+CREATE TABLE trends (
+    hashtag_id UUID PRIMARY KEY REFERENCES hashtags(id) ON DELETE CASCADE,
+    rank INTEGER NOT NULL,
+    score DOUBLE PRECISION NOT NULL,
+    recent_authors BIGINT NOT NULL,
+    baseline_authors BIGINT NOT NULL,
+    computed_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
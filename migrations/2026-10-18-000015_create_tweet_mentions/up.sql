@@ -0,0 +1,10 @@
+-- Users mentioned in a tweet, resolved when it is written.
+-- `created_at` copies the tweet's, so the mentions timeline is served from this table.
+CREATE TABLE tweet_mentions (
+    tweet_id UUID NOT NULL REFERENCES tweets(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (tweet_id, user_id)
+);
+
+CREATE INDEX tweet_mentions_user_id_idx ON tweet_mentions (user_id, created_at DESC);
@@ -0,0 +1,18 @@
+-- One row per actor and event. Rows sharing a `group_key` are listed as one
+-- entry ("alice and 12 others liked your tweet").
+CREATE TABLE notifications (
+    id UUID PRIMARY KEY,
+    recipient_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    actor_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    tweet_id UUID REFERENCES tweets(id) ON DELETE CASCADE,
+    group_key TEXT NOT NULL,
+    read_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    -- The same actor repeating an event (unlike, like again) is not notified twice
+    UNIQUE (recipient_id, group_key, actor_id)
+);
+
+CREATE INDEX notifications_recipient_id_idx ON notifications (recipient_id, created_at DESC, id DESC);
+CREATE INDEX notifications_unread_idx ON notifications (recipient_id) WHERE read_at IS NULL;
+CREATE INDEX notifications_tweet_id_idx ON notifications (tweet_id);
@@ -0,0 +1,5 @@
+DROP TABLE message_media;
+DROP TABLE messages;
+DROP TABLE conversation_members;
+DROP TABLE conversations;
+ALTER TABLE users DROP COLUMN dm_policy;
@@ -0,0 +1,46 @@
+-- Who may start a conversation with a user: everyone, or only people they follow
+ALTER TABLE users ADD COLUMN dm_policy TEXT NOT NULL DEFAULT 'everyone'
+    CHECK (dm_policy IN ('everyone', 'following'));
+
+CREATE TABLE conversations (
+    id UUID PRIMARY KEY,
+    is_group BOOLEAN NOT NULL DEFAULT FALSE,
+    name TEXT,
+    creator_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    -- The two members of a one-to-one conversation, smaller ID first, so a pair
+    -- never gets a second conversation. NULL for groups.
+    direct_key TEXT UNIQUE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    last_message_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE conversation_members (
+    conversation_id UUID NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    joined_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    -- Set when the member left a group; they no longer see it
+    left_at TIMESTAMP,
+    muted BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Read receipt: messages up to this time have been read
+    last_read_at TIMESTAMP,
+    PRIMARY KEY (conversation_id, user_id)
+);
+
+CREATE INDEX conversation_members_user_id_idx ON conversation_members (user_id);
+
+CREATE TABLE messages (
+    id UUID PRIMARY KEY,
+    conversation_id UUID NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    sender_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX messages_conversation_id_idx ON messages (conversation_id, created_at DESC, id DESC);
+
+CREATE TABLE message_media (
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    media_id UUID NOT NULL UNIQUE REFERENCES media(id) ON DELETE CASCADE,
+    position SMALLINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (message_id, media_id)
+);
@@ -2,7 +2,11 @@ use argon2::{
     Argon2,
     password_hash::{Error, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
 };
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rand::RngCore;
 use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
 
 pub struct PasswordService;
 
@@ -23,3 +27,20 @@ impl PasswordService {
             .is_ok())
     }
 }
+
+pub struct TokenService;
+
+impl TokenService {
+    /// Generates a random opaque token (256 bits, URL-safe base64).
+    pub fn generate_token() -> String {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    /// Hashes a token for storage. Tokens are high-entropy,
+    /// so a fast unsalted hash is sufficient for lookups.
+    pub fn hash_token(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+}
@@ -23,3 +23,39 @@ pub fn get_db_conn(
         )
     })
 }
+
+diesel::define_sql_function! {
+    /// SQL `LOWER()`, for case-insensitive matches on usernames
+    fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text;
+}
+
+/// Pool for repository tests on `TEST_DATABASE_URL`, a database with the
+/// migrations applied, or `None` (and the test is skipped) when it is not set. The pool holds a single
+/// connection whose work is rolled back when the pool is dropped.
+#[cfg(test)]
+pub fn test_pool() -> Option<DbPool> {
+    use diesel::r2d2::CustomizeConnection;
+
+    #[derive(Debug)]
+    struct TestTransaction;
+
+    impl CustomizeConnection<PgConnection, diesel::r2d2::Error> for TestTransaction {
+        fn on_acquire(&self, conn: &mut PgConnection) -> Result<(), diesel::r2d2::Error> {
+            conn.begin_test_transaction()
+                .map_err(diesel::r2d2::Error::QueryError)
+        }
+    }
+
+    let Ok(database_url) = env::var("TEST_DATABASE_URL") else {
+        eprintln!("TEST_DATABASE_URL is not set, skipping database test");
+        return None;
+    };
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+
+    let pool = Pool::builder()
+        .max_size(1)
+        .connection_customizer(Box::new(TestTransaction))
+        .build(manager)
+        .expect("Failed to create test database pool");
+    Some(pool)
+}
@@ -0,0 +1,183 @@
+/// Longest hashtag indexed, in characters (without the `#`)
+pub const MAX_HASHTAG_CHARS: usize = 100;
+
+/// Whether `c` starts a hashtag: `#` or the full-width `＃` used by CJK keyboards
+fn is_hash_sign(c: char) -> bool {
+    c == '#' || c == '＃'
+}
+
+/// Letters and digits of any script, `_`, and the zero-width joiners that
+/// Persian, Indic and emoji sequences need inside a word
+fn is_hashtag_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '\u{200c}' || c == '\u{200d}' || is_combining_mark(c)
+}
+
+/// Combining marks not covered by `char::is_alphanumeric`, such as accents
+/// typed as separate code points
+fn is_combining_mark(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036f}'
+        | '\u{1ab0}'..='\u{1aff}'
+        | '\u{1dc0}'..='\u{1dff}'
+        | '\u{20d0}'..='\u{20ff}'
+        | '\u{fe20}'..='\u{fe2f}'
+        | '\u{3099}'..='\u{309a}')
+}
+
+/// Whether a hashtag may start right after `c`. Rules out `a#b`, HTML
+/// entities like `&#39;` and URL fragments like `example.com/#top`.
+fn can_precede_hashtag(c: char) -> bool {
+    !(is_hashtag_char(c) || is_hash_sign(c) || c == '&' || c == '/')
+}
+
+/// Stored form of a tag: lowercased, with full-width Latin letters and digits
+/// (`＃ＲＵＳＴ`) folded to ASCII so they match the same tag
+fn fold_tag(body: &str) -> String {
+    body.chars()
+        .map(|c| match c {
+            '\u{ff10}'..='\u{ff19}' | '\u{ff21}'..='\u{ff3a}' | '\u{ff41}'..='\u{ff5a}' => {
+                char::from_u32(c as u32 - 0xfee0).unwrap_or(c)
+            }
+            '\u{ff3f}' => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// An entity found in tweet text. Offsets count Unicode characters (code points),
+/// from the `#` or `@` up to the end of the entity (exclusive).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntitySpan {
+    /// The hashtag in its stored form, or the username as written; both without the sign
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Finds every hashtag of a tweet. Tags of digits only (`#1`) and tags longer
+/// than [`MAX_HASHTAG_CHARS`] are not hashtags.
+pub fn hashtag_spans(text: &str) -> Vec<EntitySpan> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut spans = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let preceded_ok = i == 0 || can_precede_hashtag(chars[i - 1]);
+        if !is_hash_sign(chars[i]) || !preceded_ok {
+            i += 1;
+            continue;
+        }
+
+        let start = i + 1;
+        let mut end = start;
+        while end < chars.len() && is_hashtag_char(chars[end]) {
+            end += 1;
+        }
+
+        let body = &chars[start..end];
+        let has_non_digit = body.iter().any(|c| !c.is_numeric() && *c != '_');
+        if has_non_digit && body.len() <= MAX_HASHTAG_CHARS {
+            spans.push(EntitySpan {
+                text: fold_tag(&body.iter().collect::<String>()),
+                start: i,
+                end,
+            });
+        }
+
+        i = end.max(i + 1);
+    }
+
+    spans
+}
+
+/// Extracts the distinct hashtags of a tweet in their stored form, in order of first appearance
+pub fn extract_hashtags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for span in hashtag_spans(text) {
+        if !tags.contains(&span.text) {
+            tags.push(span.text);
+        }
+    }
+    tags
+}
+
+/// Normalizes a tag given by a client (`Rust`, `#Rust`) to its stored form,
+/// or returns `None` if it is not a valid hashtag
+pub fn normalize_hashtag(input: &str) -> Option<String> {
+    let body = input.strip_prefix(is_hash_sign).unwrap_or(input);
+    let valid = body.chars().all(is_hashtag_char)
+        && body.chars().any(|c| !c.is_numeric() && c != '_')
+        && body.chars().count() <= MAX_HASHTAG_CHARS;
+
+    valid.then(|| fold_tag(body))
+}
+
+/// Longest username that can be mentioned
+pub const MAX_MENTION_CHARS: usize = 50;
+
+/// Whether `c` starts a mention: `@` or the full-width `＠`
+fn is_at_sign(c: char) -> bool {
+    c == '@' || c == '＠'
+}
+
+/// Mentionable usernames are ASCII letters, digits and `_`
+fn is_username_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Whether a mention may start right after `c`. Rules out email addresses
+/// like `bob@example.com` and runs like `@@alice`.
+fn can_precede_mention(c: char) -> bool {
+    !(c.is_alphanumeric() || is_at_sign(c) || matches!(c, '_' | '!' | '#' | '$' | '%' | '&' | '*'))
+}
+
+/// Finds every `@username` of a tweet, whether or not the user exists.
+/// A name running into another `@` (`@alice@example.com`) or a non-ASCII
+/// letter (`@josé`) is not a mention.
+pub fn mention_spans(text: &str) -> Vec<EntitySpan> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut spans = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let preceded_ok = i == 0 || can_precede_mention(chars[i - 1]);
+        if !is_at_sign(chars[i]) || !preceded_ok {
+            i += 1;
+            continue;
+        }
+
+        let start = i + 1;
+        let mut end = start;
+        while end < chars.len() && is_username_char(chars[end]) {
+            end += 1;
+        }
+
+        let followed_ok =
+            end == chars.len() || !(is_at_sign(chars[end]) || chars[end].is_alphanumeric());
+        let length = end - start;
+        if length > 0 && length <= MAX_MENTION_CHARS && followed_ok {
+            spans.push(EntitySpan {
+                text: chars[start..end].iter().collect(),
+                start: i,
+                end,
+            });
+        }
+
+        i = end.max(i + 1);
+    }
+
+    spans
+}
+
+/// Extracts the distinct usernames mentioned in a tweet, lowercased
+pub fn extract_mentions(text: &str) -> Vec<String> {
+    let mut usernames: Vec<String> = Vec::new();
+    for span in mention_spans(text) {
+        let username = span.text.to_lowercase();
+        if !usernames.contains(&username) {
+            usernames.push(username);
+        }
+    }
+    usernames
+}
@@ -0,0 +1,63 @@
+use super::{Event, EventHub};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use uuid::Uuid;
+
+/// Events buffered per connection before new ones are dropped
+const CONNECTION_BUFFER: usize = 64;
+
+/// Hub delivering events to the connections of this process
+#[derive(Default)]
+pub struct InProcessHub {
+    subscribers: Mutex<HashMap<Uuid, Vec<mpsc::Sender<Event>>>>,
+}
+
+impl InProcessHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EventHub for InProcessHub {
+    fn publish(&self, recipients: &[Uuid], event: Event) {
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+
+        for recipient in recipients {
+            let Some(senders) = subscribers.get_mut(recipient) else {
+                continue;
+            };
+
+            senders.retain(|sender| match sender.try_send(event.clone()) {
+                Ok(()) | Err(TrySendError::Full(_)) => true,
+                Err(TrySendError::Closed(_)) => false,
+            });
+            if senders.is_empty() {
+                subscribers.remove(recipient);
+            }
+        }
+    }
+
+    fn subscribe(&self, user_id: Uuid) -> mpsc::Receiver<Event> {
+        let (sender, receiver) = mpsc::channel(CONNECTION_BUFFER);
+
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        let senders = subscribers.entry(user_id).or_default();
+        senders.retain(|sender| !sender.is_closed());
+        senders.push(sender);
+
+        receiver
+    }
+
+    fn prune(&self, user_id: Uuid) {
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(senders) = subscribers.get_mut(&user_id) {
+            senders.retain(|sender| !sender.is_closed());
+            if senders.is_empty() {
+                subscribers.remove(&user_id);
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+mod in_process;
+
+pub use in_process::InProcessHub;
+
+/// Something that happened for a user, pushed to their `GET /stream` connections.
+/// Events carry IDs only: they stay small enough for a Postgres `NOTIFY` payload,
+/// and each connection loads what it delivers as its own user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A tweet for the recipient's home timeline
+    Tweet { tweet_id: Uuid },
+    /// A notification for the recipient
+    Notification { notification_id: Uuid },
+    /// A direct message in one of the recipient's conversations
+    Message { message_id: Uuid },
+}
+
+impl Event {
+    /// Name of the event as sent to clients
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Tweet { .. } => "tweet",
+            Event::Notification { .. } => "notification",
+            Event::Message { .. } => "message",
+        }
+    }
+}
+
+/// Publish/subscribe hub connecting write paths to streaming connections.
+/// Handlers receive it as `web::Data<dyn EventHub>`.
+///
+/// [`InProcessHub`] only reaches connections to the same process. To run several
+/// instances, a Postgres implementation would `NOTIFY` the serialized event and
+/// its recipients in `publish`, and deliver what a `LISTEN` loop receives to its
+/// local subscribers exactly as [`InProcessHub`] does.
+pub trait EventHub: Send + Sync {
+    /// Sends an event to every connection of each recipient. Never blocks: events
+    /// for a connection that is too far behind are dropped.
+    fn publish(&self, recipients: &[Uuid], event: Event);
+
+    /// Subscribes a connection of `user_id`. It is unsubscribed when the receiver is dropped.
+    fn subscribe(&self, user_id: Uuid) -> mpsc::Receiver<Event>;
+
+    /// Forgets the connections of `user_id` whose receivers were closed or dropped.
+    /// Called when a connection ends, so idle users do not keep dead senders around.
+    fn prune(&self, user_id: Uuid);
+}
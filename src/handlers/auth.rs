@@ -1,14 +1,101 @@
-use crate::crypto::PasswordService;
+use crate::crypto::{PasswordService, TokenService};
 use crate::database::DbPool;
-use crate::jwt::{AuthenticatedUser, create_jwt};
+use crate::jwt::{
+    ACCESS_TOKEN_TTL_SECS, AUTH_COOKIE, AuthenticatedUser, CSRF_COOKIE, REFRESH_COOKIE,
+    REFRESH_TOKEN_TTL_DAYS, create_jwt, verify_csrf,
+};
 use crate::models::users::NewUser;
+use crate::repositories::refresh_tokens::{
+    RotationResult, create_refresh_token_repo, revoke_all_user_tokens_repo, revoke_family_repo,
+    rotate_refresh_token_repo,
+};
 use crate::repositories::users::{
     does_user_exist, find_user_by_id, find_user_by_username_or_email, insert_user,
 };
-use crate::requests::users::{LoginRequest, RegisterRequest};
+use crate::requests::users::{LoginRequest, RefreshRequest, RegisterRequest};
+use chrono::Duration;
+use std::env;
 use uuid::Uuid;
 
-use actix_web::{Error, HttpResponse, web};
+use actix_web::cookie::{Cookie, SameSite, time};
+use actix_web::{Error, HttpRequest, HttpResponse, web};
+
+/// Whether auth cookies get the `Secure` attribute (disable only for plain-HTTP development)
+fn cookies_secure() -> bool {
+    env::var("COOKIE_SECURE")
+        .map(|v| v != "false" && v != "0")
+        .unwrap_or(true)
+}
+
+/// Builds an auth cookie; `http_only` is false only for the CSRF cookie,
+/// which scripts must read to echo it back in a header.
+fn auth_cookie(
+    name: &'static str,
+    value: String,
+    path: &'static str,
+    max_age: time::Duration,
+    http_only: bool,
+) -> Cookie<'static> {
+    Cookie::build(name, value)
+        .path(path)
+        .http_only(http_only)
+        .secure(cookies_secure())
+        .same_site(SameSite::Strict)
+        .max_age(max_age)
+        .finish()
+}
+
+/// Builds the response for a freshly issued token pair.
+/// Browser clients get the tokens as cookies plus a CSRF token; other clients get them in the body.
+fn token_response(
+    user_id: &Uuid,
+    session_id: &Uuid,
+    refresh_token: String,
+    use_cookies: bool,
+) -> Result<HttpResponse, Error> {
+    let access_token = create_jwt(&user_id.to_string(), session_id)
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Token creation error"))?;
+
+    if !use_cookies {
+        return Ok(HttpResponse::Ok().json(serde_json::json!({
+            "token": access_token,
+            "refresh_token": refresh_token,
+            "token_type": "Bearer",
+            "expires_in": ACCESS_TOKEN_TTL_SECS
+        })));
+    }
+
+    let csrf_token = TokenService::generate_token();
+    let refresh_max_age = time::Duration::days(REFRESH_TOKEN_TTL_DAYS);
+
+    Ok(HttpResponse::Ok()
+        .cookie(auth_cookie(
+            AUTH_COOKIE,
+            access_token,
+            "/",
+            time::Duration::seconds(ACCESS_TOKEN_TTL_SECS as i64),
+            true,
+        ))
+        .cookie(auth_cookie(REFRESH_COOKIE, refresh_token, "/auth", refresh_max_age, true))
+        .cookie(auth_cookie(CSRF_COOKIE, csrf_token.clone(), "/", refresh_max_age, false))
+        .json(serde_json::json!({
+            "csrf_token": csrf_token,
+            "expires_in": ACCESS_TOKEN_TTL_SECS
+        })))
+}
+
+/// Builds a response that clears all auth cookies
+fn clear_cookies_response() -> HttpResponse {
+    let mut response = HttpResponse::NoContent();
+
+    for (name, path) in [(AUTH_COOKIE, "/"), (REFRESH_COOKIE, "/auth"), (CSRF_COOKIE, "/")] {
+        let mut cookie = Cookie::build(name, "").path(path).finish();
+        cookie.make_removal();
+        response.cookie(cookie);
+    }
+
+    response.finish()
+}
 
 /// Handles user registration
 pub async fn register(
@@ -44,7 +131,6 @@ pub async fn register(
         password_hash,
         name: user.name,
         bio: user.bio,
-        avatar_url: user.avatar_url,
     };
 
     // Insert the new user into the database
@@ -78,14 +164,26 @@ pub async fn login(
             .map_err(|_| actix_web::error::ErrorUnauthorized("Invalid credentials"))?;
 
         if valid {
-            // Generate a JWT token containing the user ID as the subject
-            let token = create_jwt(&user.id.to_string())
-                .map_err(|_| actix_web::error::ErrorInternalServerError("Token creation error"))?;
-
-            // Return the token as a JSON response body
-            return Ok(HttpResponse::Ok()
-                .content_type("application/json")
-                .body(format!(r#"{{"token":"{}"}}"#, token)));
+            // Start a new session with a refresh token; only its hash is stored
+            let refresh_token = TokenService::generate_token();
+            let session = create_refresh_token_repo(
+                &pool,
+                &user.id,
+                &TokenService::hash_token(&refresh_token),
+                Duration::days(REFRESH_TOKEN_TTL_DAYS),
+            )
+            .map_err(|e| {
+                eprintln!("Database create refresh token error: {}", e);
+                actix_web::error::ErrorInternalServerError("Database error")
+            })?;
+
+            // Return the access token (subject = user ID) and refresh token
+            return token_response(
+                &user.id,
+                &session.family_id,
+                refresh_token,
+                login_data.use_cookies,
+            );
         }
     }
 
@@ -95,6 +193,88 @@ pub async fn login(
     ))
 }
 
+/// Exchanges a refresh token for a new token pair.
+/// The presented token is rotated; reusing an old one revokes the whole session.
+/// Browser clients may send the token as a cookie instead, which requires the CSRF header.
+pub async fn refresh(
+    pool: web::Data<DbPool>,
+    req: HttpRequest,
+    request: Option<web::Json<RefreshRequest>>,
+) -> Result<HttpResponse, Error> {
+    let body_token = request.and_then(|r| r.into_inner().refresh_token);
+
+    let (presented_token, use_cookies) = match body_token {
+        Some(token) => (token, false),
+        None => {
+            let cookie = req
+                .cookie(REFRESH_COOKIE)
+                .ok_or_else(|| actix_web::error::ErrorUnauthorized("Missing refresh token"))?;
+
+            if !verify_csrf(&req) {
+                return Err(actix_web::error::ErrorForbidden(
+                    "Missing or invalid CSRF token",
+                ));
+            }
+
+            (cookie.value().to_string(), true)
+        }
+    };
+
+    let new_refresh_token = TokenService::generate_token();
+
+    let result = rotate_refresh_token_repo(
+        &pool,
+        &TokenService::hash_token(&presented_token),
+        &TokenService::hash_token(&new_refresh_token),
+        Duration::days(REFRESH_TOKEN_TTL_DAYS),
+    )
+    .map_err(|e| {
+        eprintln!("Database rotate refresh token error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    match result {
+        RotationResult::Rotated(token) => {
+            token_response(&token.user_id, &token.family_id, new_refresh_token, use_cookies)
+        }
+        RotationResult::ReuseDetected => Err(actix_web::error::ErrorUnauthorized(
+            "Refresh token reuse detected, session revoked",
+        )),
+        RotationResult::Invalid => Err(actix_web::error::ErrorUnauthorized(
+            "Invalid or expired refresh token",
+        )),
+    }
+}
+
+/// Revokes the current session
+pub async fn logout(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    revoke_family_repo(&pool, &user.session_id).map_err(|e| {
+        eprintln!("Database revoke session error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    Ok(clear_cookies_response())
+}
+
+/// Revokes every session of the current user
+pub async fn logout_all(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+
+    revoke_all_user_tokens_repo(&pool, &user_id).map_err(|e| {
+        eprintln!("Database revoke sessions error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    Ok(clear_cookies_response())
+}
+
 /// Example protected endpoint which requires a valid JWT token.
 /// Returns the user ID extracted from the token.
 pub async fn protected_route(user: AuthenticatedUser) -> Result<HttpResponse, Error> {
@@ -121,13 +301,15 @@ pub async fn get_current_user(
     Ok(HttpResponse::Ok()
         .content_type("application/json")
         .body(format!(
-            r#"{{"id":"{}","username":"{}","email":"{}","name":{},"bio":{},"avatar_url":{},"created_at":"{}"}}"#,
+            r#"{{"id":"{}","username":"{}","email":"{}","name":{},"bio":{},"avatar_url":{},"created_at":"{}","sensitive_media":"{}","dm_policy":"{}"}}"#,
             db_user.id,
             db_user.username,
             db_user.email,
             db_user.name.as_deref().map(|s| format!("\"{}\"", s)).unwrap_or_else(|| "null".to_string()),
             db_user.bio.as_deref().map(|s| format!("\"{}\"", s)).unwrap_or_else(|| "null".to_string()),
             db_user.avatar_url.as_deref().map(|s| format!("\"{}\"", s)).unwrap_or_else(|| "null".to_string()),
-            db_user.created_at
+            db_user.created_at,
+            db_user.sensitive_media,
+            db_user.dm_policy
         )))
 }
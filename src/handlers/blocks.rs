@@ -0,0 +1,52 @@
+use crate::database::DbPool;
+use crate::jwt::AuthenticatedUser;
+use crate::repositories::blocks::{block_user_repo, unblock_user_repo};
+use actix_web::{Error, HttpResponse, web};
+use uuid::Uuid;
+
+/// Block a user
+pub async fn block_user(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, Error> {
+    // Parse user_id from JWT token
+    let blocker_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+
+    let blocked_id = path.into_inner();
+    if blocked_id == blocker_id {
+        return Err(actix_web::error::ErrorBadRequest("You cannot block yourself"));
+    }
+
+    let block = block_user_repo(&pool, &blocker_id, &blocked_id).map_err(|e| {
+        eprintln!("Database block error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .json(block))
+}
+
+/// Unblock a user
+pub async fn unblock_user(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, Error> {
+    // Parse user_id from JWT token
+    let blocker_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+
+    let blocked_id = path.into_inner();
+
+    let unblocked = unblock_user_repo(&pool, &blocker_id, &blocked_id).map_err(|e| {
+        eprintln!("Database unblock error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .json(unblocked))
+}
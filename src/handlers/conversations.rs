@@ -0,0 +1,400 @@
+use crate::database::DbPool;
+use crate::events::{Event, EventHub};
+use crate::jwt::AuthenticatedUser;
+use crate::media_urls::MediaUrls;
+use crate::models::conversations::{Conversation, ConversationMember, ConversationSummary};
+use crate::pagination::{clamp_per_page, parse_cursor};
+use crate::repositories::blocks::is_blocked_between_repo;
+use crate::repositories::conversations::{
+    create_group_repo, dm_policy_allows_repo, get_conversations_repo, get_member_ids_repo,
+    get_membership_repo, get_messages_repo, get_or_create_direct_conversation_repo,
+    has_sent_message_repo, hydrate_messages_repo, leave_conversation_repo, mark_conversation_read_repo,
+    send_message_repo, set_conversation_muted_repo, summarize_conversations_repo,
+    unread_conversations_count_repo,
+};
+use crate::repositories::media::AttachMediaError;
+use crate::repositories::users::find_user_by_id;
+use crate::requests::conversations::{
+    CreateConversationRequest, MarkConversationReadRequest, SendMessageRequest,
+};
+use crate::requests::optional_json;
+use crate::requests::tweets::CursorQuery;
+use actix_web::{Error, HttpResponse, web};
+use serde_json::json;
+use uuid::Uuid;
+
+/// Maximum number of members of a group, its creator included
+const MAX_GROUP_MEMBERS: usize = 50;
+
+/// Maximum length of a group name, in characters
+const MAX_GROUP_NAME_CHARS: usize = 50;
+
+/// Maximum length of a message, in characters
+const MAX_MESSAGE_CHARS: usize = 10_000;
+
+fn parse_user_id(user: &AuthenticatedUser) -> Result<Uuid, Error> {
+    Uuid::parse_str(&user.user_id).map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))
+}
+
+/// Gets the conversation if the user is a current member; 404 otherwise
+fn membership(
+    pool: &DbPool,
+    conversation_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<(Conversation, ConversationMember), Error> {
+    get_membership_repo(pool, conversation_id, user_id)
+        .map_err(|e| {
+            eprintln!("Database get conversation error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Conversation not found"))
+}
+
+/// Refuses with 403 if either user blocked the other
+fn check_not_blocked(pool: &DbPool, sender: &Uuid, recipient: &Uuid) -> Result<(), Error> {
+    let blocked = is_blocked_between_repo(pool, sender, recipient).map_err(|e| {
+        eprintln!("Database check block error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+    if blocked {
+        return Err(actix_web::error::ErrorForbidden("You cannot message this user"));
+    }
+
+    Ok(())
+}
+
+/// Refuses with 403 if either user blocked the other, or the recipient only
+/// accepts messages from people they follow and does not follow the sender
+fn check_can_message(pool: &DbPool, sender: &Uuid, recipient: &Uuid) -> Result<(), Error> {
+    check_not_blocked(pool, sender, recipient)?;
+
+    let allowed = dm_policy_allows_repo(pool, sender, recipient).map_err(|e| {
+        eprintln!("Database check message permission error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+    if !allowed {
+        return Err(actix_web::error::ErrorForbidden("You cannot message this user"));
+    }
+
+    Ok(())
+}
+
+fn summarize(
+    pool: &DbPool,
+    urls: &MediaUrls,
+    conversations: Vec<Conversation>,
+    user_id: &Uuid,
+) -> Result<Vec<ConversationSummary>, Error> {
+    summarize_conversations_repo(pool, urls, conversations, user_id).map_err(|e| {
+        eprintln!("Database summarize conversations error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })
+}
+
+/// Gets the user's conversations, most recently active first
+pub async fn list_conversations(
+    pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: AuthenticatedUser,
+    query: web::Query<CursorQuery>,
+) -> Result<HttpResponse, Error> {
+    let user_id = parse_user_id(&user)?;
+
+    let cursor = parse_cursor(query.cursor.as_deref())?;
+    let per_page = clamp_per_page(query.per_page);
+
+    let page = get_conversations_repo(&pool, &user_id, cursor.as_ref(), per_page).map_err(|e| {
+        eprintln!("Database get conversations error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "conversations": summarize(&pool, &urls, page.items, &user_id)?,
+        "next_cursor": page.next_cursor,
+        "prev_cursor": page.prev_cursor
+    })))
+}
+
+/// Starts a conversation. With one other participant this is the one-to-one
+/// conversation between the two users, returned as is if it already exists;
+/// with more it is a new group.
+pub async fn create_conversation(
+    pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: AuthenticatedUser,
+    request: web::Json<CreateConversationRequest>,
+) -> Result<HttpResponse, Error> {
+    let user_id = parse_user_id(&user)?;
+    let request = request.into_inner();
+
+    let mut participant_ids: Vec<Uuid> = Vec::new();
+    for participant in request.participant_ids {
+        if participant != user_id && !participant_ids.contains(&participant) {
+            participant_ids.push(participant);
+        }
+    }
+    if participant_ids.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "participant_ids must include another user",
+        ));
+    }
+    if participant_ids.len() + 1 > MAX_GROUP_MEMBERS {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "A group can have at most {} members",
+            MAX_GROUP_MEMBERS
+        )));
+    }
+
+    let name = request
+        .name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    if name.as_ref().is_some_and(|name| name.chars().count() > MAX_GROUP_NAME_CHARS) {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Group name cannot exceed {} characters",
+            MAX_GROUP_NAME_CHARS
+        )));
+    }
+    if name.is_some() && participant_ids.len() == 1 {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Only group conversations can have a name",
+        ));
+    }
+
+    for participant in &participant_ids {
+        let exists = find_user_by_id(&pool, participant).map_err(|e| {
+            eprintln!("Database find user error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?;
+        if exists.is_none() {
+            return Err(actix_web::error::ErrorNotFound(format!(
+                "User {} not found",
+                participant
+            )));
+        }
+        check_can_message(&pool, &user_id, participant)?;
+    }
+
+    let (conversation, created) = match participant_ids.as_slice() {
+        [other] => get_or_create_direct_conversation_repo(&pool, &user_id, other),
+        members => create_group_repo(&pool, &user_id, name, members).map(|c| (c, true)),
+    }
+    .map_err(|e| {
+        eprintln!("Database create conversation error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    let summary = summarize(&pool, &urls, vec![conversation], &user_id)?.pop();
+    let mut response = if created {
+        HttpResponse::Created()
+    } else {
+        HttpResponse::Ok()
+    };
+
+    Ok(response.json(summary))
+}
+
+/// Gets one of the user's conversations
+pub async fn get_conversation(
+    pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, Error> {
+    let user_id = parse_user_id(&user)?;
+    let (conversation, _) = membership(&pool, &path.into_inner(), &user_id)?;
+
+    Ok(HttpResponse::Ok().json(summarize(&pool, &urls, vec![conversation], &user_id)?.pop()))
+}
+
+/// Gets the messages of a conversation, newest first
+pub async fn get_messages(
+    pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    query: web::Query<CursorQuery>,
+) -> Result<HttpResponse, Error> {
+    let user_id = parse_user_id(&user)?;
+    let (conversation, _) = membership(&pool, &path.into_inner(), &user_id)?;
+
+    let cursor = parse_cursor(query.cursor.as_deref())?;
+    let per_page = clamp_per_page(query.per_page);
+
+    let db_error = |e: diesel::result::Error| {
+        eprintln!("Database get messages error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    };
+
+    let page = get_messages_repo(&pool, &conversation.id, &user_id, cursor.as_ref(), per_page)
+        .map_err(db_error)?;
+    let messages = hydrate_messages_repo(&pool, &urls, page.items, &user_id).map_err(db_error)?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "messages": messages,
+        "next_cursor": page.next_cursor,
+        "prev_cursor": page.prev_cursor
+    })))
+}
+
+/// Sends a message with optional media to a conversation
+pub async fn send_message(
+    pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    events: web::Data<dyn EventHub>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    request: web::Json<SendMessageRequest>,
+) -> Result<HttpResponse, Error> {
+    let user_id = parse_user_id(&user)?;
+    let (conversation, _) = membership(&pool, &path.into_inner(), &user_id)?;
+
+    if request.content.trim().is_empty() && request.media_ids.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "A message needs content or media",
+        ));
+    }
+    if request.content.chars().count() > MAX_MESSAGE_CHARS {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Message cannot exceed {} characters",
+            MAX_MESSAGE_CHARS
+        )));
+    }
+
+    let db_error = |e: diesel::result::Error| {
+        eprintln!("Database send message error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    };
+
+    let member_ids = get_member_ids_repo(&pool, &conversation.id).map_err(db_error)?;
+
+    // Blocks are checked on every one-to-one message. The recipient's dm_policy
+    // only applies until they have written in the conversation, so they can
+    // always be answered. In groups, messages between users who blocked each
+    // other are hidden from them instead.
+    if !conversation.is_group {
+        for member in member_ids.iter().filter(|member| **member != user_id) {
+            if has_sent_message_repo(&pool, &conversation.id, member).map_err(db_error)? {
+                check_not_blocked(&pool, &user_id, member)?;
+            } else {
+                check_can_message(&pool, &user_id, member)?;
+            }
+        }
+    }
+
+    let message = send_message_repo(
+        &pool,
+        &conversation.id,
+        &user_id,
+        &request.content,
+        &request.media_ids,
+    )
+    .map_err(|e| match e {
+        AttachMediaError::Invalid(message) => actix_web::error::ErrorBadRequest(message),
+        AttachMediaError::Database(e) => db_error(e),
+    })?;
+
+    events.publish(&member_ids, Event::Message { message_id: message.id });
+
+    let message = hydrate_messages_repo(&pool, &urls, vec![message], &user_id)
+        .map_err(db_error)?
+        .pop();
+
+    Ok(HttpResponse::Created().json(message))
+}
+
+/// Marks a conversation as read up to the given message, or its latest message
+pub async fn mark_conversation_read(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let user_id = parse_user_id(&user)?;
+    let request: Option<MarkConversationReadRequest> = optional_json(&body)?;
+    let (conversation, _) = membership(&pool, &path.into_inner(), &user_id)?;
+
+    let up_to = request.and_then(|r| r.up_to);
+
+    let last_read_at = mark_conversation_read_repo(&pool, &conversation.id, &user_id, up_to.as_ref())
+        .map_err(|e| {
+            eprintln!("Database mark conversation read error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Message not found"))?;
+
+    Ok(HttpResponse::Ok().json(json!({ "last_read_at": last_read_at })))
+}
+
+/// Leaves a group conversation
+pub async fn leave_conversation(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, Error> {
+    let user_id = parse_user_id(&user)?;
+    let (conversation, _) = membership(&pool, &path.into_inner(), &user_id)?;
+
+    if !conversation.is_group {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Only group conversations can be left",
+        ));
+    }
+
+    leave_conversation_repo(&pool, &conversation.id, &user_id).map_err(|e| {
+        eprintln!("Database leave conversation error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Mutes a conversation
+pub async fn mute_conversation(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, Error> {
+    set_muted(&pool, &user, &path.into_inner(), true)
+}
+
+/// Unmutes a conversation
+pub async fn unmute_conversation(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, Error> {
+    set_muted(&pool, &user, &path.into_inner(), false)
+}
+
+fn set_muted(
+    pool: &DbPool,
+    user: &AuthenticatedUser,
+    conversation_id: &Uuid,
+    muted: bool,
+) -> Result<HttpResponse, Error> {
+    let user_id = parse_user_id(user)?;
+    let (conversation, _) = membership(pool, conversation_id, &user_id)?;
+
+    set_conversation_muted_repo(pool, &conversation.id, &user_id, muted).map_err(|e| {
+        eprintln!("Database mute conversation error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({ "muted": muted })))
+}
+
+/// Gets the number of unmuted conversations with unread messages
+pub async fn get_unread_conversations_count(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    let user_id = parse_user_id(&user)?;
+
+    let unread_count = unread_conversations_count_repo(&pool, &user_id).map_err(|e| {
+        eprintln!("Database count unread conversations error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({ "unread_count": unread_count })))
+}
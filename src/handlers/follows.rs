@@ -1,5 +1,7 @@
 use crate::database::DbPool;
 use crate::jwt::AuthenticatedUser;
+use crate::notifications::NotificationService;
+use crate::repositories::blocks::is_blocked_between_repo;
 use crate::repositories::followers::{follow_user_repo, is_followed_repo, unfollow_user_repo};
 use actix_web::{Error, HttpResponse, web};
 use uuid::Uuid;
@@ -7,6 +9,7 @@ use uuid::Uuid;
 /// Follow a user
 pub async fn follow_user(
     pool: web::Data<DbPool>,
+    notifications: web::Data<NotificationService>,
     user: AuthenticatedUser,
     path: web::Path<Uuid>,
 ) -> Result<HttpResponse, Error> {
@@ -16,12 +19,23 @@ pub async fn follow_user(
 
     let followed_id = path.into_inner();
 
+    // Users who blocked each other cannot follow
+    let blocked = is_blocked_between_repo(&pool, &follower_id, &followed_id).map_err(|e| {
+        eprintln!("Database check block error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+    if blocked {
+        return Err(actix_web::error::ErrorForbidden("You cannot follow this user"));
+    }
+
     // Follow user
     let follow = follow_user_repo(&pool, &follower_id, &followed_id).map_err(|e| {
         eprintln!("Database follow error: {}", e);
         actix_web::error::ErrorInternalServerError("Database error")
     })?;
 
+    notifications.user_followed(&follower_id, &followed_id);
+
     Ok(HttpResponse::Ok()
         .content_type("application/json")
         .json(follow))
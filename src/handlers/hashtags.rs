@@ -0,0 +1,53 @@
+use crate::database::DbPool;
+use crate::entities::normalize_hashtag;
+use crate::handlers::tweets::hydrate_tweets;
+use crate::jwt::AuthenticatedUser;
+use crate::media_urls::MediaUrls;
+use crate::pagination::{clamp_per_page, parse_cursor};
+use crate::repositories::hashtags::{get_hashtag_tweets_repo, get_trends_repo};
+use crate::requests::tweets::CursorQuery;
+use actix_web::{Error, HttpResponse, web};
+use serde_json::json;
+use uuid::Uuid;
+
+/// Gets the tweets with a hashtag, newest first.
+/// The tag is matched case-insensitively, with or without the `#`.
+pub async fn get_hashtag_tweets(
+    pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: Option<AuthenticatedUser>,
+    path: web::Path<String>,
+    query: web::Query<CursorQuery>,
+) -> Result<HttpResponse, Error> {
+    let tag = normalize_hashtag(&path.into_inner())
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("Invalid hashtag"))?;
+
+    let viewer_id = user
+        .as_ref()
+        .and_then(|u| Uuid::parse_str(&u.user_id).ok());
+    let cursor = parse_cursor(query.cursor.as_deref())?;
+    let per_page = clamp_per_page(query.per_page);
+
+    let page = get_hashtag_tweets_repo(&pool, &tag, viewer_id.as_ref(), cursor.as_ref(), per_page)
+        .map_err(|e| {
+            eprintln!("Database get hashtag tweets error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "hashtag": tag,
+        "tweets": hydrate_tweets(&pool, &urls, page.items, user.as_ref())?,
+        "next_cursor": page.next_cursor,
+        "prev_cursor": page.prev_cursor
+    })))
+}
+
+/// Gets the trending hashtags, as last computed by the trends job
+pub async fn get_trends(pool: web::Data<DbPool>) -> Result<HttpResponse, Error> {
+    let trends = get_trends_repo(&pool).map_err(|e| {
+        eprintln!("Database get trends error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({ "trends": trends })))
+}
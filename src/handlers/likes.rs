@@ -1,12 +1,14 @@
 use crate::database::DbPool;
 use crate::repositories::likes::{like_tweet_repo, delete_like_repo, get_likes_repo};
 use crate::jwt::AuthenticatedUser;
+use crate::notifications::NotificationService;
 use actix_web::{HttpResponse, web, Error};
 use uuid::Uuid;
 
 /// Creates a like
 pub async fn like_tweet(
     pool: web::Data<DbPool>,
+    notifications: web::Data<NotificationService>,
     user: AuthenticatedUser,
     path: web::Path<Uuid>,
 ) -> Result<HttpResponse, Error> {
@@ -23,6 +25,8 @@ pub async fn like_tweet(
             actix_web::error::ErrorInternalServerError("Database error")
         })?;
 
+    notifications.tweet_liked(&user_uuid, &tweet_id);
+
     Ok(HttpResponse::Ok()
         .content_type("application/json")
         .json(like))
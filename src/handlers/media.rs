@@ -1,57 +1,706 @@
-use actix_web::{web, HttpResponse, Error};
+use actix_web::http::header;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use futures_util::StreamExt;
 use crate::jwt::AuthenticatedUser;
 use uuid::Uuid;
 use mime_guess::from_path;
-use crate::storage::S3Storage;
+use crate::storage::{
+    MediaStorage, SNIFF_LEN, blob_key, detect_media_type, expected_media_type, extension_for,
+    SavedFile, hash_object, max_upload_bytes, media_quota, save_file, stream_object, upload_key,
+};
 use std::path::Path;
 use crate::database::DbPool;
-use crate::repositories::media::upload_media_repo;
+use crate::repositories::media::{
+    BlobSource, CreateMediaError, complete_pending_media_repo, create_pending_media_repo, delete_pending_media_repo,
+    DeleteMediaResult, delete_media_repo, delete_storage_objects, get_blob_repo, get_media_repo,
+    get_media_tweet_repo, get_user_media_repo, media_access_repo, media_usage_repo,
+    update_media_repo, upload_media_repo,
+};
+use crate::images::process_stored_image;
+use crate::models::media::{
+    MEDIA_STATUS_READY, Media, MediaDetails, MediaEntity, MediaUpdate, NewMediaBlob,
+};
+use crate::pagination::{clamp_per_page, parse_cursor};
+use crate::requests::tweets::CursorQuery;
+use crate::media_urls::{MediaUrls, MediaVariant};
+use crate::requests::media::{CreateUploadRequest, SignedMediaQuery, UpdateMediaRequest};
+use chrono::Utc;
+use serde_json::json;
+use std::time::Duration;
+
+/// Checks that the user may store one more file and returns the bytes left in their quota
+fn remaining_quota(pool: &DbPool, user_id: &Uuid) -> Result<i64, Error> {
+    let (bytes_used, files_used) = media_usage_repo(pool, user_id).map_err(|e| {
+        eprintln!("Database media usage error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+    let (max_bytes, max_files) = media_quota();
+
+    if files_used >= max_files {
+        return Err(actix_web::error::ErrorForbidden(format!(
+            "Media quota exceeded: at most {} files",
+            max_files
+        )));
+    }
+    if bytes_used >= max_bytes {
+        return Err(actix_web::error::ErrorForbidden(format!(
+            "Media quota exceeded: at most {} bytes",
+            max_bytes
+        )));
+    }
+
+    Ok(max_bytes - bytes_used)
+}
 
 /// Uploads a media file
 pub async fn upload_media(
     pool: web::Data<DbPool>,
-    storage: web::Data<S3Storage>,
+    storage: web::Data<dyn MediaStorage>,
+    urls: web::Data<MediaUrls>,
     user: AuthenticatedUser,
-    req: actix_web::HttpRequest,
+    req: HttpRequest,
     payload: web::Payload,
 ) -> Result<HttpResponse, Error> {
     let user_id = Uuid::parse_str(&user.user_id)
         .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
     
     // Get the original filename from the request headers
-    let original_name = req.headers().get("X-Filename").unwrap().to_str().unwrap();
+    let original_name = req
+        .headers()
+        .get("X-Filename")
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("Missing X-Filename header"))?;
 
     // Check file extension
-    let allowed_exts = ["png", "jpg", "jpeg", "gif", "mp4", "mov", "webm"];
     let ext = Path::new(original_name)
         .extension()
         .and_then(|e| e.to_str())
         .map(|e| e.to_lowercase())
         .unwrap_or_default();
-    if !allowed_exts.contains(&ext.as_str()) {
-        return Err(actix_web::error::ErrorBadRequest(
+    let mime_type = expected_media_type(&ext).ok_or_else(|| {
+        actix_web::error::ErrorBadRequest(
+            "Only .png, .jpg, .jpeg, .gif, .mp4, .mov, .webm files are allowed",
+        )
+    })?;
+
+    // Stream file to storage, enforcing the size limit for its type and the remaining quota.
+    // The content is sniffed and must really be the type the extension claims.
+    let max_bytes = max_upload_bytes(mime_type).min(remaining_quota(&pool, &user_id)?);
+    let saved = save_file(storage.get_ref(), &req, payload, user_id, original_name, mime_type, max_bytes).await?;
+
+    // Store the content once under its hash and save metadata to database.
+    // The streamed copy is only needed until then.
+    let media = save_uploaded_media(&pool, storage.get_ref(), &user_id, original_name, &saved).await;
+    delete_storage_objects(&pool, storage.get_ref(), vec![saved.key]).await;
+    let media = media?;
+
+    // The returned ID is what clients pass as `media_ids` when tweeting
+    Ok(HttpResponse::Ok().json(MediaEntity::new(&media, &urls)))
+}
+
+/// Creates the media record for a streamed upload, storing its content as a blob
+async fn save_uploaded_media(
+    pool: &DbPool,
+    storage: &dyn MediaStorage,
+    user_id: &Uuid,
+    original_name: &str,
+    saved: &SavedFile,
+) -> Result<Media, Error> {
+    let mut blob = store_blob(pool, storage, &saved.key, &saved.sha256, saved.content_type, saved.size).await?;
+    let mut media = upload_media_repo(pool, user_id, original_name, &blob, media_quota());
+
+    // The blob was released since it was looked up: store the content again
+    if matches!(media, Err(CreateMediaError::Database(diesel::result::Error::NotFound))) {
+        blob = BlobSource::New(
+            store_new_blob(storage, &saved.key, &saved.sha256, saved.content_type, saved.size).await?,
+        );
+        media = upload_media_repo(pool, user_id, original_name, &blob, media_quota());
+    }
+
+    let used_key = media.as_ref().ok().map(|m| m.s3_key.as_str());
+    discard_unused_blob(pool, storage, &blob, used_key).await;
+
+    media.map_err(create_media_error)
+}
+
+/// Removes the objects of a blob this request stored unless the media row points at them:
+/// saving the row failed, or another upload of the same content created the blob first
+async fn discard_unused_blob(
+    pool: &DbPool,
+    storage: &dyn MediaStorage,
+    blob: &BlobSource,
+    used_key: Option<&str>,
+) {
+    if let BlobSource::New(blob) = blob
+        && used_key != Some(blob.storage_key.as_str())
+    {
+        delete_storage_objects(pool, storage, blob.storage_keys()).await;
+    }
+}
+
+/// Quota errors are refused with 403; the rest are database errors
+fn create_media_error(e: CreateMediaError) -> Error {
+    match e {
+        CreateMediaError::Database(e) => {
+            eprintln!("Database create media error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        }
+        e => actix_web::error::ErrorForbidden(e.to_string()),
+    }
+}
+
+/// Lifetime of a presigned upload URL
+const UPLOAD_URL_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Time a direct upload has to be completed before the sweeper removes it
+const PENDING_UPLOAD_TTL_MINUTES: i64 = 60;
+
+/// Starts a direct upload: creates a pending media record and returns a presigned
+/// PUT URL bound to the file's type and exact size
+pub async fn create_upload(
+    pool: web::Data<DbPool>,
+    storage: web::Data<dyn MediaStorage>,
+    user: AuthenticatedUser,
+    upload: web::Json<CreateUploadRequest>,
+) -> Result<HttpResponse, Error> {
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+
+    // Check file extension
+    let ext = Path::new(&upload.file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    let mime_type = expected_media_type(&ext).ok_or_else(|| {
+        actix_web::error::ErrorBadRequest(
             "Only .png, .jpg, .jpeg, .gif, .mp4, .mov, .webm files are allowed",
-        ));
+        )
+    })?;
+
+    let max_bytes = max_upload_bytes(mime_type);
+    if upload.size_bytes <= 0 {
+        return Err(actix_web::error::ErrorBadRequest("size_bytes must be positive"));
+    }
+    if upload.size_bytes > max_bytes {
+        return Err(actix_web::error::ErrorPayloadTooLarge(format!(
+            "File exceeds the maximum size of {} bytes",
+            max_bytes
+        )));
+    }
+
+    let key = upload_key(user_id, &upload.file_name);
+    let upload_url = storage
+        .presign_upload(&key, mime_type, upload.size_bytes, UPLOAD_URL_TTL)
+        .await?;
+
+    let expires_at = Utc::now().naive_utc() + chrono::Duration::minutes(PENDING_UPLOAD_TTL_MINUTES);
+    // The declared size is reserved from the quota until the upload completes or expires
+    let media = create_pending_media_repo(&pool, &user_id, &key, &upload.file_name, mime_type, upload.size_bytes, expires_at, media_quota())
+        .map_err(create_media_error)?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "media_id": media.id,
+        "upload_url": upload_url,
+        "method": "PUT",
+        "headers": {
+            "Content-Type": mime_type,
+            "Content-Length": upload.size_bytes.to_string()
+        },
+        "expires_at": expires_at
+    })))
+}
+
+/// Finishes a direct upload: verifies the stored object and marks the media ready
+pub async fn complete_upload(
+    pool: web::Data<DbPool>,
+    storage: web::Data<dyn MediaStorage>,
+    urls: web::Data<MediaUrls>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, Error> {
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+    let media_id = path.into_inner();
+
+    let media = match get_media_repo(&pool, &media_id) {
+        Ok(media) if media.user_id == user_id => media,
+        Ok(_) | Err(diesel::result::Error::NotFound) => {
+            return Err(actix_web::error::ErrorNotFound("Media not found"));
+        }
+        Err(e) => {
+            eprintln!("Database get media error: {}", e);
+            return Err(actix_web::error::ErrorInternalServerError("Database error"));
+        }
+    };
+
+    // Completing twice is harmless
+    if media.status == MEDIA_STATUS_READY {
+        return Ok(HttpResponse::Ok().json(MediaEntity::new(&media, &urls)));
+    }
+    if media.expires_at.is_some_and(|at| at < Utc::now().naive_utc()) {
+        return Err(actix_web::error::ErrorGone("Upload expired"));
+    }
+
+    let info = storage
+        .head(&media.s3_key)
+        .await?
+        .ok_or_else(|| actix_web::error::ErrorConflict("File has not been uploaded yet"))?;
+
+    // The presigned URL already binds size and type; check anyway and sniff the content
+    let head = storage.get_range(&media.s3_key, 0..SNIFF_LEN as u64).await?;
+    let rejection = if info.size != media.size_bytes {
+        Some("Uploaded file size does not match the declared size")
+    } else if info.content_type.as_deref().is_some_and(|ct| ct != media.file_type) {
+        Some("Uploaded file type does not match the declared type")
+    } else if detect_media_type(&head) != Some(media.file_type.as_str()) {
+        Some("File content does not match the declared type")
+    } else {
+        None
+    };
+    if let Some(message) = rejection {
+        discard_pending_upload(&pool, storage.get_ref(), &media).await;
+        return Err(actix_web::error::ErrorBadRequest(message));
+    }
+
+    // Store the content once under its hash, as for regular uploads
+    let sha256 = hash_object(storage.clone().into_inner(), &media.s3_key, info.size).await?;
+    let mut blob = match store_blob(&pool, storage.get_ref(), &media.s3_key, &sha256, &media.file_type, info.size).await {
+        Ok(blob) => blob,
+        Err(e) => {
+            discard_pending_upload(&pool, storage.get_ref(), &media).await;
+            return Err(e);
+        }
+    };
+    let mut completed = complete_pending_media_repo(&pool, &media.id, &blob);
+
+    // The blob was released since it was looked up: store the content again
+    if matches!(completed, Err(diesel::result::Error::NotFound)) {
+        blob = match store_new_blob(storage.get_ref(), &media.s3_key, &sha256, &media.file_type, info.size).await {
+            Ok(blob) => BlobSource::New(blob),
+            Err(e) => {
+                discard_pending_upload(&pool, storage.get_ref(), &media).await;
+                return Err(e);
+            }
+        };
+        completed = complete_pending_media_repo(&pool, &media.id, &blob);
+    }
+
+    let used_key = completed.as_ref().ok().and_then(Option::as_ref).map(|m| m.s3_key.as_str());
+    discard_unused_blob(&pool, storage.get_ref(), &blob, used_key).await;
+
+    let completed = completed.map_err(|e| {
+        eprintln!("Database complete media error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    match completed {
+        Some(completed) => {
+            // The uploaded object was copied to the blob key
+            delete_storage_objects(&pool, storage.get_ref(), vec![media.s3_key]).await;
+            Ok(HttpResponse::Ok().json(MediaEntity::new(&completed, &urls)))
+        }
+        None => {
+            // Expired by the sweeper while we were verifying
+            discard_pending_upload(&pool, storage.get_ref(), &media).await;
+            Err(actix_web::error::ErrorGone("Upload expired"))
+        }
+    }
+}
+
+/// Removes a failed direct upload: its pending record and uploaded object
+async fn discard_pending_upload(pool: &DbPool, storage: &dyn MediaStorage, media: &Media) {
+    if let Err(e) = delete_pending_media_repo(pool, &media.id) {
+        eprintln!("Database delete pending media error: {}", e);
+    }
+
+    delete_storage_objects(pool, storage, vec![media.s3_key.clone()]).await;
+}
+
+/// Returns the blob for uploaded content stored at `source_key`, reusing an existing
+/// blob with the same hash
+async fn store_blob(
+    pool: &DbPool,
+    storage: &dyn MediaStorage,
+    source_key: &str,
+    sha256: &str,
+    content_type: &str,
+    size: i64,
+) -> Result<BlobSource, Error> {
+    let existing = get_blob_repo(pool, sha256).map_err(|e| {
+        eprintln!("Database get blob error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+    if existing.is_some() {
+        return Ok(BlobSource::Existing(sha256.to_string()));
+    }
+
+    store_new_blob(storage, source_key, sha256, content_type, size)
+        .await
+        .map(BlobSource::New)
+}
+
+/// Stores uploaded content under a new key for its hash, images going through
+/// the image pipeline first. The key and its renditions belong to this call only,
+/// so cleaning up after a failure never touches objects of another upload.
+async fn store_new_blob(
+    storage: &dyn MediaStorage,
+    source_key: &str,
+    sha256: &str,
+    content_type: &str,
+    size: i64,
+) -> Result<NewMediaBlob, Error> {
+    let target_key = blob_key(sha256, extension_for(content_type));
+
+    // Strip image metadata and generate renditions; videos are stored as uploaded
+    let (renditions, size) = if content_type.starts_with("image/") {
+        let (renditions, size) = process_stored_image(storage, source_key, &target_key, size).await?;
+        (Some(renditions), size)
+    } else {
+        storage.copy(source_key, &target_key).await?;
+        (None, size)
+    };
+
+    Ok(NewMediaBlob {
+        sha256: sha256.to_string(),
+        storage_key: target_key,
+        file_type: content_type.to_string(),
+        size_bytes: size,
+        width: renditions.as_ref().map(|r| r.width),
+        height: renditions.as_ref().map(|r| r.height),
+        thumb_key: renditions.as_ref().and_then(|r| r.thumb_key.clone()),
+        small_key: renditions.as_ref().and_then(|r| r.small_key.clone()),
+        large_key: renditions.and_then(|r| r.large_key),
+    })
+}
+
+/// Lists the current user's media, newest first
+pub async fn list_media(
+    pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: AuthenticatedUser,
+    query: web::Query<CursorQuery>,
+) -> Result<HttpResponse, Error> {
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+    let cursor = parse_cursor(query.cursor.as_deref())?;
+
+    let page = get_user_media_repo(&pool, &user_id, cursor.as_ref(), clamp_per_page(query.per_page))
+        .map_err(|e| {
+            eprintln!("Database get user media error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?;
+
+    let media: Vec<MediaDetails> = page
+        .items
+        .iter()
+        .map(|(media_item, tweet_id)| MediaDetails::new(media_item, *tweet_id, &urls))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "media": media,
+        "next_cursor": page.next_cursor,
+        "prev_cursor": page.prev_cursor
+    })))
+}
+
+/// Gets one of the current user's media items
+pub async fn get_media(
+    pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, Error> {
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+    let media_id = path.into_inner();
+
+    let media = match get_media_repo(&pool, &media_id) {
+        Ok(media) if media.user_id == user_id => media,
+        Ok(_) | Err(diesel::result::Error::NotFound) => {
+            return Err(actix_web::error::ErrorNotFound("Media not found"));
+        }
+        Err(e) => {
+            eprintln!("Database get media error: {}", e);
+            return Err(actix_web::error::ErrorInternalServerError("Database error"));
+        }
+    };
+
+    let tweet_id = get_media_tweet_repo(&pool, &media_id).map_err(|e| {
+        eprintln!("Database get media tweet error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    Ok(HttpResponse::Ok().json(MediaDetails::new(&media, tweet_id, &urls)))
+}
+
+/// Maximum length of alt text, in characters
+const MAX_ALT_TEXT_CHARS: usize = 1000;
+
+/// Sets the alt text and sensitive flag of one of the current user's media items
+pub async fn update_media(
+    pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    request: web::Json<UpdateMediaRequest>,
+) -> Result<HttpResponse, Error> {
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+    let media_id = path.into_inner();
+    let request = request.into_inner();
+
+    if request.alt_text.is_none() && request.sensitive.is_none() {
+        return Err(actix_web::error::ErrorBadRequest("Nothing to update"));
     }
 
-    // MIME type check (additional to extension)
-    let mime_type = from_path(original_name)
-        .first()
-        .map(|m| m.to_string())
-        .unwrap_or_else(|| "application/octet-stream".to_string());
-    if !mime_type.starts_with("image/") && !mime_type.starts_with("video/") {
-        return Err(actix_web::error::ErrorBadRequest("Only image and video files are allowed"));
+    // An empty alt text clears it
+    let alt_text = request.alt_text.map(|text| {
+        let text = text.trim().to_string();
+        (!text.is_empty()).then_some(text)
+    });
+    if alt_text
+        .as_ref()
+        .and_then(Option::as_ref)
+        .is_some_and(|text| text.chars().count() > MAX_ALT_TEXT_CHARS)
+    {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Alt text cannot exceed {} characters",
+            MAX_ALT_TEXT_CHARS
+        )));
     }
 
-    // Save file to S3
-    let (orig_name, key, _size, mime) = storage.save_file(&req, payload, user_id).await?;
+    let changes = MediaUpdate {
+        alt_text,
+        sensitive: request.sensitive,
+    };
+    let media = update_media_repo(&pool, &media_id, &user_id, &changes)
+        .map_err(|e| {
+            eprintln!("Database update media error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Media not found"))?;
+
+    let tweet_id = get_media_tweet_repo(&pool, &media_id).map_err(|e| {
+        eprintln!("Database get media tweet error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
 
-    // Save metadata to database
-    upload_media_repo(&pool, &user_id, &key, &orig_name, mime.as_deref().unwrap_or("application/octet-stream"))
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+    Ok(HttpResponse::Ok().json(MediaDetails::new(&media, tweet_id, &urls)))
+}
+
+/// Returns the current user's storage usage and quota
+pub async fn media_usage(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
 
-    let s3_url = format!("https://{}.s3.amazonaws.com/{}", storage.bucket_name, key);
+    let (bytes_used, files_used) = media_usage_repo(&pool, &user_id).map_err(|e| {
+        eprintln!("Database media usage error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+    let (bytes_limit, files_limit) = media_quota();
 
-    Ok(HttpResponse::Ok().json(s3_url))
+    Ok(HttpResponse::Ok().json(json!({
+        "bytes_used": bytes_used,
+        "bytes_limit": bytes_limit,
+        "files_used": files_used,
+        "files_limit": files_limit
+    })))
 }
 
+/// Deletes one of the current user's media items. Media attached to a tweet
+/// is refused; it is deleted together with the tweet. Media sent in a direct
+/// message is refused too.
+pub async fn delete_media(
+    pool: web::Data<DbPool>,
+    storage: web::Data<dyn MediaStorage>,
+    user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, Error> {
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+    let media_id = path.into_inner();
+
+    let result = delete_media_repo(&pool, &media_id, &user_id).map_err(|e| {
+        eprintln!("Database delete media error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    match result {
+        DeleteMediaResult::Deleted(keys) => {
+            delete_storage_objects(&pool, storage.get_ref(), keys).await;
+            Ok(HttpResponse::NoContent().finish())
+        }
+        DeleteMediaResult::Attached(tweet_id) => Ok(HttpResponse::Conflict().json(json!({
+            "error": "Media is attached to a tweet; delete the tweet instead.",
+            "tweet_id": tweet_id
+        }))),
+        DeleteMediaResult::AttachedToMessage => Ok(HttpResponse::Conflict().json(json!({
+            "error": "Media was sent in a direct message."
+        }))),
+        DeleteMediaResult::NotFound => Err(actix_web::error::ErrorNotFound("Media not found")),
+    }
+}
+
+/// Caching for public media; the file behind a media URL never changes
+const PUBLIC_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// Streams the original file of a media item
+pub async fn get_media_content(
+    pool: web::Data<DbPool>,
+    storage: web::Data<dyn MediaStorage>,
+    urls: web::Data<MediaUrls>,
+    user: Option<AuthenticatedUser>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    query: web::Query<SignedMediaQuery>,
+) -> Result<HttpResponse, Error> {
+    let media_id = path.into_inner();
+
+    stream_media(&pool, &storage, &urls, user.as_ref(), &req, &media_id, MediaVariant::Original, &query).await
+}
+
+/// Streams a rendition (`thumb`, `small`, `large`) of a media item
+pub async fn get_media_variant(
+    pool: web::Data<DbPool>,
+    storage: web::Data<dyn MediaStorage>,
+    urls: web::Data<MediaUrls>,
+    user: Option<AuthenticatedUser>,
+    req: HttpRequest,
+    path: web::Path<(Uuid, String)>,
+    query: web::Query<SignedMediaQuery>,
+) -> Result<HttpResponse, Error> {
+    let (media_id, variant) = path.into_inner();
+    let variant = MediaVariant::parse(&variant)
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Unknown media variant"))?;
+
+    stream_media(&pool, &storage, &urls, user.as_ref(), &req, &media_id, variant, &query).await
+}
+
+/// Streams a media file after checking access, honouring `If-None-Match` and single `Range` requests
+#[allow(clippy::too_many_arguments)]
+async fn stream_media(
+    pool: &DbPool,
+    storage: &web::Data<dyn MediaStorage>,
+    urls: &MediaUrls,
+    user: Option<&AuthenticatedUser>,
+    req: &HttpRequest,
+    media_id: &Uuid,
+    variant: MediaVariant,
+    query: &SignedMediaQuery,
+) -> Result<HttpResponse, Error> {
+    let not_found = || actix_web::error::ErrorNotFound("Media not found");
+
+    let media = match get_media_repo(pool, media_id) {
+        Ok(media) if media.status == MEDIA_STATUS_READY => media,
+        Ok(_) | Err(diesel::result::Error::NotFound) => return Err(not_found()),
+        Err(e) => {
+            eprintln!("Database get media error: {}", e);
+            return Err(actix_web::error::ErrorInternalServerError("Database error"));
+        }
+    };
+    let key = variant.key(&media).ok_or_else(not_found)?.to_string();
+
+    let viewer_id = user.and_then(|u| Uuid::parse_str(&u.user_id).ok());
+    let access = media_access_repo(pool, &media, viewer_id.as_ref()).map_err(|e| {
+        eprintln!("Database media access error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    // A valid signature grants access until it expires; otherwise the viewer must be allowed.
+    // In signed mode anonymous requests always need a signature.
+    let signed_expiry = match (query.expires, query.signature.as_deref()) {
+        (Some(expires), Some(signature)) if urls.verify(media_id, variant, expires, signature) => {
+            Some(expires)
+        }
+        _ => None,
+    };
+    if signed_expiry.is_none() {
+        if urls.is_signed() && viewer_id.is_none() {
+            return Err(actix_web::error::ErrorForbidden("Missing or invalid signature"));
+        }
+        if !access.can_view {
+            return Err(not_found());
+        }
+    }
+
+    let scope = if access.is_private { "private" } else { "public" };
+    let cache_control = match signed_expiry {
+        Some(expires) => format!("{}, max-age={}", scope, (expires - Utc::now().timestamp()).max(0)),
+        None if access.is_private => "private, no-cache".to_string(),
+        None => PUBLIC_CACHE_CONTROL.to_string(),
+    };
+    let etag = format!("\"{}-{}\"", media.id, variant.as_str());
+
+    let if_none_match = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok());
+    if if_none_match.is_some_and(|v| {
+        v.split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == etag)
+    }) {
+        return Ok(HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .insert_header((header::CACHE_CONTROL, cache_control))
+            .finish());
+    }
+
+    let size = storage.head(&key).await?.ok_or_else(not_found)?.size as u64;
+
+    let content_type = match variant {
+        MediaVariant::Original => media.file_type.clone(),
+        _ => from_path(&key)
+            .first()
+            .map(|m| m.to_string())
+            .unwrap_or_else(|| "application/octet-stream".to_string()),
+    };
+
+    // Only a single range is honoured, and only if `If-Range` (when sent) still matches
+    let if_range_matches = req
+        .headers()
+        .get(header::IF_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .is_none_or(|v| v.trim() == etag);
+    let range = req
+        .headers()
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<header::Range>().ok())
+        .filter(|_| if_range_matches);
+
+    let (mut builder, range) = match range {
+        Some(header::Range::Bytes(specs)) if specs.len() == 1 => {
+            let Some((start, end)) = specs[0].to_satisfiable_range(size) else {
+                return Ok(HttpResponse::RangeNotSatisfiable()
+                    .insert_header((header::CONTENT_RANGE, format!("bytes */{}", size)))
+                    .finish());
+            };
+
+            let mut response = HttpResponse::PartialContent();
+            response.insert_header((
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end, size),
+            ));
+            (response, start..end + 1)
+        }
+        _ => (HttpResponse::Ok(), 0..size),
+    };
+
+    let body = stream_object(storage.clone().into_inner(), key, range.clone())
+        .map(|chunk| chunk.map_err(|e| std::io::Error::other(e.to_string())));
+
+    Ok(builder
+        .content_type(content_type)
+        .insert_header((header::ETAG, etag))
+        .insert_header((header::CACHE_CONTROL, cache_control))
+        .insert_header((header::ACCEPT_RANGES, "bytes"))
+        // Browsers must not second-guess the sniffed type, e.g. render a video as HTML
+        .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .no_chunking(range.end - range.start)
+        .streaming(body))
+}
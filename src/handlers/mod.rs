@@ -1,7 +1,14 @@
 pub mod auth;
+pub mod blocks;
+pub mod conversations;
 pub mod follows;
+pub mod hashtags;
 pub mod likes;
 pub mod media;
+pub mod notifications;
+pub mod search;
+pub mod stream;
+pub mod timeline;
 pub mod tweets;
 pub mod users;
 
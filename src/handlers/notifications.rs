@@ -0,0 +1,132 @@
+use crate::database::DbPool;
+use crate::handlers::tweets::hydrate_tweets;
+use crate::jwt::AuthenticatedUser;
+use crate::media_urls::MediaUrls;
+use crate::models::notifications::{NotificationGroup, NotificationGroupRow};
+use crate::notifications::describe;
+use crate::pagination::{clamp_per_page, parse_cursor};
+use crate::repositories::notifications::{
+    get_group_actors_repo, get_notification_groups_repo, mark_notifications_read_repo,
+    unread_count_repo,
+};
+use crate::repositories::tweets::get_tweets_by_ids_repo;
+use crate::requests::notifications::MarkReadRequest;
+use crate::requests::optional_json;
+use crate::requests::tweets::CursorQuery;
+use actix_web::{Error, HttpResponse, web};
+use serde_json::json;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Hydrates notification groups with their recent actors, text and tweet for the recipient
+pub fn hydrate_notification_groups(
+    pool: &DbPool,
+    urls: &MediaUrls,
+    user: &AuthenticatedUser,
+    rows: Vec<NotificationGroupRow>,
+) -> Result<Vec<NotificationGroup>, Error> {
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+
+    let db_error = |e: diesel::result::Error| {
+        eprintln!("Database get notifications error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    };
+
+    let group_keys: Vec<String> = rows.iter().map(|row| row.group_key.clone()).collect();
+    let mut actors = get_group_actors_repo(pool, &user_id, &group_keys).map_err(db_error)?;
+
+    let tweet_ids: Vec<Uuid> = rows.iter().filter_map(|row| row.tweet_id).collect();
+    let tweets = get_tweets_by_ids_repo(pool, &tweet_ids).map_err(db_error)?;
+    let mut tweets: HashMap<Uuid, _> = hydrate_tweets(pool, urls, tweets, Some(user))?
+        .into_iter()
+        .map(|tweet| (tweet.tweet.id, tweet))
+        .collect();
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let group_actors = actors.remove(&row.group_key).unwrap_or_default();
+            NotificationGroup {
+                id: row.id,
+                text: describe(&row.kind, &group_actors, row.actors_count),
+                kind: row.kind,
+                actors: group_actors,
+                actors_count: row.actors_count,
+                tweet: row.tweet_id.and_then(|tweet_id| tweets.remove(&tweet_id)),
+                read: row.read_at.is_some(),
+                created_at: row.created_at,
+            }
+        })
+        .collect())
+}
+
+/// Gets the user's notifications, grouped and newest first
+pub async fn get_notifications(
+    pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: AuthenticatedUser,
+    query: web::Query<CursorQuery>,
+) -> Result<HttpResponse, Error> {
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+
+    let cursor = parse_cursor(query.cursor.as_deref())?;
+    let per_page = clamp_per_page(query.per_page);
+
+    let page = get_notification_groups_repo(&pool, &user_id, cursor.as_ref(), per_page)
+        .map_err(|e| {
+            eprintln!("Database get notifications error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?;
+    let groups = hydrate_notification_groups(&pool, &urls, &user, page.items)?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "notifications": groups,
+        "next_cursor": page.next_cursor,
+        "prev_cursor": page.prev_cursor
+    })))
+}
+
+/// Marks notifications as read up to the given one, or all of them
+pub async fn mark_notifications_read(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+
+    // An empty body marks everything read; a body that does not parse is rejected
+    let request: Option<MarkReadRequest> = optional_json(&body)?;
+    let up_to = request.and_then(|r| r.up_to);
+
+    mark_notifications_read_repo(&pool, &user_id, up_to.as_ref())
+        .map_err(|e| {
+            eprintln!("Database mark notifications read error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Notification not found"))?;
+
+    unread_count_response(&pool, &user_id)
+}
+
+/// Gets the number of notification groups with unread notifications
+pub async fn get_unread_count(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+
+    unread_count_response(&pool, &user_id)
+}
+
+fn unread_count_response(pool: &DbPool, user_id: &Uuid) -> Result<HttpResponse, Error> {
+    let unread_count = unread_count_repo(pool, user_id).map_err(|e| {
+        eprintln!("Database count unread notifications error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({ "unread_count": unread_count })))
+}
@@ -0,0 +1,55 @@
+use crate::database::DbPool;
+use crate::handlers::tweets::hydrate_tweets;
+use crate::jwt::AuthenticatedUser;
+use crate::media_urls::MediaUrls;
+use crate::pagination::{clamp_per_page, parse_rank_cursor};
+use crate::repositories::search::search_tweets_repo;
+use crate::requests::search::SearchTweetsQuery;
+use crate::search_query::SearchQuery;
+use actix_web::{Error, HttpResponse, web};
+use serde_json::json;
+use uuid::Uuid;
+
+/// Maximum length of a search query, in characters
+const MAX_QUERY_CHARS: usize = 500;
+
+/// Searches tweets by text and operators, best match first.
+/// Supports `"exact phrases"`, `-excluded` words, `or`, and operators such as
+/// `from:`, `to:`, `since:`, `until:`, `has:media`, `filter:retweets`, `min_likes:` and `lang:`.
+pub async fn search_tweets(
+    pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: Option<AuthenticatedUser>,
+    query: web::Query<SearchTweetsQuery>,
+) -> Result<HttpResponse, Error> {
+    let text = query.q.trim();
+    if text.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest("Query parameter q is required"));
+    }
+    if text.chars().count() > MAX_QUERY_CHARS {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Query cannot exceed {} characters",
+            MAX_QUERY_CHARS
+        )));
+    }
+
+    let search =
+        SearchQuery::parse(text).map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+
+    let viewer_id = user
+        .as_ref()
+        .and_then(|u| Uuid::parse_str(&u.user_id).ok());
+    let cursor = parse_rank_cursor(query.cursor.as_deref())?;
+    let per_page = clamp_per_page(query.per_page);
+
+    let page = search_tweets_repo(&pool, &search, viewer_id.as_ref(), cursor.as_ref(), per_page)
+        .map_err(|e| {
+            eprintln!("Database search tweets error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "tweets": hydrate_tweets(&pool, &urls, page.items, user.as_ref())?,
+        "next_cursor": page.next_cursor
+    })))
+}
@@ -0,0 +1,264 @@
+use crate::database::DbPool;
+use crate::events::{Event, EventHub};
+use crate::handlers::notifications::hydrate_notification_groups;
+use crate::handlers::tweets::hydrate_tweets;
+use crate::jwt::AuthenticatedUser;
+use crate::media_urls::MediaUrls;
+use crate::repositories::blocks::is_blocked_between_repo;
+use crate::repositories::conversations::{
+    get_membership_repo, get_message_repo, hydrate_messages_repo,
+};
+use crate::repositories::notifications::get_notification_group_repo;
+use crate::repositories::refresh_tokens::is_session_active_repo;
+use crate::repositories::tweets::get_tweets_by_ids_repo;
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::{Error, HttpRequest, HttpResponse, web};
+use actix_ws::Message;
+use futures_util::{StreamExt, stream};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use uuid::Uuid;
+
+/// Silence after which a keep-alive is sent, so proxies keep the connection
+/// open and dead WebSocket peers are noticed
+const KEEP_ALIVE: Duration = Duration::from_secs(25);
+
+/// How often a connection checks that its session was not logged out or revoked
+const SESSION_CHECK: Duration = KEEP_ALIVE;
+
+/// What a connection sends next
+enum Delivery {
+    /// An event's name and its payload as seen by the subscriber
+    Event(&'static str, Value),
+    KeepAlive,
+}
+
+/// A user's connection to the event hub
+struct Subscription {
+    receiver: mpsc::Receiver<Event>,
+    loader: Arc<EventLoader>,
+    events: web::Data<dyn EventHub>,
+    session_checked_at: Instant,
+}
+
+impl Subscription {
+    /// Waits for the next event to deliver, or a keep-alive after [`KEEP_ALIVE`] of silence.
+    /// `None` ends the connection: the hub closed it, or the session is no longer active.
+    async fn next(&mut self) -> Option<Delivery> {
+        loop {
+            if self.session_checked_at.elapsed() >= SESSION_CHECK {
+                if !self.session_active().await {
+                    return None;
+                }
+                self.session_checked_at = Instant::now();
+            }
+
+            match timeout(KEEP_ALIVE, self.receiver.recv()).await {
+                Err(_) => return Some(Delivery::KeepAlive),
+                Ok(None) => return None,
+                Ok(Some(event)) => {
+                    // Loading queries the database, so it runs on the blocking thread pool
+                    let loader = self.loader.clone();
+                    let name = event.name();
+                    match web::block(move || loader.load(&event)).await {
+                        Ok(Some(payload)) => return Some(Delivery::Event(name, payload)),
+                        Ok(None) => {}
+                        Err(e) => eprintln!("Stream event loading error: {}", e),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether the session the stream was opened with is still active
+    async fn session_active(&self) -> bool {
+        let pool = self.loader.pool.clone();
+        let session_id = self.loader.user.session_id;
+
+        match web::block(move || is_session_active_repo(&pool, &session_id)).await {
+            Ok(Ok(active)) => active,
+            Ok(Err(e)) => {
+                eprintln!("Database session check error: {}", e);
+                false
+            }
+            Err(e) => {
+                eprintln!("Session check error: {}", e);
+                false
+            }
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        // Closing the receiver first lets the hub see that this connection is gone
+        self.receiver.close();
+        self.events.prune(self.loader.user_id);
+    }
+}
+
+/// Loads events for the subscriber of a connection
+struct EventLoader {
+    pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: AuthenticatedUser,
+    user_id: Uuid,
+}
+
+impl EventLoader {
+    /// Loads an event's payload for the subscriber: a tweet as returned by
+    /// `GET /tweets/{id}`, a group as returned by `GET /notifications`, or a
+    /// message as returned by `GET /conversations/{id}/messages`.
+    /// `None` if it is gone, e.g. the tweet was deleted in the meantime.
+    fn load(&self, event: &Event) -> Option<Value> {
+        let payload = match event {
+            Event::Tweet { tweet_id } => get_tweets_by_ids_repo(&self.pool, &[*tweet_id])
+                .map_err(|e| {
+                    eprintln!("Database get tweet error: {}", e);
+                    actix_web::error::ErrorInternalServerError("Database error")
+                })
+                .and_then(|tweets| hydrate_tweets(&self.pool, &self.urls, tweets, Some(&self.user)))
+                .map(|mut tweets| tweets.pop().map(|tweet| json!(tweet))),
+            Event::Notification { notification_id } => {
+                get_notification_group_repo(&self.pool, &self.user_id, notification_id)
+                    .map_err(|e| {
+                        eprintln!("Database get notification error: {}", e);
+                        actix_web::error::ErrorInternalServerError("Database error")
+                    })
+                    .and_then(|row| {
+                        hydrate_notification_groups(&self.pool, &self.urls, &self.user, row.into_iter().collect())
+                    })
+                    .map(|mut groups| groups.pop().map(|group| json!(group)))
+            }
+            Event::Message { message_id } => self.load_message(message_id).map_err(|e| {
+                eprintln!("Database get message error: {}", e);
+                actix_web::error::ErrorInternalServerError("Database error")
+            }),
+        };
+
+        // Errors are logged where they happen; the event is skipped
+        payload.ok().flatten()
+    }
+
+    /// Loads a message unless the subscriber left its conversation since it was
+    /// sent, or the sender and the subscriber blocked one another
+    fn load_message(&self, message_id: &Uuid) -> Result<Option<Value>, diesel::result::Error> {
+        let Some(message) = get_message_repo(&self.pool, message_id)? else {
+            return Ok(None);
+        };
+        if get_membership_repo(&self.pool, &message.conversation_id, &self.user_id)?.is_none() {
+            return Ok(None);
+        }
+        if is_blocked_between_repo(&self.pool, &message.sender_id, &self.user_id)? {
+            return Ok(None);
+        }
+
+        let mut hydrated = hydrate_messages_repo(&self.pool, &self.urls, vec![message], &self.user_id)?;
+        Ok(hydrated.pop().map(|message| json!(message)))
+    }
+}
+
+/// Streams new home timeline tweets, notifications and direct messages to the user.
+/// Requests with `Upgrade: websocket` get a WebSocket sending each event as a
+/// `{"type", "data"}` text message; others get Server-Sent Events.
+pub async fn stream(
+    req: HttpRequest,
+    body: web::Payload,
+    pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    events: web::Data<dyn EventHub>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+
+    let is_websocket = req
+        .headers()
+        .get(header::UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+
+    let subscription = Subscription {
+        receiver: events.subscribe(user_id),
+        loader: Arc::new(EventLoader {
+            pool,
+            urls,
+            user,
+            user_id,
+        }),
+        events,
+        session_checked_at: Instant::now(),
+    };
+
+    if is_websocket {
+        websocket(&req, body, subscription)
+    } else {
+        Ok(server_sent_events(subscription))
+    }
+}
+
+/// Sends events as `event: <type>` / `data: <json>` frames and keep-alives as comments
+fn server_sent_events(subscription: Subscription) -> HttpResponse {
+    let frames = stream::unfold(subscription, |mut subscription| async move {
+        let frame = match subscription.next().await? {
+            Delivery::Event(name, data) => format!("event: {}\ndata: {}\n\n", name, data),
+            Delivery::KeepAlive => ": keep-alive\n\n".to_string(),
+        };
+        Some((Ok::<_, Error>(Bytes::from(frame)), subscription))
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(frames)
+}
+
+/// Forwards events as text messages and keep-alives as pings, and answers the client's pings
+fn websocket(
+    req: &HttpRequest,
+    body: web::Payload,
+    mut subscription: Subscription,
+) -> Result<HttpResponse, Error> {
+    let (response, mut session, mut messages) = actix_ws::handle(req, body)?;
+
+    let mut outgoing = session.clone();
+    actix_web::rt::spawn(async move {
+        while let Some(delivery) = subscription.next().await {
+            let sent = match delivery {
+                Delivery::Event(name, data) => {
+                    outgoing
+                        .text(json!({ "type": name, "data": data }).to_string())
+                        .await
+                }
+                Delivery::KeepAlive => outgoing.ping(b"").await,
+            };
+            // The session is closed; dropping the subscription unsubscribes it
+            if sent.is_err() {
+                return;
+            }
+        }
+        // The subscription ended, e.g. because the session was revoked
+        let _ = outgoing.close(None).await;
+    });
+
+    actix_web::rt::spawn(async move {
+        while let Some(Ok(message)) = messages.next().await {
+            match message {
+                Message::Ping(bytes) if session.pong(&bytes).await.is_err() => return,
+                Message::Close(reason) => {
+                    let _ = session.close(reason).await;
+                    return;
+                }
+                _ => {}
+            }
+        }
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
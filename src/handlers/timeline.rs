@@ -0,0 +1,89 @@
+use crate::database::DbPool;
+use crate::handlers::tweets::hydrate_tweets;
+use crate::jwt::AuthenticatedUser;
+use crate::pagination::{PaginationMode, clamp_per_page, parse_cursor, parse_page};
+use crate::repositories::mentions::get_mentions_timeline_repo;
+use crate::repositories::tweets::{get_home_timeline_cursor_repo, get_home_timeline_repo};
+use crate::requests::tweets::{CursorQuery, TweetsQuery};
+use crate::media_urls::MediaUrls;
+use actix_web::{Error, HttpResponse, web};
+use uuid::Uuid;
+
+/// Gets the home timeline: own tweets and tweets of followed users
+pub async fn home_timeline(
+    pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: AuthenticatedUser,
+    query: web::Query<TweetsQuery>,
+) -> Result<HttpResponse, Error> {
+    // Parse user_id from JWT token
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+
+    let per_page = clamp_per_page(query.per_page);
+
+    // Legacy page-based mode
+    if query.pagination_mode() == PaginationMode::Page {
+        let page = parse_page(query.page)?;
+        let (tweets, total_count) = get_home_timeline_repo(&pool, &user_id, page, per_page)
+            .map_err(|e| {
+            eprintln!("Database get home timeline error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?;
+
+        // Calculate pagination info
+        let total_pages = (total_count + per_page - 1) / per_page;
+
+        return Ok(HttpResponse::Ok().json(serde_json::json!({
+            "tweets": hydrate_tweets(&pool, &urls, tweets, Some(&user))?,
+            "pagination": {
+                "page": page,
+                "per_page": per_page,
+                "total_count": total_count,
+                "total_pages": total_pages,
+                "has_next": page < total_pages,
+                "has_prev": page > 1
+            }
+        })));
+    }
+
+    let cursor = parse_cursor(query.cursor.as_deref())?;
+
+    let page =
+        get_home_timeline_cursor_repo(&pool, &user_id, cursor.as_ref(), per_page).map_err(|e| {
+            eprintln!("Database get home timeline error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "tweets": hydrate_tweets(&pool, &urls, page.items, Some(&user))?,
+        "next_cursor": page.next_cursor,
+        "prev_cursor": page.prev_cursor
+    })))
+}
+
+/// Gets the mentions timeline: other users' tweets that mention the user, newest first
+pub async fn mentions_timeline(
+    pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: AuthenticatedUser,
+    query: web::Query<CursorQuery>,
+) -> Result<HttpResponse, Error> {
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+
+    let cursor = parse_cursor(query.cursor.as_deref())?;
+    let per_page = clamp_per_page(query.per_page);
+
+    let page =
+        get_mentions_timeline_repo(&pool, &user_id, cursor.as_ref(), per_page).map_err(|e| {
+            eprintln!("Database get mentions timeline error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "tweets": hydrate_tweets(&pool, &urls, page.items, Some(&user))?,
+        "next_cursor": page.next_cursor,
+        "prev_cursor": page.prev_cursor
+    })))
+}
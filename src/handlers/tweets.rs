@@ -1,17 +1,90 @@
 use crate::database::DbPool;
+use crate::events::{Event, EventHub};
 use crate::jwt::AuthenticatedUser;
+use crate::models::tweets::{Tweet, TweetWithStats, normalize_lang};
+use crate::pagination::{PaginationMode, clamp_per_page, parse_cursor, parse_page};
+use crate::repositories::followers::get_follower_ids_repo;
+use crate::repositories::media::AttachMediaError;
 use crate::repositories::tweets::{
-    create_reply_repo, create_retweet_repo, create_tweet_repo, delete_tweet_repo, get_replies_repo, get_tweet_repo, get_tweets_repo,
+    create_reply_repo, create_retweet_repo, create_tweet_repo, delete_tweet_repo, get_replies_repo, get_tweet_repo, get_tweets_cursor_repo, get_tweets_repo,
+    hydrate_tweets_repo,
 };
-use crate::requests::tweets::{CreateRetweetRequest, CreateTweetRequest, TweetsQuery};
+use crate::requests::tweets::{CreateRetweetRequest, CreateTweetRequest, CursorQuery, TweetsQuery};
 use actix_web::{Error, HttpResponse, web};
 use serde_json::json;
 use uuid::Uuid;
-use crate::storage::S3Storage;
+use crate::media_urls::MediaUrls;
+use crate::notifications::NotificationService;
+use crate::storage::MediaStorage;
+
+/// Hydrates tweets with authors and stats for the given viewer
+pub fn hydrate_tweets(
+    pool: &DbPool,
+    urls: &MediaUrls,
+    tweets: Vec<Tweet>,
+    viewer: Option<&AuthenticatedUser>,
+) -> Result<Vec<TweetWithStats>, Error> {
+    let viewer_id = viewer.and_then(|u| Uuid::parse_str(&u.user_id).ok());
+
+    hydrate_tweets_repo(pool, urls, tweets, viewer_id.as_ref()).map_err(|e| {
+        eprintln!("Database hydrate tweets error: {}", e);
+        actix_web::error::ErrorInternalServerError("Database error")
+    })
+}
+
+/// Hydrates a single tweet for the given viewer
+pub fn hydrate_tweet(
+    pool: &DbPool,
+    urls: &MediaUrls,
+    tweet: Tweet,
+    viewer: Option<&AuthenticatedUser>,
+) -> Result<TweetWithStats, Error> {
+    hydrate_tweets(pool, urls, vec![tweet], viewer)?
+        .pop()
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Tweet not found"))
+}
+
+/// Maps a failed tweet creation to 400 for invalid media and 500 otherwise
+fn attach_media_error(e: AttachMediaError) -> Error {
+    match e {
+        AttachMediaError::Invalid(message) => actix_web::error::ErrorBadRequest(message),
+        AttachMediaError::Database(e) => {
+            eprintln!("Database create tweet error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        }
+    }
+}
+
+/// Validates the optional language code of a new tweet or reply
+fn tweet_lang(request: &CreateTweetRequest) -> Result<Option<String>, Error> {
+    request
+        .lang
+        .as_deref()
+        .map(|code| {
+            normalize_lang(code).ok_or_else(|| {
+                actix_web::error::ErrorBadRequest("lang must be a two-letter ISO 639-1 code")
+            })
+        })
+        .transpose()
+}
+
+/// Publishes a new tweet to the home timelines streaming it: the author's and their followers'
+fn publish_tweet(pool: &DbPool, events: &dyn EventHub, tweet: &Tweet) {
+    match get_follower_ids_repo(pool, &tweet.user_id) {
+        Ok(mut recipients) => {
+            recipients.push(tweet.user_id);
+            events.publish(&recipients, Event::Tweet { tweet_id: tweet.id });
+        }
+        Err(e) => eprintln!("Database get follower ids error: {}", e),
+    }
+}
 
 /// Creates a tweet
 pub async fn create_tweet(
     pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    notifications: web::Data<NotificationService>,
+    events: web::Data<dyn EventHub>,
     user: AuthenticatedUser,
     tweet: web::Json<CreateTweetRequest>,
 ) -> Result<HttpResponse, Error> {
@@ -19,33 +92,65 @@ pub async fn create_tweet(
     let user_id = Uuid::parse_str(&user.user_id)
         .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
 
-    let media_urls = tweet.media_urls.clone().map(|urls| urls.into_iter().map(|url| url.map(|url| url.to_string())).collect());
+    let lang = tweet_lang(&tweet)?;
 
-    // Create tweet
-    let tweet = create_tweet_repo(&pool, &user_id, &tweet.content, media_urls).map_err(|e| {
-        eprintln!("Database create tweet error: {}", e);
-        actix_web::error::ErrorInternalServerError("Database error")
-    })?;
+    // Create tweet with its media attached
+    let tweet = create_tweet_repo(
+        &pool,
+        &user_id,
+        &tweet.content,
+        lang.as_deref(),
+        &tweet.media_ids,
+    )
+    .map_err(attach_media_error)?;
 
-    Ok(HttpResponse::Ok().json(tweet))
+    notifications.tweet_created(&tweet);
+    publish_tweet(&pool, events.get_ref(), &tweet);
+
+    Ok(HttpResponse::Ok().json(hydrate_tweet(&pool, &urls, tweet, Some(&user))?))
 }
 
 /// Gets tweets
 pub async fn get_tweets(
     pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: Option<AuthenticatedUser>,
     query: web::Query<TweetsQuery>,
 ) -> Result<HttpResponse, Error> {
-    let tweets = get_tweets_repo(&pool, query.page, query.per_page).map_err(|e| {
+    // Legacy page-based mode
+    if query.pagination_mode() == PaginationMode::Page {
+        let page = parse_page(query.page)?;
+        let per_page = clamp_per_page(query.per_page);
+        let (tweets, total_count) = get_tweets_repo(&pool, page, per_page).map_err(|e| {
+            eprintln!("Database get tweets error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?;
+
+        let tweets = hydrate_tweets(&pool, &urls, tweets, user.as_ref())?;
+
+        return Ok(HttpResponse::Ok().json((tweets, total_count)));
+    }
+
+    let cursor = parse_cursor(query.cursor.as_deref())?;
+    let per_page = clamp_per_page(query.per_page);
+
+    let page = get_tweets_cursor_repo(&pool, cursor.as_ref(), per_page).map_err(|e| {
         eprintln!("Database get tweets error: {}", e);
         actix_web::error::ErrorInternalServerError("Database error")
     })?;
 
-    Ok(HttpResponse::Ok().json(tweets))
+    Ok(HttpResponse::Ok().json(json!({
+        "tweets": hydrate_tweets(&pool, &urls, page.items, user.as_ref())?,
+        "next_cursor": page.next_cursor,
+        "prev_cursor": page.prev_cursor
+    })))
 }
 
 /// Gets a tweet
 pub async fn get_tweet(
     pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: Option<AuthenticatedUser>,
     path: web::Path<Uuid>,
 ) -> Result<HttpResponse, Error> {
     let tweet_id = path.into_inner();
@@ -55,7 +160,7 @@ pub async fn get_tweet(
         actix_web::error::ErrorInternalServerError("Database error")
     })?;
 
-    Ok(HttpResponse::Ok().json(tweet))
+    Ok(HttpResponse::Ok().json(hydrate_tweet(&pool, &urls, tweet, user.as_ref())?))
 }
 
 /// Deletes a tweet
@@ -63,7 +168,7 @@ pub async fn delete_tweet(
     pool: web::Data<DbPool>,
     user: AuthenticatedUser,
     path: web::Path<Uuid>,
-    s3_client: web::Data<S3Storage>,
+    storage: web::Data<dyn MediaStorage>,
 ) -> Result<HttpResponse, Error> {
     let tweet_id = path.into_inner();
 
@@ -84,13 +189,16 @@ pub async fn delete_tweet(
     }
 
     // Delete tweet
-    let tweet = delete_tweet_repo(&pool, &tweet_id, &s3_client).await?;
+    let tweet = delete_tweet_repo(&pool, &tweet_id, storage.get_ref()).await?;
 
     Ok(HttpResponse::Ok().json(tweet))
 }
 
 pub async fn reply_to_tweet(
     pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    notifications: web::Data<NotificationService>,
+    events: web::Data<dyn EventHub>,
     user: AuthenticatedUser,
     path: web::Path<Uuid>,
     reply: web::Json<CreateTweetRequest>,
@@ -114,33 +222,56 @@ pub async fn reply_to_tweet(
     let user_uuid = Uuid::parse_str(&user.user_id)
         .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
 
+    let lang = tweet_lang(&reply)?;
+
     // Create reply
-    let reply = create_reply_repo(&pool, &tweet_id, &user_uuid, &reply.content).map_err(|e| {
-        eprintln!("Database create reply error: {}", e);
-        actix_web::error::ErrorInternalServerError("Database error")
-    })?;
+    let reply = create_reply_repo(
+        &pool,
+        &tweet_id,
+        &user_uuid,
+        &reply.content,
+        lang.as_deref(),
+        &reply.media_ids,
+    )
+    .map_err(attach_media_error)?;
 
-    Ok(HttpResponse::Ok().json(reply))
+    notifications.tweet_created(&reply);
+    publish_tweet(&pool, events.get_ref(), &reply);
+
+    Ok(HttpResponse::Ok().json(hydrate_tweet(&pool, &urls, reply, Some(&user))?))
 }
 
 /// Gets replies to a tweet
 pub async fn get_replies(
     pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    user: Option<AuthenticatedUser>,
     path: web::Path<Uuid>,
+    query: web::Query<CursorQuery>,
 ) -> Result<HttpResponse, Error> {
     let tweet_id = path.into_inner();
 
-    let replies = get_replies_repo(&pool, &tweet_id).map_err(|e| {
+    let cursor = parse_cursor(query.cursor.as_deref())?;
+    let per_page = clamp_per_page(query.per_page);
+
+    let page = get_replies_repo(&pool, &tweet_id, cursor.as_ref(), per_page).map_err(|e| {
         eprintln!("Database get replies error: {}", e);
         actix_web::error::ErrorInternalServerError("Database error")
     })?;
 
-    Ok(HttpResponse::Ok().json(replies))
+    Ok(HttpResponse::Ok().json(json!({
+        "tweets": hydrate_tweets(&pool, &urls, page.items, user.as_ref())?,
+        "next_cursor": page.next_cursor,
+        "prev_cursor": page.prev_cursor
+    })))
 }
 
 /// Creates a retweet
 pub async fn retweet_tweet(
     pool: web::Data<DbPool>,
+    urls: web::Data<MediaUrls>,
+    notifications: web::Data<NotificationService>,
+    events: web::Data<dyn EventHub>,
     user: AuthenticatedUser,
     path: web::Path<Uuid>,
     create_retweet_request: web::Json<CreateRetweetRequest>,
@@ -157,5 +288,8 @@ pub async fn retweet_tweet(
         actix_web::error::ErrorInternalServerError("Database error")
     })?;
 
-    Ok(HttpResponse::Ok().json(retweet))
+    notifications.tweet_created(&retweet);
+    publish_tweet(&pool, events.get_ref(), &retweet);
+
+    Ok(HttpResponse::Ok().json(hydrate_tweet(&pool, &urls, retweet, Some(&user))?))
 }
\ No newline at end of file
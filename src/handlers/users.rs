@@ -1,11 +1,28 @@
 use crate::database::DbPool;
 use crate::jwt::AuthenticatedUser;
 use crate::models::users::User;
-use crate::models::users::{UserPublic, UserUpdate};
+use crate::models::users::{
+    DM_POLICY_EVERYONE, DM_POLICY_FOLLOWING, ProfileImage, SENSITIVE_MEDIA_BLUR,
+    SENSITIVE_MEDIA_HIDE, SENSITIVE_MEDIA_SHOW, UserPublic, UserUpdate,
+};
+use crate::pagination::{PaginationMode, clamp_per_page, parse_cursor, parse_page};
 use crate::repositories::followers::{get_followers_repo, get_followings_repo};
-use crate::repositories::users::{delete_user_repo, find_user_by_id, get_users, update_user_repo};
-use crate::requests::users::UsersQuery;
-use actix_web::{Error, HttpResponse, web};
+use crate::repositories::users::{
+    delete_user_repo, find_user_by_id, get_users, get_users_cursor, replace_profile_image_repo,
+    update_user_repo,
+};
+use crate::images::process_profile_image;
+use crate::media_urls::MediaUrls;
+use crate::repositories::media::delete_storage_objects;
+use crate::requests::tweets::CursorQuery;
+use crate::requests::users::{ProfileImageQuery, UsersQuery};
+use crate::storage::{
+    MediaStorage, detect_media_type, expected_media_type, max_upload_bytes, profile_image_key,
+    read_payload,
+};
+use actix_web::http::header;
+use actix_web::{Error, HttpRequest, HttpResponse, web};
+use std::path::Path;
 use uuid::Uuid;
 
 /// Get paginated list of users with optional search
@@ -13,8 +30,14 @@ pub async fn list_users(
     pool: web::Data<DbPool>,
     query: web::Query<UsersQuery>,
 ) -> Result<HttpResponse, Error> {
+    if query.pagination_mode() == PaginationMode::Cursor {
+        return list_users_by_cursor(pool, query.into_inner()).await;
+    }
+    let page = parse_page(query.page)?;
+    let per_page = clamp_per_page(query.per_page);
+
     let (users_list, total_count) =
-        get_users(&pool, query.page, query.per_page, query.search.as_deref()).map_err(|e| {
+        get_users(&pool, page, per_page, query.search.as_deref()).map_err(|e| {
             eprintln!("Database query error: {}", e);
             actix_web::error::ErrorInternalServerError("Database error")
         })?;
@@ -26,16 +49,16 @@ pub async fn list_users(
         .collect();
 
     // Calculate pagination info
-    let total_pages = (total_count + query.per_page - 1) / query.per_page;
-    let has_next = query.page < total_pages;
-    let has_prev = query.page > 1;
+    let total_pages = (total_count + per_page - 1) / per_page;
+    let has_next = page < total_pages;
+    let has_prev = page > 1;
 
     // Build response
     let response = serde_json::json!({
         "users": public_users,
         "pagination": {
-            "page": query.page,
-            "per_page": query.per_page,
+            "page": page,
+            "per_page": per_page,
             "total_count": total_count,
             "total_pages": total_pages,
             "has_next": has_next,
@@ -48,6 +71,29 @@ pub async fn list_users(
         .json(response))
 }
 
+/// Get cursor-paginated list of users with optional search
+async fn list_users_by_cursor(
+    pool: web::Data<DbPool>,
+    query: UsersQuery,
+) -> Result<HttpResponse, Error> {
+    let cursor = parse_cursor(query.cursor.as_deref())?;
+    let per_page = clamp_per_page(query.per_page);
+
+    let page = get_users_cursor(&pool, cursor.as_ref(), per_page, query.search.as_deref())
+        .map_err(|e| {
+            eprintln!("Database query error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?;
+
+    let page = page.map(|users_list| users_list.into_iter().map(UserPublic::from).collect());
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "users": page.items,
+        "next_cursor": page.next_cursor,
+        "prev_cursor": page.prev_cursor
+    })))
+}
+
 pub async fn get_user(
     pool: web::Data<DbPool>,
     path: web::Path<String>,
@@ -80,6 +126,25 @@ pub async fn update_user(
     let user_id = Uuid::parse_str(&user.user_id)
         .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
 
+    // Validate the sensitive media preference
+    if let Some(ref preference) = request.sensitive_media
+        && ![SENSITIVE_MEDIA_SHOW, SENSITIVE_MEDIA_BLUR, SENSITIVE_MEDIA_HIDE]
+            .contains(&preference.as_str())
+    {
+        return Err(actix_web::error::ErrorBadRequest(
+            "sensitive_media must be show, blur or hide",
+        ));
+    }
+
+    // Validate the direct message policy
+    if let Some(ref policy) = request.dm_policy
+        && ![DM_POLICY_EVERYONE, DM_POLICY_FOLLOWING].contains(&policy.as_str())
+    {
+        return Err(actix_web::error::ErrorBadRequest(
+            "dm_policy must be everyone or following",
+        ));
+    }
+
     // Update user in database
     let updated_user = update_user_repo(&pool, &user_id, &request.into_inner()).map_err(|e| {
         eprintln!("Database update error: {}", e);
@@ -97,6 +162,7 @@ pub async fn update_user(
 /// Delete a user
 pub async fn delete_user(
     pool: web::Data<DbPool>,
+    storage: web::Data<dyn MediaStorage>,
     user: AuthenticatedUser,
 ) -> Result<HttpResponse, Error> {
     // Parse user_id from JWT token
@@ -104,8 +170,12 @@ pub async fn delete_user(
         .map_err(|_| actix_web::error::ErrorBadRequest("Ivalid user ID"))?;
 
     match delete_user_repo(&pool, &user_id) {
-        Ok(true) => Ok(HttpResponse::Ok().finish()),
-        Ok(false) => Err(actix_web::error::ErrorNotFound("User not found")),
+        Ok(Some(keys)) => {
+            // Objects are removed once the rows are gone; failures are retried in the background
+            delete_storage_objects(&pool, storage.get_ref(), keys).await;
+            Ok(HttpResponse::Ok().finish())
+        }
+        Ok(None) => Err(actix_web::error::ErrorNotFound("User not found")),
         Err(e) => {
             eprintln!("Database delete error: {}", e);
             Err(actix_web::error::ErrorInternalServerError("Database error"))
@@ -117,32 +187,246 @@ pub async fn delete_user(
 pub async fn get_followers(
     pool: web::Data<DbPool>,
     path: web::Path<Uuid>,
+    query: web::Query<CursorQuery>,
 ) -> Result<HttpResponse, Error> {
     let user_id = path.into_inner();
 
-    let followers = get_followers_repo(&pool, &user_id).map_err(|e| {
+    let cursor = parse_cursor(query.cursor.as_deref())?;
+    let per_page = clamp_per_page(query.per_page);
+
+    let page = get_followers_repo(&pool, &user_id, cursor.as_ref(), per_page).map_err(|e| {
         eprintln!("Database query error: {}", e);
         actix_web::error::ErrorInternalServerError("Database error")
     })?;
 
     Ok(HttpResponse::Ok()
         .content_type("application/json")
-        .json(followers))
+        .json(serde_json::json!({
+            "users": page.items,
+            "next_cursor": page.next_cursor,
+            "prev_cursor": page.prev_cursor
+        })))
 }
 
 /// Get followings of a user
 pub async fn get_followings(
     pool: web::Data<DbPool>,
     path: web::Path<Uuid>,
+    query: web::Query<CursorQuery>,
 ) -> Result<HttpResponse, Error> {
     let user_id = path.into_inner();
 
-    let followings = get_followings_repo(&pool, &user_id).map_err(|e| {
+    let cursor = parse_cursor(query.cursor.as_deref())?;
+    let per_page = clamp_per_page(query.per_page);
+
+    let page = get_followings_repo(&pool, &user_id, cursor.as_ref(), per_page).map_err(|e| {
         eprintln!("Database query error: {}", e);
         actix_web::error::ErrorInternalServerError("Database error")
     })?;
 
     Ok(HttpResponse::Ok()
         .content_type("application/json")
-        .json(followings))
+        .json(serde_json::json!({
+            "users": page.items,
+            "next_cursor": page.next_cursor,
+            "prev_cursor": page.prev_cursor
+        })))
+}
+
+/// Uploads the current user's avatar, cropped to a square
+pub async fn upload_avatar(
+    pool: web::Data<DbPool>,
+    storage: web::Data<dyn MediaStorage>,
+    urls: web::Data<MediaUrls>,
+    user: AuthenticatedUser,
+    req: HttpRequest,
+    payload: web::Payload,
+) -> Result<HttpResponse, Error> {
+    upload_profile_image(
+        &pool,
+        storage.get_ref(),
+        &urls,
+        &user,
+        &req,
+        payload,
+        ProfileImage::Avatar,
+    )
+    .await
+}
+
+/// Uploads the current user's profile banner, cropped to 3:1
+pub async fn upload_banner(
+    pool: web::Data<DbPool>,
+    storage: web::Data<dyn MediaStorage>,
+    urls: web::Data<MediaUrls>,
+    user: AuthenticatedUser,
+    req: HttpRequest,
+    payload: web::Payload,
+) -> Result<HttpResponse, Error> {
+    upload_profile_image(
+        &pool,
+        storage.get_ref(),
+        &urls,
+        &user,
+        &req,
+        payload,
+        ProfileImage::Banner,
+    )
+    .await
+}
+
+/// Stores a new avatar or banner, points the user at it and deletes the one it replaces
+async fn upload_profile_image(
+    pool: &DbPool,
+    storage: &dyn MediaStorage,
+    urls: &MediaUrls,
+    user: &AuthenticatedUser,
+    req: &HttpRequest,
+    payload: web::Payload,
+    kind: ProfileImage,
+) -> Result<HttpResponse, Error> {
+    let user_id = Uuid::parse_str(&user.user_id)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid user ID"))?;
+
+    let data = read_payload(req, payload, max_upload_bytes("image/jpeg")).await?;
+    if !detect_media_type(&data).is_some_and(|t| t.starts_with("image/")) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Profile images must be PNG, JPEG or GIF",
+        ));
+    }
+
+    let image = web::block(move || process_profile_image(&data, kind))
+        .await?
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid image: {}", e)))?;
+
+    let version = Uuid::new_v4().simple().to_string();
+    let key = profile_image_key(user_id, kind.as_str(), &version, image.ext);
+    storage
+        .put(&key, web::Bytes::from(image.data), image.content_type)
+        .await?;
+
+    let url = urls.profile_image_url(&user_id, kind, &version);
+    let replaced = replace_profile_image_repo(pool, &user_id, kind, &key, &url);
+    let (updated_user, old_key) = match replaced {
+        Ok(result) => result,
+        Err(e) => {
+            delete_storage_objects(pool, storage, vec![key]).await;
+            return Err(match e {
+                diesel::result::Error::NotFound => {
+                    actix_web::error::ErrorNotFound("User not found")
+                }
+                e => {
+                    eprintln!("Database update profile image error: {}", e);
+                    actix_web::error::ErrorInternalServerError("Database error")
+                }
+            });
+        }
+    };
+
+    delete_storage_objects(pool, storage, old_key.into_iter().collect()).await;
+
+    let public_user: UserPublic = updated_user.into();
+    Ok(HttpResponse::Ok().json(public_user))
+}
+
+/// Serves a user's avatar
+pub async fn get_avatar(
+    pool: web::Data<DbPool>,
+    storage: web::Data<dyn MediaStorage>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    query: web::Query<ProfileImageQuery>,
+) -> Result<HttpResponse, Error> {
+    serve_profile_image(
+        &pool,
+        storage.get_ref(),
+        &req,
+        path.into_inner(),
+        &query,
+        ProfileImage::Avatar,
+    )
+    .await
+}
+
+/// Serves a user's profile banner
+pub async fn get_banner(
+    pool: web::Data<DbPool>,
+    storage: web::Data<dyn MediaStorage>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    query: web::Query<ProfileImageQuery>,
+) -> Result<HttpResponse, Error> {
+    serve_profile_image(
+        &pool,
+        storage.get_ref(),
+        &req,
+        path.into_inner(),
+        &query,
+        ProfileImage::Banner,
+    )
+    .await
+}
+
+async fn serve_profile_image(
+    pool: &DbPool,
+    storage: &dyn MediaStorage,
+    req: &HttpRequest,
+    user_id: Uuid,
+    query: &ProfileImageQuery,
+    kind: ProfileImage,
+) -> Result<HttpResponse, Error> {
+    let user = find_user_by_id(pool, &user_id)
+        .map_err(|e| {
+            eprintln!("Database query error: {}", e);
+            actix_web::error::ErrorInternalServerError("Database error")
+        })?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("User not found"))?;
+    let key = kind
+        .key(&user)
+        .ok_or_else(|| actix_web::error::ErrorNotFound("No image uploaded"))?;
+
+    // Keys end in `{kind}_{version}.{ext}`
+    let path = Path::new(key);
+    let version = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.strip_prefix(kind.as_str()))
+        .and_then(|rest| rest.strip_prefix('_'))
+        .unwrap_or_default();
+    let content_type = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(expected_media_type)
+        .unwrap_or("application/octet-stream");
+
+    // Versioned URLs never change content; unversioned ones must revalidate
+    let cache_control = if query.v.as_deref() == Some(version) {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+    let etag = format!("\"{}\"", version);
+
+    let if_none_match = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok());
+    if if_none_match.is_some_and(|v| {
+        v.split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == etag)
+    }) {
+        return Ok(HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .insert_header((header::CACHE_CONTROL, cache_control))
+            .finish());
+    }
+
+    let data = storage.get(key).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((header::ETAG, etag))
+        .insert_header((header::CACHE_CONTROL, cache_control))
+        .body(data))
 }
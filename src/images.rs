@@ -0,0 +1,251 @@
+use crate::models::media::ImageRenditions;
+use crate::models::users::ProfileImage;
+use crate::storage::MediaStorage;
+use actix_web::web::Bytes;
+use actix_web::{Error, web};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use std::io::Cursor;
+
+/// Largest width or height accepted for decoding, to guard against decompression bombs
+const MAX_IMAGE_DIMENSION: u32 = 16_384;
+
+/// JPEG quality used for re-encoded originals and renditions
+const JPEG_QUALITY: u8 = 85;
+
+/// Square thumbnail edge in pixels
+const THUMB_SIZE: u32 = 150;
+
+/// Bounding box of the "small" rendition
+const SMALL_SIZE: u32 = 680;
+
+/// Bounding box of the "large" rendition
+const LARGE_SIZE: u32 = 1200;
+
+/// Edge of the square avatar in pixels
+const AVATAR_SIZE: u32 = 400;
+
+/// Banner size in pixels, a 3:1 crop
+const BANNER_WIDTH: u32 = 1500;
+const BANNER_HEIGHT: u32 = 500;
+
+/// An encoded image ready to be stored
+pub struct EncodedImage {
+    pub data: Vec<u8>,
+    pub content_type: &'static str,
+    pub ext: &'static str,
+}
+
+/// Result of running an uploaded image through the pipeline
+pub struct ProcessedImage {
+    /// Metadata-free replacement for the upload, `None` to keep it as is
+    pub original: Option<EncodedImage>,
+    pub width: u32,
+    pub height: u32,
+    pub thumb: EncodedImage,
+    /// `None` when the image already fits the rendition's bounding box
+    pub small: Option<EncodedImage>,
+    pub large: Option<EncodedImage>,
+}
+
+/// Decodes an image with dimension limits, applying its EXIF orientation.
+pub fn decode_image(data: &[u8]) -> Result<(DynamicImage, Option<ImageFormat>), image::ImageError> {
+    let mut reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+    let format = reader.format();
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+
+    Ok((img, format))
+}
+
+/// Encodes as PNG when the image has transparency and as JPEG otherwise.
+/// Encoding from decoded pixels drops EXIF, GPS and any other metadata.
+pub fn encode_image(img: &DynamicImage) -> Result<EncodedImage, image::ImageError> {
+    encode_as(img, img.color().has_alpha())
+}
+
+fn encode_as(img: &DynamicImage, png: bool) -> Result<EncodedImage, image::ImageError> {
+    let mut data = Vec::new();
+
+    if png {
+        img.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)?;
+        return Ok(EncodedImage {
+            data,
+            content_type: "image/png",
+            ext: "png",
+        });
+    }
+
+    let rgb = DynamicImage::ImageRgb8(img.to_rgb8());
+    rgb.write_with_encoder(JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY))?;
+    Ok(EncodedImage {
+        data,
+        content_type: "image/jpeg",
+        ext: "jpg",
+    })
+}
+
+/// Scales an image down to fit `max` x `max`, or `None` if it already fits.
+fn fit_within(img: &DynamicImage, max: u32) -> Option<DynamicImage> {
+    (img.width() > max || img.height() > max).then(|| img.resize(max, max, FilterType::Lanczos3))
+}
+
+/// Strips metadata and generates the thumb/small/large renditions.
+/// CPU bound; run it on a blocking thread.
+pub fn process_image(data: &[u8]) -> Result<ProcessedImage, image::ImageError> {
+    let (img, format) = decode_image(data)?;
+
+    // GIFs carry no EXIF and re-encoding would drop their animation,
+    // so only JPEG and PNG originals are replaced, keeping their format.
+    let original = match format {
+        Some(ImageFormat::Gif) => None,
+        _ => Some(encode_as(&img, format == Some(ImageFormat::Png))?),
+    };
+
+    let thumb = encode_image(&img.resize_to_fill(THUMB_SIZE, THUMB_SIZE, FilterType::Lanczos3))?;
+    let small = fit_within(&img, SMALL_SIZE)
+        .map(|resized| encode_image(&resized))
+        .transpose()?;
+    let large = fit_within(&img, LARGE_SIZE)
+        .map(|resized| encode_image(&resized))
+        .transpose()?;
+
+    Ok(ProcessedImage {
+        original,
+        width: img.width(),
+        height: img.height(),
+        thumb,
+        small,
+        large,
+    })
+}
+
+/// Crops and resizes an avatar (square) or banner (3:1) to its fixed size,
+/// stripping metadata. CPU bound; run it on a blocking thread.
+pub fn process_profile_image(
+    data: &[u8],
+    kind: ProfileImage,
+) -> Result<EncodedImage, image::ImageError> {
+    let (img, _) = decode_image(data)?;
+
+    let (width, height) = match kind {
+        ProfileImage::Avatar => (AVATAR_SIZE, AVATAR_SIZE),
+        ProfileImage::Banner => (BANNER_WIDTH, BANNER_HEIGHT),
+    };
+
+    encode_image(&img.resize_to_fill(width, height, FilterType::Lanczos3))
+}
+
+/// Builds the key of a rendition stored next to the original,
+/// e.g. `uploads/{user_id}/{uuid}.jpg` -> `uploads/{user_id}/{uuid}_thumb.jpg`.
+pub fn rendition_key(key: &str, name: &str, ext: &str) -> String {
+    let stem = match key.rfind('.') {
+        Some(dot) if dot > key.rfind('/').unwrap_or(0) => &key[..dot],
+        _ => key,
+    };
+    format!("{}_{}.{}", stem, name, ext)
+}
+
+/// Runs a stored upload through the image pipeline: a metadata-free copy of
+/// `source_key` is stored at `target_key`, with renditions as sibling keys.
+/// Returns the renditions and the size of the stored original in bytes.
+/// Renditions written are removed again if a step fails, so `target_key` must be
+/// a key no other upload writes to, such as a new `blob_key`.
+pub async fn process_stored_image(
+    storage: &dyn MediaStorage,
+    source_key: &str,
+    target_key: &str,
+    size_bytes: i64,
+) -> Result<(ImageRenditions, i64), Error> {
+    let data = storage.get(source_key).await?;
+
+    let processed = web::block(move || process_image(&data))
+        .await?
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid image: {}", e)))?;
+
+    let mut written = Vec::new();
+    let result = store_processed(
+        storage,
+        source_key,
+        target_key,
+        size_bytes,
+        processed,
+        &mut written,
+    )
+    .await;
+
+    if result.is_err() {
+        for rendition in written {
+            if let Err(e) = storage.delete(&rendition).await {
+                eprintln!("Failed to clean up rendition {}: {}", rendition, e);
+            }
+        }
+    }
+
+    result
+}
+
+async fn store_processed(
+    storage: &dyn MediaStorage,
+    source_key: &str,
+    target_key: &str,
+    mut size_bytes: i64,
+    processed: ProcessedImage,
+    written: &mut Vec<String>,
+) -> Result<(ImageRenditions, i64), Error> {
+    let thumb_key =
+        store_rendition(storage, target_key, "thumb", Some(processed.thumb), written).await?;
+    let small_key = store_rendition(storage, target_key, "small", processed.small, written).await?;
+    let large_key = store_rendition(storage, target_key, "large", processed.large, written).await?;
+
+    // Store the original last so a failure leaves no object under the target key
+    match processed.original {
+        Some(original) => {
+            size_bytes = original.data.len() as i64;
+            storage
+                .put(target_key, Bytes::from(original.data), original.content_type)
+                .await?;
+        }
+        None => storage.copy(source_key, target_key).await?,
+    }
+
+    Ok((
+        ImageRenditions {
+            width: processed.width as i32,
+            height: processed.height as i32,
+            thumb_key,
+            small_key,
+            large_key,
+        },
+        size_bytes,
+    ))
+}
+
+async fn store_rendition(
+    storage: &dyn MediaStorage,
+    key: &str,
+    name: &str,
+    image: Option<EncodedImage>,
+    written: &mut Vec<String>,
+) -> Result<Option<String>, Error> {
+    let Some(image) = image else {
+        return Ok(None);
+    };
+
+    let rendition = rendition_key(key, name, image.ext);
+    storage
+        .put(&rendition, Bytes::from(image.data), image.content_type)
+        .await?;
+    written.push(rendition.clone());
+
+    Ok(Some(rendition))
+}
@@ -0,0 +1,214 @@
+use crate::database::DbPool;
+use crate::repositories::media::{
+    delete_media_records_repo, delete_storage_objects, delete_unattached_media_repo,
+    ready_media_before_repo, referenced_storage_keys_repo, unattached_media_repo,
+};
+use crate::storage::MediaStorage;
+use chrono::Utc;
+use std::collections::HashSet;
+use std::env;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Prefix under which all uploaded media is stored
+const UPLOADS_PREFIX: &str = "uploads/";
+
+/// Settings for one collection pass
+#[derive(Debug, Clone, Copy)]
+pub struct GcOptions {
+    /// Orphans younger than this are left alone, so uploads in flight are never touched
+    pub grace_period: chrono::Duration,
+    /// Only report what would be deleted
+    pub dry_run: bool,
+}
+
+impl GcOptions {
+    /// Reads `MEDIA_GC_GRACE_HOURS` (default 24) and `MEDIA_GC_DRY_RUN` (default false)
+    pub fn from_env() -> Self {
+        let grace_hours = env::var("MEDIA_GC_GRACE_HOURS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(24);
+
+        Self {
+            grace_period: chrono::Duration::hours(grace_hours),
+            dry_run: env::var("MEDIA_GC_DRY_RUN").is_ok_and(|v| v == "true" || v == "1"),
+        }
+    }
+}
+
+/// What a collection pass found, and deleted unless it was a dry run
+#[derive(Debug, Default)]
+pub struct GcReport {
+    pub dry_run: bool,
+    /// Ready media never attached to a tweet or direct message
+    pub unattached_media: Vec<Uuid>,
+    /// Objects under `uploads/` that no media record or blob references
+    pub orphaned_objects: Vec<String>,
+    /// Media records whose original object is gone from storage
+    pub missing_objects: Vec<Uuid>,
+}
+
+impl GcReport {
+    pub fn is_empty(&self) -> bool {
+        self.unattached_media.is_empty()
+            && self.orphaned_objects.is_empty()
+            && self.missing_objects.is_empty()
+    }
+}
+
+/// A summary line followed by every orphan found
+impl fmt::Display for GcReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Media GC{}: {} unattached media, {} orphaned objects, {} records with missing objects",
+            if self.dry_run { " (dry run)" } else { "" },
+            self.unattached_media.len(),
+            self.orphaned_objects.len(),
+            self.missing_objects.len()
+        )?;
+
+        for media_id in &self.unattached_media {
+            write!(f, "\n  unattached media {}", media_id)?;
+        }
+        for key in &self.orphaned_objects {
+            write!(f, "\n  orphaned object {}", key)?;
+        }
+        for media_id in &self.missing_objects {
+            write!(f, "\n  missing object for media {}", media_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reconciles the `media` table with the objects stored under `uploads/`:
+/// deletes media never attached to a tweet or message, objects nothing references,
+/// and records whose object is missing. Only orphans older than the grace period are considered.
+pub async fn collect_garbage(
+    pool: &DbPool,
+    storage: &dyn MediaStorage,
+    options: GcOptions,
+) -> Result<GcReport, String> {
+    let cutoff = Utc::now() - options.grace_period;
+    let mut report = GcReport {
+        dry_run: options.dry_run,
+        ..GcReport::default()
+    };
+
+    // Uploads that were never attached to a tweet or message
+    let unattached = unattached_media_repo(pool, cutoff.naive_utc())
+        .map_err(|e| format!("Database get unattached media error: {}", e))?;
+    report.unattached_media = unattached.iter().map(|m| m.id).collect();
+
+    if !options.dry_run && !report.unattached_media.is_empty() {
+        let keys = delete_unattached_media_repo(pool, &report.unattached_media)
+            .map_err(|e| format!("Database delete unattached media error: {}", e))?;
+        delete_storage_objects(pool, storage, keys).await;
+    }
+
+    // List the bucket before reading references, so objects uploaded meanwhile are too young to match
+    let objects = storage
+        .list(UPLOADS_PREFIX)
+        .await
+        .map_err(|e| format!("Storage list error: {}", e))?;
+    let referenced = referenced_storage_keys_repo(pool)
+        .map_err(|e| format!("Database get referenced keys error: {}", e))?;
+
+    // Objects whose record is gone
+    report.orphaned_objects = objects
+        .iter()
+        .filter(|object| !referenced.contains(&object.key))
+        .filter(|object| object.last_modified.is_some_and(|modified| modified < cutoff))
+        .map(|object| object.key.clone())
+        .collect();
+
+    if !options.dry_run {
+        delete_storage_objects(pool, storage, report.orphaned_objects.clone()).await;
+    }
+
+    // Records whose object is gone
+    let stored: HashSet<&str> = objects.iter().map(|object| object.key.as_str()).collect();
+    let records = ready_media_before_repo(pool, cutoff.naive_utc())
+        .map_err(|e| format!("Database get media error: {}", e))?;
+    report.missing_objects = records
+        .iter()
+        .filter(|m| !stored.contains(m.s3_key.as_str()))
+        .filter(|m| !report.unattached_media.contains(&m.id))
+        .map(|m| m.id)
+        .collect();
+
+    if !options.dry_run && !report.missing_objects.is_empty() {
+        let keys = delete_media_records_repo(pool, &report.missing_objects)
+            .map_err(|e| format!("Database delete media error: {}", e))?;
+        delete_storage_objects(pool, storage, keys).await;
+    }
+
+    Ok(report)
+}
+
+/// Runs a collection pass every `MEDIA_GC_INTERVAL_HOURS` (default 24).
+/// Runs forever; spawn it on the actix runtime.
+pub async fn run(pool: DbPool, storage: Arc<dyn MediaStorage>) {
+    let interval_hours = env::var("MEDIA_GC_INTERVAL_HOURS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(24);
+    let mut interval = actix_web::rt::time::interval(Duration::from_secs(interval_hours * 60 * 60));
+    let options = GcOptions::from_env();
+
+    loop {
+        interval.tick().await;
+
+        match collect_garbage(&pool, storage.as_ref(), options).await {
+            Ok(report) if !report.is_empty() => eprintln!("{}", report),
+            Ok(_) => {}
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+/// Entry point of the `gc-media [--dry-run] [--grace-hours N]` subcommand: runs one pass and prints the report,
+/// on stdout for a dry run and on stderr otherwise
+pub async fn run_cli(
+    pool: &DbPool,
+    storage: &dyn MediaStorage,
+    args: &[String],
+) -> std::io::Result<()> {
+    let mut options = GcOptions::from_env();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" => options.dry_run = true,
+            "--grace-hours" => {
+                let hours = args
+                    .next()
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(|| std::io::Error::other("--grace-hours expects a number of hours"))?;
+                options.grace_period = chrono::Duration::hours(hours);
+            }
+            other => {
+                return Err(std::io::Error::other(format!(
+                    "Unknown gc-media argument: {}",
+                    other
+                )));
+            }
+        }
+    }
+
+    let report = collect_garbage(pool, storage, options)
+        .await
+        .map_err(std::io::Error::other)?;
+
+    // A dry run's report is its output; otherwise it is a log of what was deleted
+    if report.dry_run {
+        println!("{}", report);
+    } else {
+        eprintln!("{}", report);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,4 @@
+pub mod media_gc;
+pub mod pending_uploads;
+pub mod storage_deletions;
+pub mod trends;
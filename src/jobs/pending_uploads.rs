@@ -0,0 +1,33 @@
+use crate::database::DbPool;
+use crate::repositories::media::{delete_storage_objects, expire_pending_media_repo};
+use crate::storage::MediaStorage;
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often expired direct uploads are swept
+const POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Maximum number of pending uploads expired per sweep
+const BATCH_SIZE: i64 = 100;
+
+/// Deletes direct uploads that were never completed, along with any object
+/// the client managed to upload. Runs forever; spawn it on the actix runtime.
+pub async fn run(pool: DbPool, storage: Arc<dyn MediaStorage>) {
+    let mut interval = actix_web::rt::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let keys = match expire_pending_media_repo(&pool, Utc::now().naive_utc(), BATCH_SIZE) {
+            Ok(keys) => keys,
+            Err(e) => {
+                eprintln!("Database expire pending uploads error: {}", e);
+                continue;
+            }
+        };
+
+        // Deleting a key that was never uploaded is not an error
+        delete_storage_objects(&pool, storage.as_ref(), keys).await;
+    }
+}
@@ -0,0 +1,48 @@
+use crate::database::DbPool;
+use crate::repositories::media::{
+    complete_storage_deletion_repo, due_storage_deletions_repo, reschedule_storage_deletion_repo,
+};
+use crate::storage::MediaStorage;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the retry queue is polled
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Maximum number of deletions retried per poll
+const BATCH_SIZE: i64 = 100;
+
+/// Retries storage deletions that failed when their media was deleted.
+/// Runs forever; spawn it on the actix runtime.
+pub async fn run(pool: DbPool, storage: Arc<dyn MediaStorage>) {
+    let mut interval = actix_web::rt::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let due = match due_storage_deletions_repo(&pool, BATCH_SIZE) {
+            Ok(due) => due,
+            Err(e) => {
+                eprintln!("Database get storage deletions error: {}", e);
+                continue;
+            }
+        };
+
+        for deletion in due {
+            let result = match storage.delete(&deletion.storage_key).await {
+                Ok(()) => complete_storage_deletion_repo(&pool, &deletion.id),
+                Err(e) => {
+                    eprintln!(
+                        "Storage delete retry error for {}: {}",
+                        deletion.storage_key, e
+                    );
+                    reschedule_storage_deletion_repo(&pool, &deletion, &e.to_string())
+                }
+            };
+
+            if let Err(e) = result {
+                eprintln!("Database update storage deletion error: {}", e);
+            }
+        }
+    }
+}
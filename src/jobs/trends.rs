@@ -0,0 +1,122 @@
+use crate::database::DbPool;
+use crate::models::hashtags::NewTrend;
+use crate::repositories::hashtags::{hashtag_author_counts_repo, replace_trends_repo};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Settings for computing trends
+#[derive(Debug, Clone, Copy)]
+pub struct TrendOptions {
+    /// Recent window whose activity is compared to the baseline
+    pub window: chrono::Duration,
+    /// Window right before the recent one that sets the expected activity
+    pub baseline: chrono::Duration,
+    /// Fewest distinct recent authors for a hashtag to trend
+    pub min_authors: i64,
+    /// Number of trends kept
+    pub limit: usize,
+}
+
+impl TrendOptions {
+    /// Reads `TRENDS_WINDOW_MINUTES` (default 60), `TRENDS_BASELINE_HOURS` (default 24),
+    /// `TRENDS_MIN_AUTHORS` (default 3) and `TRENDS_LIMIT` (default 20)
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        }
+
+        Self {
+            window: chrono::Duration::minutes(var("TRENDS_WINDOW_MINUTES", 60)),
+            baseline: chrono::Duration::hours(var("TRENDS_BASELINE_HOURS", 24)),
+            min_authors: var("TRENDS_MIN_AUTHORS", 3),
+            limit: var("TRENDS_LIMIT", 20),
+        }
+    }
+}
+
+/// How far a hashtag's recent activity is above what its baseline predicts.
+/// The baseline is scaled to the recent window's length, and the excess is
+/// divided by its Poisson deviation, so a jump from 2 to 20 authors outranks
+/// a steady 500.
+fn trend_score(recent: i64, baseline: i64, options: &TrendOptions) -> f64 {
+    let scale = options.window.num_seconds() as f64 / options.baseline.num_seconds() as f64;
+    let expected = baseline as f64 * scale;
+
+    (recent as f64 - expected) / (expected + 1.0).sqrt()
+}
+
+/// Computes trends over the sliding windows ending now and stores them
+pub fn refresh_trends(pool: &DbPool, options: &TrendOptions) -> Result<(), String> {
+    let now = Utc::now().naive_utc();
+    let window_start = now - options.window;
+    let baseline_start = window_start - options.baseline;
+
+    let candidates: Vec<(Uuid, i64)> = hashtag_author_counts_repo(pool, window_start, now, None)
+        .map_err(|e| format!("Database count recent hashtags error: {}", e))?
+        .into_iter()
+        .filter(|(_, recent)| *recent >= options.min_authors)
+        .collect();
+
+    let ids: Vec<Uuid> = candidates.iter().map(|(hashtag_id, _)| *hashtag_id).collect();
+    let baseline: HashMap<Uuid, i64> = if ids.is_empty() {
+        HashMap::new()
+    } else {
+        hashtag_author_counts_repo(pool, baseline_start, window_start, Some(&ids))
+            .map_err(|e| format!("Database count baseline hashtags error: {}", e))?
+            .into_iter()
+            .collect()
+    };
+
+    let mut scored: Vec<(Uuid, i64, i64, f64)> = candidates
+        .into_iter()
+        .map(|(hashtag_id, recent)| {
+            let before = baseline.get(&hashtag_id).copied().unwrap_or(0);
+            (hashtag_id, recent, before, trend_score(recent, before, options))
+        })
+        .filter(|(_, _, _, score)| *score > 0.0)
+        .collect();
+
+    scored.sort_by(|a, b| b.3.total_cmp(&a.3).then(b.1.cmp(&a.1)));
+    scored.truncate(options.limit);
+
+    let new_trends: Vec<NewTrend> = scored
+        .into_iter()
+        .enumerate()
+        .map(|(i, (hashtag_id, recent, before, score))| NewTrend {
+            hashtag_id,
+            rank: i as i32 + 1,
+            score,
+            recent_authors: recent,
+            baseline_authors: before,
+            computed_at: now,
+        })
+        .collect();
+
+    replace_trends_repo(pool, &new_trends)
+        .map_err(|e| format!("Database replace trends error: {}", e))
+}
+
+/// Recomputes trends every `TRENDS_REFRESH_MINUTES` (default 5).
+/// Runs forever; spawn it on the actix runtime.
+pub async fn run(pool: DbPool) {
+    let refresh_minutes = env::var("TRENDS_REFRESH_MINUTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5);
+    let mut interval = actix_web::rt::time::interval(Duration::from_secs(refresh_minutes * 60));
+    let options = TrendOptions::from_env();
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = refresh_trends(&pool, &options) {
+            eprintln!("{}", e);
+        }
+    }
+}
@@ -1,12 +1,35 @@
-use actix_web::{Error, HttpRequest};
-use futures_util::future::{Ready, ready};
+use crate::database::DbPool;
+use crate::repositories::refresh_tokens::is_session_active_repo;
+use actix_web::http::{Method, header};
+use actix_web::{Error, HttpRequest, web};
+use futures_util::future::LocalBoxFuture;
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
 use serde::{Deserialize, Serialize};
 use std::env;
+use uuid::Uuid;
+
+/// Lifetime of an access token in seconds (15 minutes)
+pub const ACCESS_TOKEN_TTL_SECS: u64 = 60 * 15;
+
+/// Lifetime of a refresh token in days
+pub const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+
+/// Cookie holding the access token for browser clients
+pub const AUTH_COOKIE: &str = "auth_token";
+
+/// Cookie holding the refresh token for browser clients
+pub const REFRESH_COOKIE: &str = "refresh_token";
+
+/// Cookie holding the CSRF token (readable by scripts, echoed in `CSRF_HEADER`)
+pub const CSRF_COOKIE: &str = "csrf_token";
+
+/// Header that must repeat the CSRF cookie on cookie-authenticated writes
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Claims {
     sub: String, // Subject (user ID)
+    sid: Uuid,   // Session ID (refresh token family)
     exp: usize,  // Expiration timestamp (unix seconds)
 }
 
@@ -17,19 +40,20 @@ fn jwt_secret() -> Vec<u8> {
         .into_bytes()
 }
 
-/// Creates a JWT token for a given user ID with 24H expiration.
-pub fn create_jwt(user_id: &str) -> Result<String, jsonwebtoken::errors::Error> {
+/// Creates a short-lived access token for a given user ID and session.
+pub fn create_jwt(user_id: &str, session_id: &Uuid) -> Result<String, jsonwebtoken::errors::Error> {
     use std::time::{SystemTime, UNIX_EPOCH};
 
-    // Calculate expiration time: current time + 24 hours
+    // Calculate expiration time: current time + access token lifetime
     let expiration = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("time went backwards")
         .as_secs()
-        + 60 * 60 * 24;
+        + ACCESS_TOKEN_TTL_SECS;
 
     let claims = Claims {
         sub: user_id.to_owned(),
+        sid: *session_id,
         exp: expiration as usize,
     };
 
@@ -40,34 +64,110 @@ pub fn create_jwt(user_id: &str) -> Result<String, jsonwebtoken::errors::Error>
     )
 }
 
-/// Validates the JWT token and returns the user_id (subject) if valid.
-pub fn validate_jwt(token: &str) -> Option<String> {
+/// Validates the JWT token and returns the user_id (subject) and session ID if valid.
+pub fn validate_jwt(token: &str) -> Option<(String, Uuid)> {
     decode::<Claims>(
         token,
         &DecodingKey::from_secret(&jwt_secret()),
         &Validation::default(),
     )
-    .map(|data| data.claims.sub)
+    .map(|data| (data.claims.sub, data.claims.sid))
     .ok()
 }
 
-/// Extractor for authenticated user from the "auth_token" cookie.
+/// Returns the token from an `Authorization: Bearer <jwt>` header, if present.
+fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Checks the double-submit CSRF token: the `CSRF_HEADER` value must match the `CSRF_COOKIE`.
+pub fn verify_csrf(req: &HttpRequest) -> bool {
+    let Some(cookie) = req.cookie(CSRF_COOKIE) else {
+        return false;
+    };
+    let Some(header_value) = req.headers().get(CSRF_HEADER).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+
+    let (expected, actual) = (cookie.value().as_bytes(), header_value.as_bytes());
+
+    // Constant-time comparison
+    expected.len() == actual.len()
+        && expected
+            .iter()
+            .zip(actual)
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Extractor for authenticated user from the `Authorization: Bearer` header
+/// or the "auth_token" cookie.
 pub struct AuthenticatedUser {
     pub user_id: String,
+    pub session_id: Uuid,
+}
+
+impl AuthenticatedUser {
+    /// Reads the user ID and session ID from the request's token, checking CSRF for cookies
+    fn claims(req: &HttpRequest) -> Result<(String, Uuid), Error> {
+        let claims = match bearer_token(req) {
+            Some(token) => validate_jwt(token),
+            None => {
+                let claims = req
+                    .cookie(AUTH_COOKIE)
+                    .and_then(|cookie| validate_jwt(cookie.value()));
+
+                let is_safe_method =
+                    matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+                if claims.is_some() && !is_safe_method && !verify_csrf(req) {
+                    return Err(actix_web::error::ErrorForbidden(
+                        "Missing or invalid CSRF token",
+                    ));
+                }
+
+                claims
+            }
+        };
+
+        claims.ok_or_else(|| actix_web::error::ErrorUnauthorized("Unauthorized"))
+    }
 }
 
 impl actix_web::FromRequest for AuthenticatedUser {
     type Error = Error;
-    type Future = Ready<Result<Self, Self::Error>>;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
 
-    /// Extract user info from JWT stored in "auth_token" cookie.
-    /// Returns Unauthorized error if missing or invalid.
+    /// Extract user info from the bearer JWT, falling back to the "auth_token" cookie.
+    /// Cookie-authenticated state-changing requests must pass the CSRF check.
+    /// Returns Unauthorized error if missing, invalid or the session was revoked.
     fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
-        if let Some(cookie) = req.cookie("auth_token") {
-            if let Some(user_id) = validate_jwt(cookie.value()) {
-                return ready(Ok(AuthenticatedUser { user_id }));
+        let claims = Self::claims(req);
+        let pool = req.app_data::<web::Data<DbPool>>().cloned();
+
+        Box::pin(async move {
+            let (user_id, session_id) = claims?;
+            let pool = pool.ok_or_else(|| {
+                actix_web::error::ErrorInternalServerError("Database pool not configured")
+            })?;
+
+            // Reject tokens whose session was logged out or revoked.
+            // The lookup is blocking, so it runs on the blocking thread pool.
+            match web::block(move || is_session_active_repo(&pool, &session_id)).await? {
+                Ok(true) => Ok(AuthenticatedUser {
+                    user_id,
+                    session_id,
+                }),
+                Ok(false) => Err(actix_web::error::ErrorUnauthorized("Session revoked")),
+                Err(e) => {
+                    eprintln!("Database session check error: {}", e);
+                    Err(actix_web::error::ErrorInternalServerError("Database error"))
+                }
             }
-        }
-        ready(Err(actix_web::error::ErrorUnauthorized("Unauthorized")))
+        })
     }
 }
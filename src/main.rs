@@ -1,50 +1,81 @@
-use crate::storage::S3Storage;
 use actix_web::{App, HttpServer, web};
-use aws_config::BehaviorVersion;
-use aws_config::meta::region::RegionProviderChain;
-use aws_sdk_s3::Client;
-use aws_sdk_s3::config::Region;
-use std::env;
+use std::sync::Arc;
 
 mod crypto;
 mod database;
+mod entities;
+mod events;
 mod handlers;
+mod images;
+mod jobs;
 mod jwt;
+mod media_urls;
 mod models;
+mod notifications;
+mod pagination;
 mod repositories;
 mod requests;
 mod schema;
+mod search_query;
 mod storage;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv::dotenv().ok();
 
-    let region_provider = RegionProviderChain::default_provider().or_else(Region::new(
-        env::var("AWS_REGION").unwrap_or_else(|_| "eu-north-1".to_string()),
-    ));
+    // Media storage backend, selected by STORAGE_BACKEND
+    let media_storage = storage::from_env().await;
+
+    let pool = database::create_pool();
 
-    let config = aws_config::defaults(BehaviorVersion::latest())
-        .region(region_provider)
-        .load()
-        .await;
+    // `gc-media [--dry-run] [--grace-hours N]` runs the orphaned media collector once and exits
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("gc-media") {
+        return jobs::media_gc::run_cli(&pool, media_storage.as_ref(), &args[1..]).await;
+    }
 
-    let client = Client::new(&config);
-    let baucket_name = env::var("AWS_BUCKET_NAME").unwrap_or_else(|_| "file-storage".to_string());
-    let storage_s3 = S3Storage::new(client, baucket_name);
+    // URLs handed out for media, plain or signed depending on MEDIA_URL_MODE
+    let media_urls = web::Data::new(media_urls::MediaUrls::from_env());
 
-    let pool = database::create_pool();
+    // Pub/sub hub feeding `GET /stream`; in-process, so it only reaches this instance's connections
+    let event_hub: Arc<dyn events::EventHub> = Arc::new(events::InProcessHub::new());
+
+    let notification_service = web::Data::new(notifications::NotificationService::new(
+        pool.clone(),
+        event_hub.clone(),
+    ));
+
+    // Background jobs
+    actix_web::rt::spawn(jobs::storage_deletions::run(
+        pool.clone(),
+        media_storage.clone(),
+    ));
+    actix_web::rt::spawn(jobs::pending_uploads::run(
+        pool.clone(),
+        media_storage.clone(),
+    ));
+    actix_web::rt::spawn(jobs::media_gc::run(
+        pool.clone(),
+        media_storage.clone(),
+    ));
+    actix_web::rt::spawn(jobs::trends::run(pool.clone()));
 
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(pool.clone()))
-            .app_data(web::Data::new(storage_s3.clone()))
+            .app_data(web::Data::from(media_storage.clone()))
+            .app_data(media_urls.clone())
+            .app_data(notification_service.clone())
+            .app_data(web::Data::from(event_hub.clone()))
 
             // Auth endpoints
             .service(
                 web::scope("/auth/")
                     .route("/register", web::post().to(handlers::auth::register))
                     .route("/login", web::post().to(handlers::auth::login))
+                    .route("/refresh", web::post().to(handlers::auth::refresh))
+                    .route("/logout", web::post().to(handlers::auth::logout))
+                    .route("/logout-all", web::post().to(handlers::auth::logout_all))
                     .route("/protected", web::post().to(handlers::auth::protected_route))
                     .route("/me", web::get().to(handlers::auth::get_current_user))
             )
@@ -53,11 +84,15 @@ async fn main() -> std::io::Result<()> {
             .service(
                 web::scope("/users/")
                     .route("", web::get().to(handlers::users::list_users))
+                    .route("/me/avatar", web::put().to(handlers::users::upload_avatar))
+                    .route("/me/banner", web::put().to(handlers::users::upload_banner))
                     .route("/{id}", web::get().to(handlers::users::get_user))
                     .route("/{id}", web::patch().to(handlers::users::update_user))
                     .route("/{id}", web::delete().to(handlers::users::delete_user))
                     .route("/{id}/followers", web::get().to(handlers::users::get_followers))
                     .route("/{id}/following", web::get().to(handlers::users::get_followings))
+                    .route("/{id}/avatar", web::get().to(handlers::users::get_avatar))
+                    .route("/{id}/banner", web::get().to(handlers::users::get_banner))
             )
 
             // Follow endpoints
@@ -68,13 +103,20 @@ async fn main() -> std::io::Result<()> {
                     .route("/{id}", web::get().to(handlers::follows::check_follow))
             )
 
+            // Block endpoints
+            .service(
+                web::scope("/blocks/")
+                    .route("/{id}", web::post().to(handlers::blocks::block_user))
+                    .route("/{id}", web::delete().to(handlers::blocks::unblock_user))
+            )
+
             // Tweet endpoints
             .service(
                 web::scope("/tweets/")
                     .route("", web::post().to(handlers::tweets::create_tweet))
                     .route("", web::get().to(handlers::tweets::get_tweets))
                     .route("/{id}", web::get().to(handlers::tweets::get_tweet))
-                    .route("", web::delete().to(handlers::tweets::delete_tweet))
+                    .route("/{id}", web::delete().to(handlers::tweets::delete_tweet))
                     .route("/{id}/reply", web::post().to(handlers::tweets::reply_to_tweet))
                     .route("/{id}/replies", web::get().to(handlers::tweets::get_replies))
                     .route("/{id}/retweet", web::post().to(handlers::tweets::retweet_tweet))
@@ -83,10 +125,65 @@ async fn main() -> std::io::Result<()> {
                     .route("/{id}/likes", web::get().to(handlers::likes::get_likes))
             )
 
+            // Timeline endpoints
+            .service(
+                web::scope("/timeline/")
+                    .route("/home", web::get().to(handlers::timeline::home_timeline))
+                    .route("/mentions", web::get().to(handlers::timeline::mentions_timeline))
+            )
+
+            // Search endpoints
+            .service(
+                web::scope("/search/")
+                    .route("/tweets", web::get().to(handlers::search::search_tweets))
+            )
+
+            // Hashtag endpoints
+            .service(
+                web::scope("/hashtags/")
+                    .route("/{tag}/tweets", web::get().to(handlers::hashtags::get_hashtag_tweets))
+            )
+            .route("/trends", web::get().to(handlers::hashtags::get_trends))
+
+            // Notification endpoints
+            .service(
+                web::scope("/notifications/")
+                    .route("", web::get().to(handlers::notifications::get_notifications))
+                    .route("/read", web::post().to(handlers::notifications::mark_notifications_read))
+                    .route("/unread_count", web::get().to(handlers::notifications::get_unread_count))
+            )
+
+            // Direct message endpoints
+            .service(
+                web::scope("/conversations/")
+                    .route("", web::get().to(handlers::conversations::list_conversations))
+                    .route("", web::post().to(handlers::conversations::create_conversation))
+                    .route("/unread_count", web::get().to(handlers::conversations::get_unread_conversations_count))
+                    .route("/{id}", web::get().to(handlers::conversations::get_conversation))
+                    .route("/{id}/messages", web::get().to(handlers::conversations::get_messages))
+                    .route("/{id}/messages", web::post().to(handlers::conversations::send_message))
+                    .route("/{id}/read", web::post().to(handlers::conversations::mark_conversation_read))
+                    .route("/{id}/leave", web::post().to(handlers::conversations::leave_conversation))
+                    .route("/{id}/mute", web::post().to(handlers::conversations::mute_conversation))
+                    .route("/{id}/mute", web::delete().to(handlers::conversations::unmute_conversation))
+            )
+
+            // Real-time events over Server-Sent Events or WebSocket
+            .route("/stream", web::get().to(handlers::stream::stream))
+
             // Media endpoints
             .service(
                 web::scope("/media/")
+                    .route("", web::get().to(handlers::media::list_media))
+                    .route("/usage", web::get().to(handlers::media::media_usage))
                     .route("/upload", web::post().to(handlers::media::upload_media))
+                    .route("/uploads", web::post().to(handlers::media::create_upload))
+                    .route("/{id}", web::get().to(handlers::media::get_media))
+                    .route("/{id}", web::patch().to(handlers::media::update_media))
+                    .route("/{id}", web::delete().to(handlers::media::delete_media))
+                    .route("/{id}/complete", web::post().to(handlers::media::complete_upload))
+                    .route("/{id}/content", web::get().to(handlers::media::get_media_content))
+                    .route("/{id}/content/{variant}", web::get().to(handlers::media::get_media_variant))
             )
 
     })
@@ -0,0 +1,171 @@
+use crate::models::media::Media;
+use crate::models::users::ProfileImage;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::env;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// A stored file of a media item: the original or one of its renditions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaVariant {
+    Original,
+    Thumb,
+    Small,
+    Large,
+}
+
+impl MediaVariant {
+    /// Parses the `{variant}` path segment
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "original" => Some(Self::Original),
+            "thumb" => Some(Self::Thumb),
+            "small" => Some(Self::Small),
+            "large" => Some(Self::Large),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Original => "original",
+            Self::Thumb => "thumb",
+            Self::Small => "small",
+            Self::Large => "large",
+        }
+    }
+
+    /// Storage key of this variant, if the media has it
+    pub fn key<'a>(&self, media_item: &'a Media) -> Option<&'a str> {
+        match self {
+            Self::Original => Some(&media_item.s3_key),
+            Self::Thumb => media_item.thumb_key.as_deref(),
+            Self::Small => media_item.small_key.as_deref(),
+            Self::Large => media_item.large_key.as_deref(),
+        }
+    }
+}
+
+/// Builds the URLs clients use to fetch media through `GET /media/{id}/content`.
+///
+/// With `MEDIA_URL_MODE=signed` every URL carries an expiry and an HMAC signature,
+/// and the content endpoint only serves signed requests (or authorized viewers).
+/// The default `proxy` mode issues plain URLs and checks access on each request.
+pub struct MediaUrls {
+    base_url: String,
+    signing: Option<Signing>,
+}
+
+struct Signing {
+    secret: Vec<u8>,
+    ttl_secs: i64,
+}
+
+impl MediaUrls {
+    /// Reads `API_BASE_URL`, `MEDIA_URL_MODE`, `MEDIA_URL_SECRET` (defaults to `JWT_SECRET`)
+    /// and `MEDIA_URL_TTL_SECS` (default one hour).
+    pub fn from_env() -> Self {
+        let base_url =
+            env::var("API_BASE_URL").unwrap_or_else(|_| "http://127.0.0.1:8080".to_string());
+
+        let signing = match env::var("MEDIA_URL_MODE").as_deref() {
+            Ok("signed") => Some(Signing {
+                secret: env::var("MEDIA_URL_SECRET")
+                    .or_else(|_| env::var("JWT_SECRET"))
+                    .expect("MEDIA_URL_SECRET or JWT_SECRET must be set for signed media URLs")
+                    .into_bytes(),
+                ttl_secs: env::var("MEDIA_URL_TTL_SECS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(60 * 60),
+            }),
+            Ok("proxy") | Err(_) => None,
+            Ok(other) => panic!("Unknown MEDIA_URL_MODE: {}", other),
+        };
+
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            signing,
+        }
+    }
+
+    /// Whether URLs are signed and unsigned requests need an authorized viewer
+    pub fn is_signed(&self) -> bool {
+        self.signing.is_some()
+    }
+
+    /// Returns the URL of a media variant, signed when signing is enabled
+    pub fn url_for(&self, media_id: &Uuid, variant: MediaVariant) -> String {
+        let url = match variant {
+            MediaVariant::Original => format!("{}/media/{}/content", self.base_url, media_id),
+            _ => format!(
+                "{}/media/{}/content/{}",
+                self.base_url,
+                media_id,
+                variant.as_str()
+            ),
+        };
+
+        let Some(signing) = &self.signing else {
+            return url;
+        };
+
+        let expires = chrono::Utc::now().timestamp() + signing.ttl_secs;
+        let signature = hex::encode(
+            signing
+                .mac(media_id, variant, expires)
+                .finalize()
+                .into_bytes(),
+        );
+
+        format!("{}?expires={}&signature={}", url, expires, signature)
+    }
+
+    /// Returns the URL of a user's avatar or banner. The version changes with
+    /// every upload, so the URL can be cached for good.
+    pub fn profile_image_url(&self, user_id: &Uuid, kind: ProfileImage, version: &str) -> String {
+        format!(
+            "{}/users/{}/{}?v={}",
+            self.base_url,
+            user_id,
+            kind.as_str(),
+            version
+        )
+    }
+
+    /// Checks a signature and expiry taken from a signed URL
+    pub fn verify(
+        &self,
+        media_id: &Uuid,
+        variant: MediaVariant,
+        expires: i64,
+        signature: &str,
+    ) -> bool {
+        let Some(signing) = &self.signing else {
+            return false;
+        };
+        if expires < chrono::Utc::now().timestamp() {
+            return false;
+        }
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+
+        // Constant-time comparison
+        signing
+            .mac(media_id, variant, expires)
+            .verify_slice(&signature)
+            .is_ok()
+    }
+}
+
+impl Signing {
+    fn mac(&self, media_id: &Uuid, variant: MediaVariant, expires: i64) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(format!("{}/{}/{}", media_id, variant.as_str(), expires).as_bytes());
+        mac
+    }
+}
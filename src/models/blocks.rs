@@ -0,0 +1,23 @@
+use crate::schema::blocks;
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A user blocking another. Blocks hide each user's content from the other
+/// and remove any follow between them.
+#[derive(Debug, Clone, Queryable, Identifiable, Serialize, Deserialize)]
+#[diesel(table_name = blocks)]
+#[diesel(primary_key(blocker_id, blocked_id))]
+pub struct Block {
+    pub blocker_id: Uuid,
+    pub blocked_id: Uuid,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = blocks)]
+pub struct NewBlock {
+    pub blocker_id: Uuid,
+    pub blocked_id: Uuid,
+}
@@ -0,0 +1,101 @@
+use crate::models::media::MediaEntity;
+use crate::models::users::UserPublic;
+use crate::schema::{conversation_members, conversations, messages};
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Queryable, Identifiable, Serialize, Deserialize)]
+#[diesel(table_name = conversations)]
+pub struct Conversation {
+    pub id: Uuid,
+    pub is_group: bool,
+    /// Optional name of a group
+    pub name: Option<String>,
+    pub creator_id: Option<Uuid>,
+    /// Identifies the pair of a one-to-one conversation; `None` for groups
+    #[serde(skip)]
+    pub direct_key: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_message_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = conversations)]
+pub struct NewConversation {
+    pub id: Uuid,
+    pub is_group: bool,
+    pub name: Option<String>,
+    pub creator_id: Option<Uuid>,
+    pub direct_key: Option<String>,
+}
+
+/// A current member of a conversation
+#[derive(Debug, Clone, Queryable)]
+pub struct ConversationMember {
+    pub conversation_id: Uuid,
+    pub user_id: Uuid,
+    pub joined_at: NaiveDateTime,
+    pub muted: bool,
+    /// Messages up to this time have been read
+    pub last_read_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = conversation_members)]
+pub struct NewConversationMember {
+    pub conversation_id: Uuid,
+    pub user_id: Uuid,
+}
+
+#[derive(Debug, Clone, Queryable, Identifiable, Serialize, Deserialize)]
+#[diesel(table_name = messages)]
+pub struct Message {
+    pub id: Uuid,
+    pub conversation_id: Uuid,
+    pub sender_id: Uuid,
+    pub content: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = messages)]
+pub struct NewMessage {
+    pub id: Uuid,
+    pub conversation_id: Uuid,
+    pub sender_id: Uuid,
+    pub content: String,
+}
+
+/// Message hydrated with its sender and attached media
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageWithMedia {
+    #[serde(flatten)]
+    pub message: Message,
+    pub sender: UserPublic,
+    pub media: Vec<MediaEntity>,
+}
+
+/// A current member of a conversation with their read receipt
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Participant {
+    #[serde(flatten)]
+    pub user: UserPublic,
+    pub joined_at: NaiveDateTime,
+    /// Messages sent up to this time have been read by the participant
+    pub last_read_at: Option<NaiveDateTime>,
+}
+
+/// A conversation as seen by one of its members
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    #[serde(flatten)]
+    pub conversation: Conversation,
+    /// Current members, the viewer included
+    pub participants: Vec<Participant>,
+    pub muted: bool,
+    /// Messages from others the viewer has not read
+    pub unread_count: i64,
+    pub last_message: Option<MessageWithMedia>,
+}
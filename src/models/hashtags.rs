@@ -0,0 +1,54 @@
+use crate::schema::{hashtags, trends, tweet_hashtags};
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A hashtag, stored lowercased without the `#`
+#[derive(Debug, Clone, Queryable, Serialize, Deserialize)]
+#[diesel(table_name = hashtags)]
+pub struct Hashtag {
+    pub id: Uuid,
+    pub tag: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = hashtags)]
+pub struct NewHashtag {
+    pub id: Uuid,
+    pub tag: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = tweet_hashtags)]
+pub struct NewTweetHashtag {
+    pub tweet_id: Uuid,
+    pub hashtag_id: Uuid,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = trends)]
+pub struct NewTrend {
+    pub hashtag_id: Uuid,
+    pub rank: i32,
+    pub score: f64,
+    pub recent_authors: i64,
+    pub baseline_authors: i64,
+    pub computed_at: NaiveDateTime,
+}
+
+/// A trending hashtag as returned by `GET /trends`
+#[derive(Debug, Clone, Queryable, Serialize, Deserialize)]
+pub struct TrendEntity {
+    pub tag: String,
+    pub rank: i32,
+    /// How far recent use is above the baseline; higher trends more
+    pub score: f64,
+    /// Distinct authors using the tag in the recent window
+    pub recent_authors: i64,
+    /// Distinct authors using the tag in the baseline window before it
+    pub baseline_authors: i64,
+    pub computed_at: NaiveDateTime,
+}
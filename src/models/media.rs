@@ -1,8 +1,9 @@
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use diesel::{Queryable, Insertable, Identifiable};
-use crate::schema::media;
+use diesel::{AsChangeset, Queryable, Insertable, Identifiable};
+use crate::media_urls::{MediaUrls, MediaVariant};
+use crate::schema::{media, media_blobs, message_media, storage_deletions, tweet_media};
 
 #[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Identifiable)]
 #[diesel(table_name = media)]
@@ -13,6 +14,46 @@ pub struct Media {
     pub file_name: String,
     pub file_type: String,
     pub created_at: NaiveDateTime,
+    pub size_bytes: i64,
+    pub alt_text: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub thumb_key: Option<String>,
+    pub small_key: Option<String>,
+    pub large_key: Option<String>,
+    pub status: String,
+    pub expires_at: Option<NaiveDateTime>,
+    /// SHA-256 of the uploaded content, referencing the shared `MediaBlob`
+    pub content_hash: Option<String>,
+    /// Marked by the owner as sensitive content
+    pub sensitive: bool,
+}
+
+/// Media whose file is stored and verified
+pub const MEDIA_STATUS_READY: &str = "ready";
+
+/// Media created for a direct upload that has not been completed yet
+pub const MEDIA_STATUS_PENDING: &str = "pending";
+
+impl Media {
+    /// Storage keys of the original and all its renditions
+    pub fn storage_keys(&self) -> Vec<String> {
+        std::iter::once(self.s3_key.clone())
+            .chain(self.thumb_key.clone())
+            .chain(self.small_key.clone())
+            .chain(self.large_key.clone())
+            .collect()
+    }
+}
+
+/// Dimensions and rendition keys produced by the image pipeline
+#[derive(Debug, Clone, Default)]
+pub struct ImageRenditions {
+    pub width: i32,
+    pub height: i32,
+    pub thumb_key: Option<String>,
+    pub small_key: Option<String>,
+    pub large_key: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Insertable)]
@@ -24,9 +65,181 @@ pub struct NewMedia {
     pub file_name: String,
     pub file_type: String,
     pub created_at: NaiveDateTime,
+    pub size_bytes: i64,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub thumb_key: Option<String>,
+    pub small_key: Option<String>,
+    pub large_key: Option<String>,
+    pub status: String,
+    pub expires_at: Option<NaiveDateTime>,
+    pub content_hash: Option<String>,
+}
+
+/// Changes to a media item's description; `None` fields are left as they are
+#[derive(Debug, AsChangeset)]
+#[diesel(table_name = media)]
+pub struct MediaUpdate {
+    pub alt_text: Option<Option<String>>,
+    pub sensitive: Option<bool>,
+}
+
+/// A content-addressed file (and its renditions) shared by every media row
+/// uploaded with the same content. Deleted when `ref_count` drops to zero.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
+#[diesel(table_name = media_blobs, primary_key(sha256))]
+pub struct MediaBlob {
+    pub sha256: String,
+    pub storage_key: String,
+    pub file_type: String,
+    pub size_bytes: i64,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub thumb_key: Option<String>,
+    pub small_key: Option<String>,
+    pub large_key: Option<String>,
+    pub ref_count: i32,
+    pub created_at: NaiveDateTime,
+}
+
+impl MediaBlob {
+    /// Storage keys of the blob and all its renditions
+    pub fn storage_keys(&self) -> Vec<String> {
+        std::iter::once(self.storage_key.clone())
+            .chain(self.thumb_key.clone())
+            .chain(self.small_key.clone())
+            .chain(self.large_key.clone())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = media_blobs)]
+pub struct NewMediaBlob {
+    pub sha256: String,
+    pub storage_key: String,
+    pub file_type: String,
+    pub size_bytes: i64,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub thumb_key: Option<String>,
+    pub small_key: Option<String>,
+    pub large_key: Option<String>,
+}
+
+impl NewMediaBlob {
+    /// Storage keys of the blob and all its renditions
+    pub fn storage_keys(&self) -> Vec<String> {
+        std::iter::once(self.storage_key.clone())
+            .chain(self.thumb_key.clone())
+            .chain(self.small_key.clone())
+            .chain(self.large_key.clone())
+            .collect()
+    }
+}
+
+/// Links a media item to the tweet it is attached to
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable)]
+#[diesel(table_name = tweet_media)]
+pub struct TweetMedia {
+    pub tweet_id: Uuid,
+    pub media_id: Uuid,
+    pub position: i16,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UploadMediaForm {
-    pub media_url: String,
+/// Links a media item to the direct message it is attached to
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable)]
+#[diesel(table_name = message_media)]
+pub struct MessageMedia {
+    pub message_id: Uuid,
+    pub media_id: Uuid,
+    pub position: i16,
+}
+
+/// A storage object whose deletion failed and is waiting to be retried
+#[derive(Debug, Serialize, Deserialize, Queryable, Identifiable)]
+#[diesel(table_name = storage_deletions)]
+pub struct StorageDeletion {
+    pub id: Uuid,
+    pub storage_key: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = storage_deletions)]
+pub struct NewStorageDeletion {
+    pub id: Uuid,
+    pub storage_key: String,
+    pub last_error: Option<String>,
+}
+
+/// A user's own media with its storage details, as listed under `/media/`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaDetails {
+    #[serde(flatten)]
+    pub media: MediaEntity,
+    pub file_name: String,
+    pub size_bytes: i64,
+    pub status: String,
+    pub created_at: NaiveDateTime,
+    /// Tweet the media is attached to, if any
+    pub tweet_id: Option<Uuid>,
+}
+
+impl MediaDetails {
+    pub fn new(media_item: &Media, tweet_id: Option<Uuid>, urls: &MediaUrls) -> Self {
+        Self {
+            media: MediaEntity::new(media_item, urls),
+            file_name: media_item.file_name.clone(),
+            size_bytes: media_item.size_bytes,
+            status: media_item.status.clone(),
+            created_at: media_item.created_at,
+            tweet_id,
+        }
+    }
+}
+
+/// Media as exposed in API responses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaEntity {
+    pub id: Uuid,
+    pub url: String,
+    pub file_type: String,
+    pub alt_text: Option<String>,
+    pub sensitive: bool,
+    /// Sensitive media the viewer asked to see blurred
+    pub blurred: bool,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub thumb_url: Option<String>,
+    pub small_url: Option<String>,
+    pub large_url: Option<String>,
+}
+
+impl MediaEntity {
+    /// Builds the response with URLs served by `GET /media/{id}/content`
+    pub fn new(media_item: &Media, urls: &MediaUrls) -> Self {
+        let rendition_url = |variant: MediaVariant| {
+            variant
+                .key(media_item)
+                .map(|_| urls.url_for(&media_item.id, variant))
+        };
+
+        Self {
+            id: media_item.id,
+            url: urls.url_for(&media_item.id, MediaVariant::Original),
+            file_type: media_item.file_type.clone(),
+            alt_text: media_item.alt_text.clone(),
+            sensitive: media_item.sensitive,
+            blurred: false,
+            width: media_item.width,
+            height: media_item.height,
+            thumb_url: rendition_url(MediaVariant::Thumb),
+            small_url: rendition_url(MediaVariant::Small),
+            large_url: rendition_url(MediaVariant::Large),
+        }
+    }
 } 
\ No newline at end of file
@@ -1,5 +1,10 @@
+pub mod blocks;
+pub mod conversations;
 pub mod follows;
+pub mod hashtags;
 pub mod likes;
 pub mod media;
+pub mod notifications;
+pub mod refresh_tokens;
 pub mod tweets;
 pub mod users;
@@ -0,0 +1,64 @@
+use crate::models::tweets::TweetWithStats;
+use crate::models::users::UserPublic;
+use crate::schema::notifications;
+use chrono::NaiveDateTime;
+use diesel::{Insertable, QueryableByName};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Someone liked the recipient's tweet
+pub const NOTIFICATION_LIKE: &str = "like";
+
+/// Someone retweeted the recipient's tweet
+pub const NOTIFICATION_RETWEET: &str = "retweet";
+
+/// Someone replied to the recipient's tweet
+pub const NOTIFICATION_REPLY: &str = "reply";
+
+/// Someone mentioned the recipient in a tweet
+pub const NOTIFICATION_MENTION: &str = "mention";
+
+/// Someone followed the recipient
+pub const NOTIFICATION_FOLLOW: &str = "follow";
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = notifications)]
+pub struct NewNotification {
+    pub id: Uuid,
+    pub recipient_id: Uuid,
+    pub actor_id: Uuid,
+    pub kind: String,
+    pub tweet_id: Option<Uuid>,
+    pub group_key: String,
+}
+
+/// The newest notification of a group, with the size of the group
+#[derive(Debug, QueryableByName)]
+#[diesel(table_name = notifications)]
+pub struct NotificationGroupRow {
+    pub id: Uuid,
+    pub kind: String,
+    pub tweet_id: Option<Uuid>,
+    pub group_key: String,
+    pub read_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub actors_count: i64,
+}
+
+/// A group of notifications as returned by `GET /notifications`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotificationGroup {
+    /// ID of the newest notification in the group; pass it to `POST /notifications/read`
+    pub id: Uuid,
+    pub kind: String,
+    /// e.g. "alice and 12 others liked your tweet"
+    pub text: String,
+    /// The most recent actors, newest first
+    pub actors: Vec<UserPublic>,
+    pub actors_count: i64,
+    /// The tweet liked, retweeted, replied with or mentioning the recipient
+    pub tweet: Option<TweetWithStats>,
+    pub read: bool,
+    pub created_at: NaiveDateTime,
+}
@@ -1,11 +1,12 @@
-use crate::models::users::User;
-use crate::schema::tweets;
+use crate::models::media::MediaEntity;
+use crate::models::users::{User, UserPublic};
+use crate::schema::{tweet_mentions, tweets};
 use chrono::NaiveDateTime;
-use diesel::{Associations, Identifiable, Insertable, Queryable};
+use diesel::{Associations, Identifiable, Insertable, Queryable, QueryableByName};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Queryable, Identifiable, Associations, Serialize, Deserialize)]
+#[derive(Debug, Clone, Queryable, QueryableByName, Identifiable, Associations, Serialize, Deserialize)]
 #[diesel(belongs_to(User))]
 #[diesel(table_name = tweets)]
 pub struct Tweet {
@@ -17,6 +18,8 @@ pub struct Tweet {
     pub is_retweet: bool,
     pub original_tweet_id: Option<Uuid>,
     pub created_at: NaiveDateTime,
+    /// ISO 639-1 language code sent by the client
+    pub lang: Option<String>,
 }
 
 #[derive(Debug, Insertable, Serialize, Deserialize)]
@@ -28,6 +31,48 @@ pub struct NewTweet {
     pub reply_to_id: Option<Uuid>,
     pub is_retweet: bool,
     pub original_tweet_id: Option<Uuid>,
+    pub lang: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = tweet_mentions)]
+pub struct NewTweetMention {
+    pub tweet_id: Uuid,
+    pub user_id: Uuid,
+    pub created_at: NaiveDateTime,
+}
+
+/// Hashtags and mentions in a tweet's content. Offsets count Unicode characters
+/// (code points), from the `#` or `@` up to the end of the entity (exclusive).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TweetEntities {
+    pub hashtags: Vec<HashtagEntity>,
+    /// Only mentions of existing users; other `@names` are plain text
+    pub mentions: Vec<MentionEntity>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HashtagEntity {
+    /// Lowercased, without the `#`
+    pub tag: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MentionEntity {
+    pub user_id: Uuid,
+    /// The user's current username, which may differ in case from the text
+    pub username: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Normalizes a two-letter ISO 639-1 language code to lowercase,
+/// or returns `None` if `code` is not one
+pub fn normalize_lang(code: &str) -> Option<String> {
+    (code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic()))
+        .then(|| code.to_ascii_lowercase())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,11 +82,15 @@ pub struct TweetWithUser {
     pub user: User,
 }
 
+/// Tweet hydrated with its author, attached media and engagement counts.
+/// The `is_*_by_current_user` flags are `false` for anonymous callers.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TweetWithStats {
     #[serde(flatten)]
     pub tweet: Tweet,
-    pub user: User,
+    pub user: UserPublic,
+    pub media: Vec<MediaEntity>,
+    pub entities: TweetEntities,
     pub likes_count: i64,
     pub retweets_count: i64,
     pub replies_count: i64,
@@ -16,8 +16,33 @@ pub struct User {
     pub avatar_url: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    /// Only followers can see the media of protected accounts
+    pub is_protected: bool,
+    /// How other users' sensitive media is shown: `show`, `blur` or `hide`
+    pub sensitive_media: String,
+    /// Storage keys of the uploaded avatar and banner
+    pub avatar_key: Option<String>,
+    pub banner_key: Option<String>,
+    pub banner_url: Option<String>,
+    /// Who may start a direct message with the user: `everyone` or `following`
+    pub dm_policy: String,
 }
 
+/// Anyone not blocked may message the user (the default)
+pub const DM_POLICY_EVERYONE: &str = "everyone";
+
+/// Only users the user follows may message them
+pub const DM_POLICY_FOLLOWING: &str = "following";
+
+/// Sensitive media is shown like any other media
+pub const SENSITIVE_MEDIA_SHOW: &str = "show";
+
+/// Sensitive media is listed with `blurred` set so clients blur it (the default)
+pub const SENSITIVE_MEDIA_BLUR: &str = "blur";
+
+/// Sensitive media is left out of tweet responses
+pub const SENSITIVE_MEDIA_HIDE: &str = "hide";
+
 #[derive(Debug, Insertable, Serialize, Deserialize)]
 #[diesel(table_name = users)]
 pub struct NewUser {
@@ -26,24 +51,30 @@ pub struct NewUser {
     pub password_hash: String,
     pub name: Option<String>,
     pub bio: Option<String>,
-    pub avatar_url: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserPublic {
     pub id: Uuid,
     pub username: String,
     pub name: Option<String>,
     pub bio: Option<String>,
     pub avatar_url: Option<String>,
+    pub banner_url: Option<String>,
     pub created_at: NaiveDateTime,
+    pub is_protected: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserUpdate {
     pub name: Option<String>,
     pub bio: Option<String>,
-    pub avatar_url: Option<String>,
+    #[serde(default)]
+    pub is_protected: Option<bool>,
+    #[serde(default)]
+    pub sensitive_media: Option<String>,
+    #[serde(default)]
+    pub dm_policy: Option<String>,
 }
 
 impl From<User> for UserPublic {
@@ -54,7 +85,33 @@ impl From<User> for UserPublic {
             name: user.name,
             bio: user.bio,
             avatar_url: user.avatar_url,
+            banner_url: user.banner_url,
             created_at: user.created_at,
+            is_protected: user.is_protected,
+        }
+    }
+}
+
+/// An image shown on a user's profile, uploaded with `PUT /users/me/avatar` or `/banner`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileImage {
+    Avatar,
+    Banner,
+}
+
+impl ProfileImage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Avatar => "avatar",
+            Self::Banner => "banner",
+        }
+    }
+
+    /// Storage key of this image, if the user has uploaded one
+    pub fn key<'a>(&self, user: &'a User) -> Option<&'a str> {
+        match self {
+            Self::Avatar => user.avatar_key.as_deref(),
+            Self::Banner => user.banner_key.as_deref(),
         }
     }
 }
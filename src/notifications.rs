@@ -0,0 +1,201 @@
+use crate::database::DbPool;
+use crate::events::{Event, EventHub};
+use crate::models::notifications::{
+    NOTIFICATION_FOLLOW, NOTIFICATION_LIKE, NOTIFICATION_MENTION, NOTIFICATION_REPLY,
+    NOTIFICATION_RETWEET, NewNotification,
+};
+use crate::models::tweets::Tweet;
+use crate::models::users::UserPublic;
+use crate::repositories::blocks::is_blocked_between_repo;
+use crate::repositories::followers::is_followed_repo;
+use crate::repositories::mentions::get_mentioned_user_ids_repo;
+use crate::repositories::notifications::insert_notification_repo;
+use crate::repositories::tweets::get_tweet_repo;
+use crate::repositories::users::find_user_by_id;
+use chrono::Utc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Creates notifications for likes, follows, replies, retweets and mentions.
+/// Called by the handlers once a write has succeeded; failures are logged and
+/// never fail the write itself. New notifications are published to the
+/// recipient's streaming connections.
+#[derive(Clone)]
+pub struct NotificationService {
+    pool: DbPool,
+    events: Arc<dyn EventHub>,
+}
+
+impl NotificationService {
+    pub fn new(pool: DbPool, events: Arc<dyn EventHub>) -> Self {
+        Self { pool, events }
+    }
+
+    /// `actor` liked a tweet
+    pub fn tweet_liked(&self, actor: &Uuid, tweet_id: &Uuid) {
+        let result = get_tweet_repo(&self.pool, tweet_id).and_then(|tweet| {
+            self.notify(
+                &tweet.user_id,
+                actor,
+                NOTIFICATION_LIKE,
+                Some(tweet.id),
+                daily_group_key(NOTIFICATION_LIKE, Some(&tweet.id)),
+            )
+        });
+        log_error(result);
+    }
+
+    /// `actor` followed `followed`
+    pub fn user_followed(&self, actor: &Uuid, followed: &Uuid) {
+        log_error(self.notify(
+            followed,
+            actor,
+            NOTIFICATION_FOLLOW,
+            None,
+            daily_group_key(NOTIFICATION_FOLLOW, None),
+        ));
+    }
+
+    /// A tweet was posted: notifies the author of the tweet it replies to or
+    /// retweets, and the users it mentions
+    pub fn tweet_created(&self, tweet: &Tweet) {
+        log_error(self.notify_tweet(tweet));
+    }
+
+    fn notify_tweet(&self, tweet: &Tweet) -> Result<(), diesel::result::Error> {
+        let mut notified = Vec::new();
+
+        if let (true, Some(original_id)) = (tweet.is_retweet, tweet.original_tweet_id) {
+            let original = get_tweet_repo(&self.pool, &original_id)?;
+            self.notify(
+                &original.user_id,
+                &tweet.user_id,
+                NOTIFICATION_RETWEET,
+                Some(original.id),
+                daily_group_key(NOTIFICATION_RETWEET, Some(&original.id)),
+            )?;
+            notified.push(original.user_id);
+        }
+
+        if let Some(parent_id) = tweet.reply_to_id {
+            let parent = get_tweet_repo(&self.pool, &parent_id)?;
+            self.notify(
+                &parent.user_id,
+                &tweet.user_id,
+                NOTIFICATION_REPLY,
+                Some(tweet.id),
+                format!("{}:{}", NOTIFICATION_REPLY, tweet.id),
+            )?;
+            notified.push(parent.user_id);
+        }
+
+        // Someone already told about a reply or retweet is not told about the mention too
+        for mentioned in get_mentioned_user_ids_repo(&self.pool, &tweet.id)? {
+            if notified.contains(&mentioned) {
+                continue;
+            }
+            self.notify(
+                &mentioned,
+                &tweet.user_id,
+                NOTIFICATION_MENTION,
+                Some(tweet.id),
+                format!("{}:{}", NOTIFICATION_MENTION, tweet.id),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Records one notification unless the recipient is the actor, either blocked
+    /// the other, or the notification would show a protected actor's tweet to a
+    /// recipient who does not follow them
+    fn notify(
+        &self,
+        recipient: &Uuid,
+        actor: &Uuid,
+        kind: &str,
+        tweet_id: Option<Uuid>,
+        group_key: String,
+    ) -> Result<(), diesel::result::Error> {
+        if recipient == actor || is_blocked_between_repo(&self.pool, recipient, actor)? {
+            return Ok(());
+        }
+
+        let shows_actor_tweet = matches!(kind, NOTIFICATION_REPLY | NOTIFICATION_MENTION);
+        if shows_actor_tweet {
+            let Some(actor_user) = find_user_by_id(&self.pool, actor)? else {
+                return Ok(());
+            };
+            if actor_user.is_protected && !is_followed_repo(&self.pool, recipient, actor)? {
+                return Ok(());
+            }
+        }
+
+        let notification_id = Uuid::new_v4();
+        let inserted = insert_notification_repo(
+            &self.pool,
+            &NewNotification {
+                id: notification_id,
+                recipient_id: *recipient,
+                actor_id: *actor,
+                kind: kind.to_string(),
+                tweet_id,
+                group_key,
+            },
+        )?;
+
+        if inserted {
+            self.events
+                .publish(&[*recipient], Event::Notification { notification_id });
+        }
+
+        Ok(())
+    }
+}
+
+/// Groups notifications of one kind about the same tweet on the same (UTC) day
+fn daily_group_key(kind: &str, tweet_id: Option<&Uuid>) -> String {
+    let day = Utc::now().date_naive();
+    match tweet_id {
+        Some(tweet_id) => format!("{}:{}:{}", kind, tweet_id, day),
+        None => format!("{}:{}", kind, day),
+    }
+}
+
+fn log_error(result: Result<(), diesel::result::Error>) {
+    if let Err(e) = result {
+        eprintln!("Database create notification error: {}", e);
+    }
+}
+
+/// Describes a notification group, e.g. "alice and 12 others liked your tweet".
+/// `actors` are the most recent actors, newest first.
+pub fn describe(kind: &str, actors: &[UserPublic], actors_count: i64) -> String {
+    let display_name = |user: &UserPublic| {
+        user.name
+            .clone()
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| user.username.clone())
+    };
+
+    let who = match (actors.first(), actors.get(1), actors_count) {
+        (None, _, _) => "Someone".to_string(),
+        (Some(first), _, 1) => display_name(first),
+        (Some(first), Some(second), 2) => {
+            format!("{} and {}", display_name(first), display_name(second))
+        }
+        (Some(first), _, 2) => format!("{} and 1 other", display_name(first)),
+        (Some(first), _, count) => format!("{} and {} others", display_name(first), count - 1),
+    };
+
+    let what = match kind {
+        NOTIFICATION_LIKE => "liked your tweet",
+        NOTIFICATION_RETWEET => "retweeted your tweet",
+        NOTIFICATION_REPLY => "replied to your tweet",
+        NOTIFICATION_MENTION => "mentioned you",
+        NOTIFICATION_FOLLOW => "followed you",
+        _ => "interacted with you",
+    };
+
+    format!("{} {}", who, what)
+}
@@ -0,0 +1,308 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, NaiveDateTime};
+use diesel::dsl;
+use diesel::prelude::*;
+use diesel::query_dsl::methods::{FilterDsl, LimitDsl, OrderDsl};
+use diesel::sql_types::{self, Timestamp};
+use serde::Deserialize;
+use uuid::Uuid;
+
+/// Upper bound for `per_page` in cursor mode.
+pub const MAX_PER_PAGE: i64 = 100;
+
+/// Which way a cursor walks from its anchor row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Older rows (the "next" page in a newest-first listing)
+    Next,
+    /// Newer rows (the "previous" page)
+    Prev,
+}
+
+/// Keyset cursor anchored at a `(created_at, id)` pair.
+/// Clients only ever see it as an opaque string.
+#[derive(Debug, Clone, Copy)]
+pub struct Cursor {
+    pub created_at: NaiveDateTime,
+    pub id: Uuid,
+    pub direction: Direction,
+}
+
+impl Cursor {
+    pub fn new(created_at: NaiveDateTime, id: Uuid, direction: Direction) -> Self {
+        Self {
+            created_at,
+            id,
+            direction,
+        }
+    }
+
+    /// Encodes the cursor as an opaque URL-safe string.
+    pub fn encode(&self) -> String {
+        let direction = match self.direction {
+            Direction::Next => 'n',
+            Direction::Prev => 'p',
+        };
+        let raw = format!(
+            "{}:{}:{}",
+            direction,
+            self.created_at.and_utc().timestamp_micros(),
+            self.id
+        );
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    /// Decodes a cursor produced by [`Cursor::encode`].
+    /// Returns `None` if the string is malformed.
+    pub fn decode(value: &str) -> Option<Self> {
+        let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(value).ok()?).ok()?;
+        let mut parts = raw.splitn(3, ':');
+
+        let direction = match parts.next()? {
+            "n" => Direction::Next,
+            "p" => Direction::Prev,
+            _ => return None,
+        };
+        let micros = parts.next()?.parse::<i64>().ok()?;
+        let created_at = DateTime::from_timestamp_micros(micros)?.naive_utc();
+        let id = Uuid::parse_str(parts.next()?).ok()?;
+
+        Some(Self::new(created_at, id, direction))
+    }
+}
+
+/// Parses an optional cursor query parameter into an actix error on failure.
+pub fn parse_cursor(value: Option<&str>) -> Result<Option<Cursor>, actix_web::Error> {
+    value
+        .map(|v| {
+            Cursor::decode(v).ok_or_else(|| actix_web::error::ErrorBadRequest("Invalid cursor"))
+        })
+        .transpose()
+}
+
+/// Reads the legacy `page` parameter (default 1), rejecting pages below 1 with 400
+pub fn parse_page(page: Option<i64>) -> Result<i64, actix_web::Error> {
+    match page.unwrap_or(1) {
+        page if page >= 1 => Ok(page),
+        _ => Err(actix_web::error::ErrorBadRequest("page must be at least 1")),
+    }
+}
+
+/// Pagination of the listings that predate cursors (`/tweets/`, `/users/`,
+/// `/timeline/home`), chosen with the `pagination` query parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaginationMode {
+    /// Offset-based `page`/`per_page` with the original response format
+    Page,
+    /// Opaque cursors, as every other listing
+    Cursor,
+}
+
+impl PaginationMode {
+    /// An explicit `pagination` wins; otherwise passing a `cursor` (and no `page`)
+    /// selects cursors. Offsets stay the default so existing clients keep working.
+    pub fn resolve(requested: Option<Self>, page: Option<i64>, cursor: Option<&str>) -> Self {
+        match requested {
+            Some(mode) => mode,
+            None if page.is_none() && cursor.is_some() => Self::Cursor,
+            None => Self::Page,
+        }
+    }
+}
+
+/// Clamps a requested page size to `1..=MAX_PER_PAGE`.
+pub fn clamp_per_page(per_page: i64) -> i64 {
+    per_page.clamp(1, MAX_PER_PAGE)
+}
+
+/// `(created_at, id)` after the cursor's anchor, for walking towards newer rows
+type NewerThan<T, I> =
+    dsl::Or<dsl::Gt<T, NaiveDateTime>, dsl::And<dsl::Eq<T, NaiveDateTime>, dsl::Gt<I, Uuid>>>;
+
+/// `(created_at, id)` before the cursor's anchor, for walking towards older rows
+type OlderThan<T, I> =
+    dsl::Or<dsl::Lt<T, NaiveDateTime>, dsl::And<dsl::Eq<T, NaiveDateTime>, dsl::Lt<I, Uuid>>>;
+
+/// Restricts a boxed query to one keyset page around the cursor, keyed on the
+/// `created_at` and `id` columns: newest-first, or oldest-first when walking
+/// towards newer rows. Fetches one extra row so [`CursorPage::from_rows`] can
+/// tell whether more pages exist.
+pub fn keyset_page<Q, T, I>(
+    query: Q,
+    created_at: T,
+    id: I,
+    cursor: Option<&Cursor>,
+    per_page: i64,
+) -> Q
+where
+    T: Expression<SqlType = Timestamp> + ExpressionMethods + Copy,
+    I: Expression<SqlType = sql_types::Uuid> + ExpressionMethods + Copy,
+    Q: FilterDsl<NewerThan<T, I>, Output = Q>
+        + FilterDsl<OlderThan<T, I>, Output = Q>
+        + OrderDsl<(dsl::Asc<T>, dsl::Asc<I>), Output = Q>
+        + OrderDsl<(dsl::Desc<T>, dsl::Desc<I>), Output = Q>
+        + LimitDsl<Output = Q>,
+{
+    let query = match cursor {
+        Some(c) if c.direction == Direction::Prev => {
+            let newer = created_at
+                .gt(c.created_at)
+                .or(created_at.eq(c.created_at).and(id.gt(c.id)));
+            OrderDsl::order(FilterDsl::filter(query, newer), (created_at.asc(), id.asc()))
+        }
+        Some(c) => {
+            let older = created_at
+                .lt(c.created_at)
+                .or(created_at.eq(c.created_at).and(id.lt(c.id)));
+            OrderDsl::order(FilterDsl::filter(query, older), (created_at.desc(), id.desc()))
+        }
+        None => OrderDsl::order(query, (created_at.desc(), id.desc())),
+    };
+
+    LimitDsl::limit(query, per_page + 1)
+}
+
+/// One page of a newest-first keyset listing.
+#[derive(Debug)]
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+}
+
+impl<T> CursorPage<T> {
+    /// Builds a page from rows fetched with `LIMIT per_page + 1`.
+    ///
+    /// Rows must be ordered newest-first for `Direction::Next` and
+    /// oldest-first for `Direction::Prev`; the result is always newest-first.
+    pub fn from_rows<F>(mut rows: Vec<T>, per_page: i64, direction: Direction, key: F) -> Self
+    where
+        F: Fn(&T) -> (NaiveDateTime, Uuid),
+    {
+        let has_more = rows.len() as i64 > per_page;
+        rows.truncate(per_page as usize);
+
+        if direction == Direction::Prev {
+            rows.reverse();
+        }
+
+        let cursor_at = |row: Option<&T>, direction| {
+            row.map(|row| {
+                let (created_at, id) = key(row);
+                Cursor::new(created_at, id, direction).encode()
+            })
+        };
+
+        // Only the side we walked towards can be known to be exhausted
+        let (next_cursor, prev_cursor) = match direction {
+            Direction::Next => (
+                if has_more {
+                    cursor_at(rows.last(), Direction::Next)
+                } else {
+                    None
+                },
+                cursor_at(rows.first(), Direction::Prev),
+            ),
+            Direction::Prev => (
+                cursor_at(rows.last(), Direction::Next),
+                if has_more {
+                    cursor_at(rows.first(), Direction::Prev)
+                } else {
+                    None
+                },
+            ),
+        };
+
+        Self {
+            items: rows,
+            next_cursor,
+            prev_cursor,
+        }
+    }
+
+    /// Converts the items while keeping the cursors.
+    pub fn map<U, F: FnOnce(Vec<T>) -> Vec<U>>(self, f: F) -> CursorPage<U> {
+        CursorPage {
+            items: f(self.items),
+            next_cursor: self.next_cursor,
+            prev_cursor: self.prev_cursor,
+        }
+    }
+}
+
+/// Keyset cursor for relevance-ranked listings, anchored at a `(score, id)` pair.
+/// Ranked listings only walk forward.
+#[derive(Debug, Clone, Copy)]
+pub struct RankCursor {
+    pub score: f64,
+    pub id: Uuid,
+}
+
+impl RankCursor {
+    /// Encodes the cursor as an opaque URL-safe string.
+    /// The score is stored bit for bit so it compares equal when read back.
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("r:{}:{}", self.score.to_bits(), self.id))
+    }
+
+    /// Decodes a cursor produced by [`RankCursor::encode`].
+    /// Returns `None` if the string is malformed.
+    pub fn decode(value: &str) -> Option<Self> {
+        let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(value).ok()?).ok()?;
+        let mut parts = raw.splitn(3, ':');
+
+        if parts.next()? != "r" {
+            return None;
+        }
+        let score = f64::from_bits(parts.next()?.parse::<u64>().ok()?);
+        let id = Uuid::parse_str(parts.next()?).ok()?;
+
+        score.is_finite().then_some(Self { score, id })
+    }
+}
+
+/// Parses an optional ranked cursor query parameter into an actix error on failure.
+pub fn parse_rank_cursor(value: Option<&str>) -> Result<Option<RankCursor>, actix_web::Error> {
+    value
+        .map(|v| {
+            RankCursor::decode(v).ok_or_else(|| actix_web::error::ErrorBadRequest("Invalid cursor"))
+        })
+        .transpose()
+}
+
+/// One page of a relevance-ranked listing.
+#[derive(Debug)]
+pub struct RankedPage<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> RankedPage<T> {
+    /// Builds a page from `(item, score)` rows fetched best-first with `LIMIT per_page + 1`.
+    pub fn from_rows<F>(mut rows: Vec<(T, f64)>, per_page: i64, id: F) -> Self
+    where
+        F: Fn(&T) -> Uuid,
+    {
+        let has_more = rows.len() as i64 > per_page;
+        rows.truncate(per_page as usize);
+
+        let next_cursor = if has_more {
+            rows.last().map(|(item, score)| {
+                RankCursor {
+                    score: *score,
+                    id: id(item),
+                }
+                .encode()
+            })
+        } else {
+            None
+        };
+
+        Self {
+            items: rows.into_iter().map(|(item, _)| item).collect(),
+            next_cursor,
+        }
+    }
+}
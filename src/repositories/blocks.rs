@@ -0,0 +1,74 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::blocks::{Block, NewBlock};
+use crate::schema::blocks::dsl::*;
+use crate::schema::follows;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// Blocks a user and removes any follow between the two users.
+/// Blocking someone already blocked returns the existing block.
+pub fn block_user_repo(
+    pool: &DbPool,
+    blocker_id_val: &Uuid,
+    blocked_id_val: &Uuid,
+) -> Result<Block, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        let new_block = NewBlock {
+            blocker_id: *blocker_id_val,
+            blocked_id: *blocked_id_val,
+        };
+
+        diesel::insert_into(blocks)
+            .values(&new_block)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        diesel::delete(
+            follows::table.filter(
+                follows::follower_id
+                    .eq(blocker_id_val)
+                    .and(follows::followed_id.eq(blocked_id_val))
+                    .or(follows::follower_id
+                        .eq(blocked_id_val)
+                        .and(follows::followed_id.eq(blocker_id_val))),
+            ),
+        )
+        .execute(conn)?;
+
+        blocks.find((blocker_id_val, blocked_id_val)).first(conn)
+    })
+}
+
+/// Unblocks a user
+pub fn unblock_user_repo(
+    pool: &DbPool,
+    blocker_id_val: &Uuid,
+    blocked_id_val: &Uuid,
+) -> Result<bool, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let deleted = diesel::delete(blocks.find((blocker_id_val, blocked_id_val))).execute(&mut conn)?;
+
+    Ok(deleted > 0)
+}
+
+/// Checks whether either user has blocked the other
+pub fn is_blocked_between_repo(
+    pool: &DbPool,
+    user_a: &Uuid,
+    user_b: &Uuid,
+) -> Result<bool, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::select(diesel::dsl::exists(
+        blocks.filter(
+            blocker_id
+                .eq(user_a)
+                .and(blocked_id.eq(user_b))
+                .or(blocker_id.eq(user_b).and(blocked_id.eq(user_a))),
+        ),
+    ))
+    .get_result(&mut conn)
+}
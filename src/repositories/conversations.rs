@@ -0,0 +1,765 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::media_urls::MediaUrls;
+use crate::models::conversations::{
+    Conversation, ConversationMember, ConversationSummary, Message, MessageWithMedia,
+    NewConversation, NewConversationMember, NewMessage, Participant,
+};
+use crate::models::media::{Media, MediaEntity};
+use crate::models::users::{DM_POLICY_EVERYONE, User, UserPublic};
+use crate::pagination::{Cursor, CursorPage, Direction, keyset_page};
+use crate::repositories::media::{
+    AttachMediaError, attach_message_media, sensitive_media_preference, viewer_media_entity,
+};
+use crate::schema::{
+    blocks, conversation_members, conversations, follows, media, message_media, messages, users,
+};
+use chrono::{NaiveDateTime, Utc};
+use diesel::dsl::{count_star, exists};
+use diesel::prelude::*;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Columns loaded into a [`ConversationMember`]; members who left are never loaded
+const MEMBER_COLUMNS: (
+    conversation_members::conversation_id,
+    conversation_members::user_id,
+    conversation_members::joined_at,
+    conversation_members::muted,
+    conversation_members::last_read_at,
+) = (
+    conversation_members::conversation_id,
+    conversation_members::user_id,
+    conversation_members::joined_at,
+    conversation_members::muted,
+    conversation_members::last_read_at,
+);
+
+/// Key of the one-to-one conversation between two users, the same whoever starts it
+fn direct_key(user_a: &Uuid, user_b: &Uuid) -> String {
+    let (first, second) = if user_a < user_b {
+        (user_a, user_b)
+    } else {
+        (user_b, user_a)
+    };
+    format!("{}:{}", first, second)
+}
+
+/// Users who blocked or were blocked by the user. Their messages are hidden
+/// from the user, and the user's from them, in conversations they share.
+fn blocked_user_ids(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> Result<Vec<Uuid>, diesel::result::Error> {
+    blocks::table
+        .filter(blocks::blocker_id.eq(user_id).or(blocks::blocked_id.eq(user_id)))
+        .select((blocks::blocker_id, blocks::blocked_id))
+        .load::<(Uuid, Uuid)>(conn)
+        .map(|pairs| {
+            pairs
+                .into_iter()
+                .map(|(blocker, blocked)| if blocker == *user_id { blocked } else { blocker })
+                .collect()
+        })
+}
+
+/// Whether the recipient's `dm_policy` lets the sender message them:
+/// anyone may unless the recipient only accepts messages from people they follow
+pub fn dm_policy_allows_repo(
+    pool: &DbPool,
+    sender: &Uuid,
+    recipient: &Uuid,
+) -> Result<bool, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let policy = users::table
+        .find(recipient)
+        .select(users::dm_policy)
+        .first::<String>(&mut conn)?;
+    if policy == DM_POLICY_EVERYONE {
+        return Ok(true);
+    }
+
+    diesel::select(exists(
+        follows::table
+            .filter(follows::follower_id.eq(recipient))
+            .filter(follows::followed_id.eq(sender)),
+    ))
+    .get_result(&mut conn)
+}
+
+/// Gets the one-to-one conversation between two users, creating it if needed.
+/// Returns whether it was created.
+pub fn get_or_create_direct_conversation_repo(
+    pool: &DbPool,
+    creator: &Uuid,
+    other: &Uuid,
+) -> Result<(Conversation, bool), diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+    let key = direct_key(creator, other);
+
+    conn.transaction(|conn| {
+        let new_conversation = NewConversation {
+            id: Uuid::new_v4(),
+            is_group: false,
+            name: None,
+            creator_id: Some(*creator),
+            direct_key: Some(key.clone()),
+        };
+
+        // The unique key keeps two concurrent requests from creating the pair twice
+        let created = diesel::insert_into(conversations::table)
+            .values(&new_conversation)
+            .on_conflict(conversations::direct_key)
+            .do_nothing()
+            .execute(conn)?
+            > 0;
+
+        if created {
+            diesel::insert_into(conversation_members::table)
+                .values(&[
+                    NewConversationMember {
+                        conversation_id: new_conversation.id,
+                        user_id: *creator,
+                    },
+                    NewConversationMember {
+                        conversation_id: new_conversation.id,
+                        user_id: *other,
+                    },
+                ])
+                .execute(conn)?;
+        }
+
+        let conversation = conversations::table
+            .filter(conversations::direct_key.eq(&key))
+            .first::<Conversation>(conn)?;
+
+        Ok((conversation, created))
+    })
+}
+
+/// Creates a group conversation of the creator and the given members
+pub fn create_group_repo(
+    pool: &DbPool,
+    creator: &Uuid,
+    name: Option<String>,
+    member_ids: &[Uuid],
+) -> Result<Conversation, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        let conversation = diesel::insert_into(conversations::table)
+            .values(&NewConversation {
+                id: Uuid::new_v4(),
+                is_group: true,
+                name,
+                creator_id: Some(*creator),
+                direct_key: None,
+            })
+            .get_result::<Conversation>(conn)?;
+
+        let members: Vec<NewConversationMember> = std::iter::once(creator)
+            .chain(member_ids)
+            .map(|member| NewConversationMember {
+                conversation_id: conversation.id,
+                user_id: *member,
+            })
+            .collect();
+        diesel::insert_into(conversation_members::table)
+            .values(&members)
+            .execute(conn)?;
+
+        Ok(conversation)
+    })
+}
+
+/// Gets a conversation and the user's membership, unless they are not a current member
+pub fn get_membership_repo(
+    pool: &DbPool,
+    conversation_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<Option<(Conversation, ConversationMember)>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conversations::table
+        .inner_join(conversation_members::table)
+        .filter(conversations::id.eq(conversation_id))
+        .filter(conversation_members::user_id.eq(user_id))
+        .filter(conversation_members::left_at.is_null())
+        .select((conversations::all_columns, MEMBER_COLUMNS))
+        .first::<(Conversation, ConversationMember)>(&mut conn)
+        .optional()
+}
+
+/// Gets the IDs of the current members of a conversation
+pub fn get_member_ids_repo(
+    pool: &DbPool,
+    conversation_id: &Uuid,
+) -> Result<Vec<Uuid>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conversation_members::table
+        .filter(conversation_members::conversation_id.eq(conversation_id))
+        .filter(conversation_members::left_at.is_null())
+        .select(conversation_members::user_id)
+        .load::<Uuid>(&mut conn)
+}
+
+/// Gets a cursor-paginated list of the user's conversations,
+/// most recently active first
+pub fn get_conversations_repo(
+    pool: &DbPool,
+    user_id: &Uuid,
+    cursor: Option<&Cursor>,
+    per_page: i64,
+) -> Result<CursorPage<Conversation>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let query = conversations::table
+        .inner_join(conversation_members::table)
+        .filter(conversation_members::user_id.eq(user_id))
+        .filter(conversation_members::left_at.is_null())
+        .select(conversations::all_columns)
+        .into_boxed();
+
+    let rows = keyset_page(
+        query,
+        conversations::last_message_at,
+        conversations::id,
+        cursor,
+        per_page,
+    )
+    .load::<Conversation>(&mut conn)?;
+
+    let direction = cursor.map(|c| c.direction).unwrap_or(Direction::Next);
+    Ok(CursorPage::from_rows(rows, per_page, direction, |c| {
+        (c.last_message_at, c.id)
+    }))
+}
+
+/// Gets a cursor-paginated list of a conversation's messages as the viewer
+/// sees them, newest first
+pub fn get_messages_repo(
+    pool: &DbPool,
+    conversation_id: &Uuid,
+    viewer_id: &Uuid,
+    cursor: Option<&Cursor>,
+    per_page: i64,
+) -> Result<CursorPage<Message>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+    let hidden_senders = blocked_user_ids(&mut conn, viewer_id)?;
+
+    let query = messages::table
+        .filter(messages::conversation_id.eq(conversation_id))
+        .filter(messages::sender_id.ne_all(hidden_senders))
+        .into_boxed();
+
+    let rows = keyset_page(query, messages::created_at, messages::id, cursor, per_page)
+        .load::<Message>(&mut conn)?;
+
+    let direction = cursor.map(|c| c.direction).unwrap_or(Direction::Next);
+    Ok(CursorPage::from_rows(rows, per_page, direction, |m| {
+        (m.created_at, m.id)
+    }))
+}
+
+/// Gets a message
+pub fn get_message_repo(
+    pool: &DbPool,
+    message_id: &Uuid,
+) -> Result<Option<Message>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    messages::table
+        .find(message_id)
+        .first::<Message>(&mut conn)
+        .optional()
+}
+
+/// Whether the user has sent any message in the conversation
+pub fn has_sent_message_repo(
+    pool: &DbPool,
+    conversation_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<bool, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::select(exists(
+        messages::table
+            .filter(messages::conversation_id.eq(conversation_id))
+            .filter(messages::sender_id.eq(user_id)),
+    ))
+    .get_result(&mut conn)
+}
+
+/// Sends a message with the given media attached. The sender's read receipt
+/// moves to their own message.
+pub fn send_message_repo(
+    pool: &DbPool,
+    conversation_id: &Uuid,
+    sender_id: &Uuid,
+    content: &str,
+    media_ids: &[Uuid],
+) -> Result<Message, AttachMediaError> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        let message = diesel::insert_into(messages::table)
+            .values(&NewMessage {
+                id: Uuid::new_v4(),
+                conversation_id: *conversation_id,
+                sender_id: *sender_id,
+                content: content.to_string(),
+            })
+            .get_result::<Message>(conn)?;
+
+        attach_message_media(conn, &message.id, sender_id, media_ids)?;
+
+        diesel::update(conversations::table.find(conversation_id))
+            .set(conversations::last_message_at.eq(message.created_at))
+            .execute(conn)?;
+        diesel::update(
+            conversation_members::table.find((conversation_id, sender_id)),
+        )
+        .set(conversation_members::last_read_at.eq(message.created_at))
+        .execute(conn)?;
+
+        Ok(message)
+    })
+}
+
+/// Moves the user's read receipt up to a message of the conversation, or to its
+/// latest message. Receipts never move back. Returns the receipt, or `None`
+/// if the message is not in the conversation.
+pub fn mark_conversation_read_repo(
+    pool: &DbPool,
+    conversation_id: &Uuid,
+    user_id: &Uuid,
+    up_to: Option<&Uuid>,
+) -> Result<Option<Option<NaiveDateTime>>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        let read_until = match up_to {
+            Some(message_id) => {
+                let message_time = messages::table
+                    .find(message_id)
+                    .filter(messages::conversation_id.eq(conversation_id))
+                    .select(messages::created_at)
+                    .first::<NaiveDateTime>(conn)
+                    .optional()?;
+                match message_time {
+                    Some(time) => Some(time),
+                    None => return Ok(None),
+                }
+            }
+            None => messages::table
+                .filter(messages::conversation_id.eq(conversation_id))
+                .select(diesel::dsl::max(messages::created_at))
+                .first::<Option<NaiveDateTime>>(conn)?,
+        };
+
+        if let Some(time) = read_until {
+            diesel::update(
+                conversation_members::table
+                    .find((conversation_id, user_id))
+                    .filter(
+                        conversation_members::last_read_at
+                            .is_null()
+                            .or(conversation_members::last_read_at.lt(time)),
+                    ),
+            )
+            .set(conversation_members::last_read_at.eq(time))
+            .execute(conn)?;
+        }
+
+        let receipt = conversation_members::table
+            .find((conversation_id, user_id))
+            .select(conversation_members::last_read_at)
+            .first::<Option<NaiveDateTime>>(conn)?;
+
+        Ok(Some(receipt))
+    })
+}
+
+/// Removes the user from a group. They keep no access to it.
+pub fn leave_conversation_repo(
+    pool: &DbPool,
+    conversation_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<(), diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(
+        conversation_members::table
+            .find((conversation_id, user_id))
+            .filter(conversation_members::left_at.is_null()),
+    )
+    .set(conversation_members::left_at.eq(Utc::now().naive_utc()))
+    .execute(&mut conn)?;
+
+    Ok(())
+}
+
+/// Mutes or unmutes a conversation for the user
+pub fn set_conversation_muted_repo(
+    pool: &DbPool,
+    conversation_id: &Uuid,
+    user_id: &Uuid,
+    muted: bool,
+) -> Result<(), diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(conversation_members::table.find((conversation_id, user_id)))
+        .set(conversation_members::muted.eq(muted))
+        .execute(&mut conn)?;
+
+    Ok(())
+}
+
+/// Counts the user's unmuted conversations with messages from others they have not read
+pub fn unread_conversations_count_repo(
+    pool: &DbPool,
+    user_id: &Uuid,
+) -> Result<i64, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+    let hidden_senders = blocked_user_ids(&mut conn, user_id)?;
+
+    conversation_members::table
+        .filter(conversation_members::user_id.eq(user_id))
+        .filter(conversation_members::left_at.is_null())
+        .filter(conversation_members::muted.eq(false))
+        .filter(exists(
+            messages::table
+                .filter(messages::conversation_id.eq(conversation_members::conversation_id))
+                .filter(messages::sender_id.ne(user_id))
+                .filter(messages::sender_id.ne_all(hidden_senders))
+                .filter(
+                    conversation_members::last_read_at
+                        .is_null()
+                        .or(messages::created_at
+                            .nullable()
+                            .gt(conversation_members::last_read_at)),
+                ),
+        ))
+        .count()
+        .get_result(&mut conn)
+}
+
+/// Hydrates messages with their senders and media as the viewer sees them.
+/// Other users' sensitive media follows the viewer's preference.
+pub fn hydrate_messages_repo(
+    pool: &DbPool,
+    urls: &MediaUrls,
+    messages_list: Vec<Message>,
+    viewer_id: &Uuid,
+) -> Result<Vec<MessageWithMedia>, diesel::result::Error> {
+    if messages_list.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut conn = get_db_conn(pool)?;
+
+    let message_ids: Vec<Uuid> = messages_list.iter().map(|m| m.id).collect();
+    let sender_ids: Vec<Uuid> = messages_list.iter().map(|m| m.sender_id).collect();
+
+    let senders: HashMap<Uuid, UserPublic> = users::table
+        .filter(users::id.eq_any(&sender_ids))
+        .load::<User>(&mut conn)?
+        .into_iter()
+        .map(|u| (u.id, UserPublic::from(u)))
+        .collect();
+
+    let sensitive_media = sensitive_media_preference(&mut conn, Some(viewer_id))?;
+
+    let attached = message_media::table
+        .inner_join(media::table)
+        .filter(message_media::message_id.eq_any(&message_ids))
+        .order((message_media::message_id, message_media::position))
+        .select((message_media::message_id, media::all_columns))
+        .load::<(Uuid, Media)>(&mut conn)?;
+
+    let mut media_by_message: HashMap<Uuid, Vec<MediaEntity>> = HashMap::new();
+    for (message_id, media_item) in attached {
+        if let Some(entity) =
+            viewer_media_entity(&media_item, urls, Some(viewer_id), &sensitive_media)
+        {
+            media_by_message.entry(message_id).or_default().push(entity);
+        }
+    }
+
+    Ok(messages_list
+        .into_iter()
+        .filter_map(|message| {
+            let sender = senders.get(&message.sender_id)?.clone();
+            let attached_media = media_by_message.remove(&message.id).unwrap_or_default();
+            Some(MessageWithMedia {
+                message,
+                sender,
+                media: attached_media,
+            })
+        })
+        .collect())
+}
+
+/// Summarizes conversations for one of their members: current participants
+/// with read receipts, the viewer's mute and unread count, and the last message
+pub fn summarize_conversations_repo(
+    pool: &DbPool,
+    urls: &MediaUrls,
+    conversations_list: Vec<Conversation>,
+    viewer_id: &Uuid,
+) -> Result<Vec<ConversationSummary>, diesel::result::Error> {
+    if conversations_list.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut conn = get_db_conn(pool)?;
+
+    let conversation_ids: Vec<Uuid> = conversations_list.iter().map(|c| c.id).collect();
+    let hidden_senders = blocked_user_ids(&mut conn, viewer_id)?;
+
+    let members = conversation_members::table
+        .inner_join(users::table)
+        .filter(conversation_members::conversation_id.eq_any(&conversation_ids))
+        .filter(conversation_members::left_at.is_null())
+        .order((conversation_members::joined_at.asc(), users::username.asc()))
+        .select((MEMBER_COLUMNS, users::all_columns))
+        .load::<(ConversationMember, User)>(&mut conn)?;
+
+    let mut muted: HashMap<Uuid, bool> = HashMap::new();
+    let mut participants: HashMap<Uuid, Vec<Participant>> = HashMap::new();
+    for (member, user) in members {
+        if member.user_id == *viewer_id {
+            muted.insert(member.conversation_id, member.muted);
+        }
+        participants
+            .entry(member.conversation_id)
+            .or_default()
+            .push(Participant {
+                user: user.into(),
+                joined_at: member.joined_at,
+                last_read_at: member.last_read_at,
+            });
+    }
+
+    let unread_counts: HashMap<Uuid, i64> = messages::table
+        .inner_join(
+            conversation_members::table.on(conversation_members::conversation_id
+                .eq(messages::conversation_id)
+                .and(conversation_members::user_id.eq(viewer_id))),
+        )
+        .filter(messages::conversation_id.eq_any(&conversation_ids))
+        .filter(messages::sender_id.ne(viewer_id))
+        .filter(messages::sender_id.ne_all(&hidden_senders))
+        .filter(
+            conversation_members::last_read_at
+                .is_null()
+                .or(messages::created_at
+                    .nullable()
+                    .gt(conversation_members::last_read_at)),
+        )
+        .group_by(messages::conversation_id)
+        .select((messages::conversation_id, count_star()))
+        .load::<(Uuid, i64)>(&mut conn)?
+        .into_iter()
+        .collect();
+
+    let last_messages = messages::table
+        .filter(messages::conversation_id.eq_any(&conversation_ids))
+        .filter(messages::sender_id.ne_all(&hidden_senders))
+        .distinct_on(messages::conversation_id)
+        .order((
+            messages::conversation_id,
+            messages::created_at.desc(),
+            messages::id.desc(),
+        ))
+        .load::<Message>(&mut conn)?;
+    drop(conn);
+
+    let mut last_messages: HashMap<Uuid, MessageWithMedia> =
+        hydrate_messages_repo(pool, urls, last_messages, viewer_id)?
+            .into_iter()
+            .map(|m| (m.message.conversation_id, m))
+            .collect();
+
+    Ok(conversations_list
+        .into_iter()
+        .map(|conversation| ConversationSummary {
+            participants: participants.remove(&conversation.id).unwrap_or_default(),
+            muted: muted.get(&conversation.id).copied().unwrap_or(false),
+            unread_count: unread_counts.get(&conversation.id).copied().unwrap_or(0),
+            last_message: last_messages.remove(&conversation.id),
+            conversation,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_pool;
+    use crate::models::users::DM_POLICY_FOLLOWING;
+    use crate::repositories::blocks::block_user_repo;
+    use crate::repositories::fixtures::{create_user, send_message_at};
+    use chrono::Duration;
+
+    fn minutes_ago(minutes: i64) -> NaiveDateTime {
+        Utc::now().naive_utc() - Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn direct_conversation_is_shared_by_the_pair() {
+        let Some(pool) = test_pool() else { return };
+        let alice = create_user(&pool);
+        let bob = create_user(&pool);
+
+        let (first, created) =
+            get_or_create_direct_conversation_repo(&pool, &alice.id, &bob.id).unwrap();
+        assert!(created);
+        assert!(!first.is_group);
+
+        let (second, created) =
+            get_or_create_direct_conversation_repo(&pool, &bob.id, &alice.id).unwrap();
+        assert!(!created);
+        assert_eq!(second.id, first.id);
+
+        let mut members = get_member_ids_repo(&pool, &first.id).unwrap();
+        let mut expected = vec![alice.id, bob.id];
+        members.sort();
+        expected.sort();
+        assert_eq!(members, expected);
+    }
+
+    #[test]
+    fn read_receipt_never_moves_back() {
+        let Some(pool) = test_pool() else { return };
+        let alice = create_user(&pool);
+        let bob = create_user(&pool);
+        let (conversation, _) =
+            get_or_create_direct_conversation_repo(&pool, &alice.id, &bob.id).unwrap();
+
+        let older = send_message_at(&pool, &conversation.id, &alice.id, &[], minutes_ago(3));
+        let newer = send_message_at(&pool, &conversation.id, &alice.id, &[], minutes_ago(2));
+
+        let receipt =
+            mark_conversation_read_repo(&pool, &conversation.id, &bob.id, Some(&newer.id)).unwrap();
+        assert_eq!(receipt, Some(Some(newer.created_at)));
+
+        let receipt =
+            mark_conversation_read_repo(&pool, &conversation.id, &bob.id, Some(&older.id)).unwrap();
+        assert_eq!(receipt, Some(Some(newer.created_at)));
+
+        let latest = send_message_at(&pool, &conversation.id, &alice.id, &[], minutes_ago(1));
+        let receipt = mark_conversation_read_repo(&pool, &conversation.id, &bob.id, None).unwrap();
+        assert_eq!(receipt, Some(Some(latest.created_at)));
+    }
+
+    #[test]
+    fn read_receipt_only_accepts_messages_of_the_conversation() {
+        let Some(pool) = test_pool() else { return };
+        let alice = create_user(&pool);
+        let bob = create_user(&pool);
+        let carol = create_user(&pool);
+        let (conversation, _) =
+            get_or_create_direct_conversation_repo(&pool, &alice.id, &bob.id).unwrap();
+        let (other, _) =
+            get_or_create_direct_conversation_repo(&pool, &alice.id, &carol.id).unwrap();
+
+        let elsewhere = send_message_at(&pool, &other.id, &alice.id, &[], minutes_ago(1));
+
+        let receipt =
+            mark_conversation_read_repo(&pool, &conversation.id, &bob.id, Some(&elsewhere.id))
+                .unwrap();
+        assert_eq!(receipt, None);
+
+        // Nothing to read yet: the receipt stays unset
+        let receipt = mark_conversation_read_repo(&pool, &conversation.id, &bob.id, None).unwrap();
+        assert_eq!(receipt, Some(None));
+    }
+
+    #[test]
+    fn unread_count_skips_own_read_and_muted_conversations() {
+        let Some(pool) = test_pool() else { return };
+        let alice = create_user(&pool);
+        let bob = create_user(&pool);
+        let (conversation, _) =
+            get_or_create_direct_conversation_repo(&pool, &alice.id, &bob.id).unwrap();
+
+        send_message_at(&pool, &conversation.id, &alice.id, &[], minutes_ago(1));
+        assert_eq!(unread_conversations_count_repo(&pool, &alice.id).unwrap(), 0);
+        assert_eq!(unread_conversations_count_repo(&pool, &bob.id).unwrap(), 1);
+
+        set_conversation_muted_repo(&pool, &conversation.id, &bob.id, true).unwrap();
+        assert_eq!(unread_conversations_count_repo(&pool, &bob.id).unwrap(), 0);
+
+        set_conversation_muted_repo(&pool, &conversation.id, &bob.id, false).unwrap();
+        assert_eq!(unread_conversations_count_repo(&pool, &bob.id).unwrap(), 1);
+
+        mark_conversation_read_repo(&pool, &conversation.id, &bob.id, None).unwrap();
+        assert_eq!(unread_conversations_count_repo(&pool, &bob.id).unwrap(), 0);
+    }
+
+    #[test]
+    fn leaving_a_group_removes_access() {
+        let Some(pool) = test_pool() else { return };
+        let alice = create_user(&pool);
+        let bob = create_user(&pool);
+        let carol = create_user(&pool);
+        let group = create_group_repo(&pool, &alice.id, None, &[bob.id, carol.id]).unwrap();
+
+        send_message_at(&pool, &group.id, &alice.id, &[], minutes_ago(1));
+        assert_eq!(unread_conversations_count_repo(&pool, &carol.id).unwrap(), 1);
+
+        leave_conversation_repo(&pool, &group.id, &carol.id).unwrap();
+
+        assert!(get_membership_repo(&pool, &group.id, &carol.id).unwrap().is_none());
+        assert!(!get_member_ids_repo(&pool, &group.id).unwrap().contains(&carol.id));
+        assert_eq!(unread_conversations_count_repo(&pool, &carol.id).unwrap(), 0);
+        let listed = get_conversations_repo(&pool, &carol.id, None, 20).unwrap();
+        assert!(listed.items.is_empty());
+    }
+
+    #[test]
+    fn blocked_senders_are_hidden_in_groups() {
+        let Some(pool) = test_pool() else { return };
+        let alice = create_user(&pool);
+        let bob = create_user(&pool);
+        let carol = create_user(&pool);
+        let group = create_group_repo(&pool, &alice.id, None, &[bob.id, carol.id]).unwrap();
+
+        send_message_at(&pool, &group.id, &alice.id, &[], minutes_ago(1));
+        block_user_repo(&pool, &bob.id, &alice.id).unwrap();
+
+        let seen_by_bob = get_messages_repo(&pool, &group.id, &bob.id, None, 20).unwrap();
+        assert!(seen_by_bob.items.is_empty());
+        assert_eq!(unread_conversations_count_repo(&pool, &bob.id).unwrap(), 0);
+
+        let seen_by_carol = get_messages_repo(&pool, &group.id, &carol.id, None, 20).unwrap();
+        assert_eq!(seen_by_carol.items.len(), 1);
+    }
+
+    #[test]
+    fn dm_policy_following_requires_a_follow() {
+        let Some(pool) = test_pool() else { return };
+        let alice = create_user(&pool);
+        let bob = create_user(&pool);
+        let mut conn = get_db_conn(&pool).unwrap();
+        diesel::update(users::table.find(bob.id))
+            .set(users::dm_policy.eq(DM_POLICY_FOLLOWING))
+            .execute(&mut conn)
+            .unwrap();
+        drop(conn);
+
+        assert!(dm_policy_allows_repo(&pool, &bob.id, &alice.id).unwrap());
+        assert!(!dm_policy_allows_repo(&pool, &alice.id, &bob.id).unwrap());
+
+        let mut conn = get_db_conn(&pool).unwrap();
+        diesel::insert_into(follows::table)
+            .values((follows::follower_id.eq(bob.id), follows::followed_id.eq(alice.id)))
+            .execute(&mut conn)
+            .unwrap();
+        drop(conn);
+
+        assert!(dm_policy_allows_repo(&pool, &alice.id, &bob.id).unwrap());
+    }
+}
@@ -0,0 +1,89 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::conversations::Message;
+use crate::models::media::{MEDIA_STATUS_READY, Media, NewMedia, NewMediaBlob};
+use crate::models::users::{NewUser, User};
+use crate::repositories::conversations::send_message_repo;
+use crate::repositories::media::BlobSource;
+use crate::schema::{media, messages, users};
+use crate::storage::blob_key;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// Creates a user with a unique username
+pub fn create_user(pool: &DbPool) -> User {
+    let mut conn = get_db_conn(pool).expect("test connection");
+    let username = format!("user_{}", Uuid::new_v4().simple());
+
+    diesel::insert_into(users::table)
+        .values(&NewUser {
+            email: format!("{}@example.com", username),
+            username,
+            password_hash: "not a hash".to_string(),
+            name: None,
+            bio: None,
+        })
+        .get_result(&mut conn)
+        .expect("insert user")
+}
+
+/// Creates a ready image owned by the user
+pub fn create_media(pool: &DbPool, owner: &Uuid) -> Media {
+    let mut conn = get_db_conn(pool).expect("test connection");
+    let id = Uuid::new_v4();
+
+    diesel::insert_into(media::table)
+        .values(&NewMedia {
+            id,
+            user_id: *owner,
+            s3_key: format!("uploads/test/{}.png", id),
+            file_name: "test.png".to_string(),
+            file_type: "image/png".to_string(),
+            created_at: Utc::now().naive_utc(),
+            size_bytes: 1,
+            width: None,
+            height: None,
+            thumb_key: None,
+            small_key: None,
+            large_key: None,
+            status: MEDIA_STATUS_READY.to_string(),
+            expires_at: None,
+            content_hash: None,
+        })
+        .get_result(&mut conn)
+        .expect("insert media")
+}
+
+/// A freshly stored video blob for `sha256`
+pub fn new_blob(sha256: &str) -> BlobSource {
+    BlobSource::New(NewMediaBlob {
+        sha256: sha256.to_string(),
+        storage_key: blob_key(sha256, "mp4"),
+        file_type: "video/mp4".to_string(),
+        size_bytes: 1,
+        width: None,
+        height: None,
+        thumb_key: None,
+        small_key: None,
+        large_key: None,
+    })
+}
+
+/// Sends a message and dates it `created_at`. Timestamps must be set by hand:
+/// `NOW()` is the same for every row of the test transaction.
+pub fn send_message_at(
+    pool: &DbPool,
+    conversation_id: &Uuid,
+    sender_id: &Uuid,
+    media_ids: &[Uuid],
+    created_at: NaiveDateTime,
+) -> Message {
+    let message = send_message_repo(pool, conversation_id, sender_id, "hello", media_ids)
+        .expect("send message");
+
+    let mut conn = get_db_conn(pool).expect("test connection");
+    diesel::update(messages::table.find(message.id))
+        .set(messages::created_at.eq(created_at))
+        .get_result(&mut conn)
+        .expect("date message")
+}
@@ -1,41 +1,69 @@
 use crate::database::{DbPool, get_db_conn};
 use crate::models::follows::{Follow, NewFollow};
 use crate::models::users::{User, UserPublic};
+use crate::pagination::{Cursor, CursorPage, Direction, keyset_page};
 use crate::schema::follows::dsl::*;
 use crate::schema::users;
+use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use uuid::Uuid;
 
-/// Gets followers of a user
+/// Gets a cursor-paginated list of followers of a user,
+/// ordered by when they started following.
 pub fn get_followers_repo(
     pool: &DbPool,
     user_id_val: &Uuid,
-) -> Result<Vec<UserPublic>, diesel::result::Error> {
+    cursor: Option<&Cursor>,
+    per_page: i64,
+) -> Result<CursorPage<UserPublic>, diesel::result::Error> {
     let mut conn = get_db_conn(pool)?;
 
-    let followers_list = follows
+    let query = follows
         .inner_join(users::table.on(users::id.eq(follower_id)))
-        .filter(followed_id.eq(user_id_val))
-        .select(users::all_columns)
-        .load::<User>(&mut conn)?;
+        .filter(followed_id.eq(*user_id_val))
+        .select((users::all_columns, created_at))
+        .into_boxed();
 
-    Ok(followers_list.into_iter().map(UserPublic::from).collect())
+    let rows = keyset_page(query, created_at, follower_id, cursor, per_page)
+        .load::<(User, NaiveDateTime)>(&mut conn)?;
+
+    Ok(into_user_page(rows, per_page, cursor))
 }
 
-/// Gets followings of a user
+/// Gets a cursor-paginated list of users followed by a user,
+/// ordered by when they were followed.
 pub fn get_followings_repo(
     pool: &DbPool,
     user_id_val: &Uuid,
-) -> Result<Vec<UserPublic>, diesel::result::Error> {
+    cursor: Option<&Cursor>,
+    per_page: i64,
+) -> Result<CursorPage<UserPublic>, diesel::result::Error> {
     let mut conn = get_db_conn(pool)?;
 
-    let followings_list = follows
+    let query = follows
         .inner_join(users::table.on(users::id.eq(followed_id)))
-        .filter(follower_id.eq(user_id_val))
-        .select(users::all_columns)
-        .load::<User>(&mut conn)?;
+        .filter(follower_id.eq(*user_id_val))
+        .select((users::all_columns, created_at))
+        .into_boxed();
+
+    let rows = keyset_page(query, created_at, followed_id, cursor, per_page)
+        .load::<(User, NaiveDateTime)>(&mut conn)?;
+
+    Ok(into_user_page(rows, per_page, cursor))
+}
+
+/// Builds a page of public users keyed by the follow timestamp
+fn into_user_page(
+    rows: Vec<(User, NaiveDateTime)>,
+    per_page: i64,
+    cursor: Option<&Cursor>,
+) -> CursorPage<UserPublic> {
+    let direction = cursor.map(|c| c.direction).unwrap_or(Direction::Next);
 
-    Ok(followings_list.into_iter().map(UserPublic::from).collect())
+    CursorPage::from_rows(rows, per_page, direction, |(user, followed_at)| {
+        (*followed_at, user.id)
+    })
+    .map(|rows| rows.into_iter().map(|(user, _)| UserPublic::from(user)).collect())
 }
 
 /// Follows a user
@@ -76,6 +104,19 @@ pub fn unfollow_user_repo(
     Ok(true)
 }
 
+/// Gets the IDs of every follower of a user
+pub fn get_follower_ids_repo(
+    pool: &DbPool,
+    user_id_val: &Uuid,
+) -> Result<Vec<Uuid>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    follows
+        .filter(followed_id.eq(*user_id_val))
+        .select(follower_id)
+        .load::<Uuid>(&mut conn)
+}
+
 /// Checks if a user is followed by another user
 pub fn is_followed_repo(
     pool: &DbPool,
@@ -0,0 +1,139 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::entities::extract_hashtags;
+use crate::models::hashtags::{Hashtag, NewHashtag, NewTrend, NewTweetHashtag, TrendEntity};
+use crate::models::tweets::Tweet;
+use crate::pagination::{Cursor, CursorPage, Direction};
+use crate::repositories::search::visible_to;
+use crate::repositories::tweets::{keyset_page, tweet_key};
+use crate::schema::{hashtags, trends, tweet_hashtags, tweets, users};
+use chrono::NaiveDateTime;
+use diesel::dsl::count;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// Indexes the hashtags in a tweet's content, creating hashtags seen for the first time.
+/// Must run inside the caller's transaction.
+pub fn index_hashtags(conn: &mut PgConnection, tweet: &Tweet) -> QueryResult<()> {
+    let tags = extract_hashtags(&tweet.content);
+    if tags.is_empty() {
+        return Ok(());
+    }
+
+    let new_hashtags: Vec<NewHashtag> = tags
+        .iter()
+        .map(|tag| NewHashtag {
+            id: Uuid::new_v4(),
+            tag: tag.clone(),
+        })
+        .collect();
+
+    diesel::insert_into(hashtags::table)
+        .values(&new_hashtags)
+        .on_conflict(hashtags::tag)
+        .do_nothing()
+        .execute(conn)?;
+
+    let links: Vec<NewTweetHashtag> = hashtags::table
+        .filter(hashtags::tag.eq_any(&tags))
+        .load::<Hashtag>(conn)?
+        .into_iter()
+        .map(|hashtag| NewTweetHashtag {
+            tweet_id: tweet.id,
+            hashtag_id: hashtag.id,
+            created_at: tweet.created_at,
+        })
+        .collect();
+
+    diesel::insert_into(tweet_hashtags::table)
+        .values(&links)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Gets a cursor-paginated list of tweets with the hashtag that the viewer may see
+pub fn get_hashtag_tweets_repo(
+    pool: &DbPool,
+    tag: &str,
+    viewer_id: Option<&Uuid>,
+    cursor: Option<&Cursor>,
+    per_page: i64,
+) -> Result<CursorPage<Tweet>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let tagged = tweet_hashtags::table
+        .inner_join(hashtags::table)
+        .filter(hashtags::tag.eq(tag.to_string()))
+        .select(tweet_hashtags::tweet_id);
+
+    let query = tweets::table
+        .filter(tweets::id.eq_any(tagged))
+        .filter(visible_to(viewer_id))
+        .into_boxed();
+    let rows = keyset_page(query, cursor, per_page).load::<Tweet>(&mut conn)?;
+
+    let direction = cursor.map(|c| c.direction).unwrap_or(Direction::Next);
+    Ok(CursorPage::from_rows(rows, per_page, direction, tweet_key))
+}
+
+/// Counts the distinct authors that used each hashtag in `[since, until)`.
+/// Tweets of protected accounts are not counted, so trends never reveal them.
+/// Restricted to `hashtag_ids` when given.
+pub fn hashtag_author_counts_repo(
+    pool: &DbPool,
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+    hashtag_ids: Option<&[Uuid]>,
+) -> Result<Vec<(Uuid, i64)>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let mut query = tweet_hashtags::table
+        .inner_join(tweets::table.inner_join(users::table))
+        .filter(tweet_hashtags::created_at.ge(since))
+        .filter(tweet_hashtags::created_at.lt(until))
+        .filter(users::is_protected.eq(false))
+        .group_by(tweet_hashtags::hashtag_id)
+        .select((tweet_hashtags::hashtag_id, count(tweets::user_id).aggregate_distinct()))
+        .into_boxed();
+
+    if let Some(ids) = hashtag_ids {
+        query = query.filter(tweet_hashtags::hashtag_id.eq_any(ids.to_vec()));
+    }
+
+    query.load::<(Uuid, i64)>(&mut conn)
+}
+
+/// Replaces the stored trends with a freshly computed list
+pub fn replace_trends_repo(
+    pool: &DbPool,
+    new_trends: &[NewTrend],
+) -> Result<(), diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        diesel::delete(trends::table).execute(conn)?;
+        diesel::insert_into(trends::table)
+            .values(new_trends)
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+/// Gets the stored trends, highest ranked first
+pub fn get_trends_repo(pool: &DbPool) -> Result<Vec<TrendEntity>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    trends::table
+        .inner_join(hashtags::table)
+        .order(trends::rank.asc())
+        .select((
+            hashtags::tag,
+            trends::rank,
+            trends::score,
+            trends::recent_authors,
+            trends::baseline_authors,
+            trends::computed_at,
+        ))
+        .load::<TrendEntity>(&mut conn)
+}
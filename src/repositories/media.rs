@@ -1,19 +1,166 @@
 use crate::database::{DbPool, get_db_conn};
-use crate::models::media::{Media, NewMedia};
+use crate::models::media::{
+    MEDIA_STATUS_PENDING, MEDIA_STATUS_READY, Media, MediaBlob, MediaUpdate, NewMedia, NewMediaBlob,
+    MessageMedia, NewStorageDeletion, StorageDeletion, TweetMedia,
+};
 use crate::schema::media::dsl::*;
+use crate::media_urls::MediaUrls;
+use crate::models::media::MediaEntity;
+use crate::models::users::{
+    SENSITIVE_MEDIA_BLUR, SENSITIVE_MEDIA_HIDE, SENSITIVE_MEDIA_SHOW, UserPublic,
+};
+use crate::pagination::{Cursor, CursorPage, Direction, keyset_page};
+use crate::schema::{
+    conversation_members, follows, media_blobs, message_media, messages, storage_deletions,
+    tweet_media, users,
+};
 use diesel::prelude::*;
 use uuid::Uuid;
-use chrono::Utc;
-use crate::storage::S3Storage;
+use chrono::{Duration, NaiveDateTime, Utc};
+use crate::storage::MediaStorage;
+use diesel::result::DatabaseErrorKind;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
-/// Uploads a media file record to the database
+/// Maximum number of images attached to one tweet
+pub const MAX_IMAGES_PER_TWEET: usize = 4;
+
+/// Maximum number of videos attached to one tweet (videos cannot be mixed with images)
+pub const MAX_VIDEOS_PER_TWEET: usize = 1;
+
+/// Error while attaching media to a tweet
+#[derive(Debug)]
+pub enum AttachMediaError {
+    /// The request is invalid; the message is safe to return to the client
+    Invalid(String),
+    Database(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for AttachMediaError {
+    fn from(e: diesel::result::Error) -> Self {
+        AttachMediaError::Database(e)
+    }
+}
+
+impl fmt::Display for AttachMediaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttachMediaError::Invalid(message) => write!(f, "{}", message),
+            AttachMediaError::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Error while creating a media record within its owner's quota
+#[derive(Debug)]
+pub enum CreateMediaError {
+    /// The owner already has the maximum number of files
+    TooManyFiles(i64),
+    /// The file is larger than what is left of the quota; holds the bytes left
+    QuotaExceeded(i64),
+    Database(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for CreateMediaError {
+    fn from(e: diesel::result::Error) -> Self {
+        CreateMediaError::Database(e)
+    }
+}
+
+impl fmt::Display for CreateMediaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CreateMediaError::TooManyFiles(max_files) => {
+                write!(f, "Media quota exceeded: at most {} files", max_files)
+            }
+            CreateMediaError::QuotaExceeded(remaining) => {
+                write!(f, "Media quota exceeded: {} bytes left", remaining)
+            }
+            CreateMediaError::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Checks that one more file of `size` bytes fits in the owner's quota of
+/// `(max_bytes, max_files)`. Must run inside the transaction that inserts the media row:
+/// the owner's user row is locked so concurrent uploads are counted one after the other.
+fn reserve_quota(
+    conn: &mut PgConnection,
+    owner_id: &Uuid,
+    size: i64,
+    (max_bytes, max_files): (i64, i64),
+) -> Result<(), CreateMediaError> {
+    users::table
+        .find(owner_id)
+        .select(users::id)
+        .for_update()
+        .first::<Uuid>(conn)?;
+
+    let (bytes_used, files_used) = media_usage(conn, owner_id)?;
+    if files_used >= max_files {
+        return Err(CreateMediaError::TooManyFiles(max_files));
+    }
+    if bytes_used + size > max_bytes {
+        return Err(CreateMediaError::QuotaExceeded((max_bytes - bytes_used).max(0)));
+    }
+
+    Ok(())
+}
+
+/// Creates a ready media record for an uploaded blob, taking a reference on it.
+/// If a blob with the same hash already exists, its keys are used instead.
+/// Fails with `NotFound` if an existing blob was released in the meantime.
 pub fn upload_media_repo(
+    pool: &DbPool,
+    user_id_val: &Uuid,
+    file_name_val: &str,
+    source: &BlobSource,
+    quota: (i64, i64),
+) -> Result<Media, CreateMediaError> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        let blob = acquire_blob(conn, source)?;
+        reserve_quota(conn, user_id_val, blob.size_bytes, quota)?;
+
+        let new_media = NewMedia {
+            id: Uuid::new_v4(),
+            user_id: *user_id_val,
+            s3_key: blob.storage_key,
+            file_name: file_name_val.to_string(),
+            file_type: blob.file_type,
+            created_at: Utc::now().naive_utc(),
+            size_bytes: blob.size_bytes,
+            width: blob.width,
+            height: blob.height,
+            thumb_key: blob.thumb_key,
+            small_key: blob.small_key,
+            large_key: blob.large_key,
+            status: MEDIA_STATUS_READY.to_string(),
+            expires_at: None,
+            content_hash: Some(blob.sha256),
+        };
+
+        let created = diesel::insert_into(media)
+            .values(&new_media)
+            .get_result(conn)?;
+        Ok(created)
+    })
+}
+
+/// Creates a pending media record for a direct upload that must be completed before `expires_at_val`.
+/// Its declared size is reserved from the owner's quota of `(max_bytes, max_files)`.
+#[allow(clippy::too_many_arguments)]
+pub fn create_pending_media_repo(
     pool: &DbPool,
     user_id_val: &Uuid,
     s3_key_val: &str,
     file_name_val: &str,
     file_type_val: &str,
-) -> Result<Media, diesel::result::Error> {
+    size_bytes_val: i64,
+    expires_at_val: NaiveDateTime,
+    quota: (i64, i64),
+) -> Result<Media, CreateMediaError> {
     let mut conn = get_db_conn(pool)?;
 
     let new_media = NewMedia {
@@ -23,34 +170,1002 @@ pub fn upload_media_repo(
         file_name: file_name_val.to_string(),
         file_type: file_type_val.to_string(),
         created_at: Utc::now().naive_utc(),
+        size_bytes: size_bytes_val,
+        width: None,
+        height: None,
+        thumb_key: None,
+        small_key: None,
+        large_key: None,
+        status: MEDIA_STATUS_PENDING.to_string(),
+        expires_at: Some(expires_at_val),
+        content_hash: None,
     };
 
-    diesel::insert_into(media)
-        .values(&new_media)
+    conn.transaction(|conn| {
+        reserve_quota(conn, user_id_val, size_bytes_val, quota)?;
+
+        let created = diesel::insert_into(media)
+            .values(&new_media)
+            .get_result(conn)?;
+        Ok(created)
+    })
+}
+
+/// Gets a blob by the SHA-256 of its uploaded content
+pub fn get_blob_repo(
+    pool: &DbPool,
+    sha256_val: &str,
+) -> Result<Option<MediaBlob>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    media_blobs::table
+        .find(sha256_val)
+        .first(&mut conn)
+        .optional()
+}
+
+/// Content a media row points at
+pub enum BlobSource {
+    /// A blob found by the hash of the upload; it may be released before it is referenced
+    Existing(String),
+    /// Content the upload has just stored under its content-addressed key
+    New(NewMediaBlob),
+}
+
+/// Takes a reference on a blob, creating it if needed. Must run inside the caller's transaction.
+/// Fails with `NotFound` if an existing blob was released since it was looked up;
+/// its objects are gone, so the content must be stored again.
+fn acquire_blob(
+    conn: &mut PgConnection,
+    source: &BlobSource,
+) -> Result<MediaBlob, diesel::result::Error> {
+    match source {
+        BlobSource::Existing(sha256) => {
+            // Lock the row so it cannot be released while the reference is taken
+            let blob = media_blobs::table
+                .find(sha256)
+                .for_update()
+                .first::<MediaBlob>(conn)?;
+
+            diesel::update(media_blobs::table.find(&blob.sha256))
+                .set(media_blobs::ref_count.eq(media_blobs::ref_count + 1))
+                .get_result(conn)
+        }
+        BlobSource::New(new_blob) => diesel::insert_into(media_blobs::table)
+            .values(new_blob)
+            .on_conflict(media_blobs::sha256)
+            .do_update()
+            .set(media_blobs::ref_count.eq(media_blobs::ref_count + 1))
+            .get_result(conn),
+    }
+}
+
+/// Drops the blob references of deleted media rows. Must run inside the caller's transaction.
+/// Returns the storage keys that are no longer used: those of rows without a blob,
+/// and those of blobs whose last reference was dropped.
+pub fn release_media(
+    conn: &mut PgConnection,
+    deleted: &[Media],
+) -> Result<Vec<String>, diesel::result::Error> {
+    let mut keys: Vec<String> = deleted
+        .iter()
+        .filter(|m| m.content_hash.is_none())
+        .flat_map(Media::storage_keys)
+        .collect();
+
+    let mut references: HashMap<&str, i32> = HashMap::new();
+    for hash in deleted.iter().filter_map(|m| m.content_hash.as_deref()) {
+        *references.entry(hash).or_default() += 1;
+    }
+
+    for (hash, count) in &references {
+        diesel::update(media_blobs::table.find(hash))
+            .set(media_blobs::ref_count.eq(media_blobs::ref_count - count))
+            .execute(conn)?;
+    }
+
+    let hashes: Vec<&str> = references.into_keys().collect();
+    let released = diesel::delete(
+        media_blobs::table
+            .filter(media_blobs::sha256.eq_any(&hashes))
+            .filter(media_blobs::ref_count.le(0)),
+    )
+    .get_results::<MediaBlob>(conn)?;
+
+    keys.extend(released.iter().flat_map(MediaBlob::storage_keys));
+    Ok(keys)
+}
+
+/// Gets a media record by ID
+pub fn get_media_repo(pool: &DbPool, media_id: &Uuid) -> Result<Media, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    media.find(media_id).first(&mut conn)
+}
+
+/// Marks a pending upload as ready, pointing it at its blob and taking a reference on it.
+/// Returns `None` if the record is no longer pending (completed twice or expired).
+/// Fails with `NotFound` if an existing blob was released in the meantime.
+pub fn complete_pending_media_repo(
+    pool: &DbPool,
+    media_id: &Uuid,
+    source: &BlobSource,
+) -> Result<Option<Media>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        // Lock the row so the sweeper cannot expire it halfway
+        let pending = media
+            .find(media_id)
+            .filter(status.eq(MEDIA_STATUS_PENDING))
+            .for_update()
+            .first::<Media>(conn)
+            .optional()?;
+        if pending.is_none() {
+            return Ok(None);
+        }
+
+        let blob = acquire_blob(conn, source)?;
+
+        diesel::update(media.find(media_id))
+            .set((
+                status.eq(MEDIA_STATUS_READY),
+                expires_at.eq(None::<NaiveDateTime>),
+                s3_key.eq(blob.storage_key),
+                file_type.eq(blob.file_type),
+                size_bytes.eq(blob.size_bytes),
+                width.eq(blob.width),
+                height.eq(blob.height),
+                thumb_key.eq(blob.thumb_key),
+                small_key.eq(blob.small_key),
+                large_key.eq(blob.large_key),
+                content_hash.eq(Some(blob.sha256)),
+            ))
+            .get_result(conn)
+            .map(Some)
+    })
+}
+
+/// Deletes a pending media record, returning it if it was still pending
+pub fn delete_pending_media_repo(
+    pool: &DbPool,
+    media_id: &Uuid,
+) -> Result<Option<Media>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::delete(media.find(media_id).filter(status.eq(MEDIA_STATUS_PENDING)))
         .get_result(&mut conn)
+        .optional()
 }
 
-/// Deletes all media for a user from S3 and DB
-pub async fn delete_media_by_user_id(
+/// Deletes up to `limit` pending uploads that expired before `now`.
+/// Returns the storage keys whose objects must be removed.
+pub fn expire_pending_media_repo(
     pool: &DbPool,
-    user_id_val: &Uuid,
-    s3_client: &S3Storage,
-) -> Result<(), actix_web::Error> {
-    let mut conn = get_db_conn(pool)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+    now: NaiveDateTime,
+    limit: i64,
+) -> Result<Vec<String>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let expired: Vec<Uuid> = media
+        .filter(status.eq(MEDIA_STATUS_PENDING))
+        .filter(expires_at.lt(now))
+        .select(id)
+        .limit(limit)
+        .load(&mut conn)?;
+
+    // Re-check the status so an upload completed in the meantime is kept
+    diesel::delete(
+        media
+            .filter(id.eq_any(&expired))
+            .filter(status.eq(MEDIA_STATUS_PENDING)),
+    )
+    .returning(s3_key)
+    .get_results(&mut conn)
+}
+
+/// Updates the alt text and sensitive flag of a media item owned by `owner_id`.
+/// Returns `None` if there is no such item.
+pub fn update_media_repo(
+    pool: &DbPool,
+    media_id: &Uuid,
+    owner_id: &Uuid,
+    changes: &MediaUpdate,
+) -> Result<Option<Media>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(media.find(media_id).filter(user_id.eq(owner_id)))
+        .set(changes)
+        .get_result(&mut conn)
+        .optional()
+}
+
+/// Gets a cursor-paginated list of a user's media, newest first,
+/// with the tweet each item is attached to
+pub fn get_user_media_repo(
+    pool: &DbPool,
+    owner_id: &Uuid,
+    cursor: Option<&Cursor>,
+    per_page: i64,
+) -> Result<CursorPage<(Media, Option<Uuid>)>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let query = media
+        .left_join(tweet_media::table)
+        .filter(user_id.eq(owner_id))
+        .select((crate::schema::media::all_columns, tweet_media::tweet_id.nullable()))
+        .into_boxed();
+
+    let rows = keyset_page(query, created_at, id, cursor, per_page)
+        .load::<(Media, Option<Uuid>)>(&mut conn)?;
+
+    let direction = cursor.map(|c| c.direction).unwrap_or(Direction::Next);
+    Ok(CursorPage::from_rows(rows, per_page, direction, |(m, _)| {
+        (m.created_at, m.id)
+    }))
+}
+
+/// Gets the tweet a media item is attached to, if any
+pub fn get_media_tweet_repo(
+    pool: &DbPool,
+    media_id: &Uuid,
+) -> Result<Option<Uuid>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    tweet_media::table
+        .filter(tweet_media::media_id.eq(media_id))
+        .select(tweet_media::tweet_id)
+        .first(&mut conn)
+        .optional()
+}
+
+/// Returns the bytes and number of files a user has stored, pending uploads included
+pub fn media_usage_repo(
+    pool: &DbPool,
+    owner_id: &Uuid,
+) -> Result<(i64, i64), diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    media_usage(&mut conn, owner_id)
+}
+
+/// Bytes and number of files stored by a user, on the caller's connection
+fn media_usage(
+    conn: &mut PgConnection,
+    owner_id: &Uuid,
+) -> Result<(i64, i64), diesel::result::Error> {
+    use diesel::dsl::{count_star, sql};
+    use diesel::sql_types::BigInt;
+
+    // SUM(BIGINT) is NUMERIC in Postgres; cast it back for Diesel
+    media
+        .filter(user_id.eq(owner_id))
+        .select((
+            sql::<BigInt>("COALESCE(SUM(size_bytes), 0)::BIGINT"),
+            count_star(),
+        ))
+        .first::<(i64, i64)>(conn)
+}
 
+/// Outcome of deleting a single media item
+pub enum DeleteMediaResult {
+    /// Deleted; holds the storage keys to remove
+    Deleted(Vec<String>),
+    /// Still attached to a tweet
+    Attached(Uuid),
+    /// Sent in a direct message
+    AttachedToMessage,
+    NotFound,
+}
+
+/// Deletes a media item owned by the user unless it is attached to a tweet or message
+pub fn delete_media_repo(
+    pool: &DbPool,
+    media_id: &Uuid,
+    owner_id: &Uuid,
+) -> Result<DeleteMediaResult, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        // Lock the row so it cannot be attached while we delete it
+        let Some(media_item) = media
+            .find(media_id)
+            .filter(user_id.eq(owner_id))
+            .for_update()
+            .first::<Media>(conn)
+            .optional()?
+        else {
+            return Ok(DeleteMediaResult::NotFound);
+        };
+
+        let attached_to = tweet_media::table
+            .filter(tweet_media::media_id.eq(media_id))
+            .select(tweet_media::tweet_id)
+            .first::<Uuid>(conn)
+            .optional()?;
+        if let Some(tweet_id_val) = attached_to {
+            return Ok(DeleteMediaResult::Attached(tweet_id_val));
+        }
+
+        let sent = diesel::select(diesel::dsl::exists(
+            message_media::table.filter(message_media::media_id.eq(media_id)),
+        ))
+        .get_result::<bool>(conn)?;
+        if sent {
+            return Ok(DeleteMediaResult::AttachedToMessage);
+        }
+
+        diesel::delete(media.find(media_id)).execute(conn)?;
+
+        Ok(DeleteMediaResult::Deleted(release_media(conn, &[media_item])?))
+    })
+}
+
+/// Validates media IDs and attaches them to a tweet in the given order.
+/// The caller must own every item, none may be attached elsewhere, and a tweet
+/// takes up to 4 images or a single video. Must run inside the caller's transaction.
+pub fn attach_media(
+    conn: &mut PgConnection,
+    tweet_id_val: &Uuid,
+    owner_id: &Uuid,
+    media_ids: &[Uuid],
+) -> Result<(), AttachMediaError> {
+    if media_ids.is_empty() {
+        return Ok(());
+    }
+
+    lock_attachable_media(conn, owner_id, media_ids, "tweet")?;
+
+    let links: Vec<TweetMedia> = media_ids
+        .iter()
+        .enumerate()
+        .map(|(position, media_id)| TweetMedia {
+            tweet_id: *tweet_id_val,
+            media_id: *media_id,
+            position: position as i16,
+        })
+        .collect();
+
+    diesel::insert_into(tweet_media::table)
+        .values(&links)
+        .execute(conn)
+        .map_err(already_attached_error)?;
+
+    Ok(())
+}
+
+/// Validates media IDs and attaches them to a direct message in the given order,
+/// with the same rules as [`attach_media`]. Must run inside the caller's transaction.
+pub fn attach_message_media(
+    conn: &mut PgConnection,
+    message_id: &Uuid,
+    owner_id: &Uuid,
+    media_ids: &[Uuid],
+) -> Result<(), AttachMediaError> {
+    if media_ids.is_empty() {
+        return Ok(());
+    }
+
+    lock_attachable_media(conn, owner_id, media_ids, "message")?;
+
+    let links: Vec<MessageMedia> = media_ids
+        .iter()
+        .enumerate()
+        .map(|(position, media_id)| MessageMedia {
+            message_id: *message_id,
+            media_id: *media_id,
+            position: position as i16,
+        })
+        .collect();
+
+    diesel::insert_into(message_media::table)
+        .values(&links)
+        .execute(conn)
+        .map_err(already_attached_error)?;
+
+    Ok(())
+}
+
+fn already_attached_error(e: diesel::result::Error) -> AttachMediaError {
+    match e {
+        diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            AttachMediaError::Invalid("Media is already attached".to_string())
+        }
+        e => AttachMediaError::Database(e),
+    }
+}
+
+/// Locks media about to be attached to a tweet or message (`target`) and checks
+/// that the caller owns every item, that all are uploaded and unattached, and
+/// that together they are up to 4 images or a single video
+fn lock_attachable_media(
+    conn: &mut PgConnection,
+    owner_id: &Uuid,
+    media_ids: &[Uuid],
+    target: &str,
+) -> Result<(), AttachMediaError> {
+    let unique_ids: HashSet<&Uuid> = media_ids.iter().collect();
+    if unique_ids.len() != media_ids.len() {
+        return Err(AttachMediaError::Invalid(
+            "The same media cannot be attached twice".to_string(),
+        ));
+    }
+
+    // Lock the rows so concurrent tweets and messages cannot claim the same media
     let media_list = media
-        .filter(user_id.eq(user_id_val))
-        .load::<Media>(&mut conn)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+        .filter(id.eq_any(media_ids))
+        .for_update()
+        .load::<Media>(conn)?;
 
-    for m in media_list {
-        s3_client.delete_file(&m.s3_key).await?;
+    for media_id in media_ids {
+        // Media owned by someone else is reported as missing to avoid leaking its existence
+        let owned = media_list
+            .iter()
+            .any(|m| m.id == *media_id && m.user_id == *owner_id);
+        if !owned {
+            return Err(AttachMediaError::Invalid(format!(
+                "Media {} not found",
+                media_id
+            )));
+        }
     }
 
-    diesel::delete(media.filter(user_id.eq(user_id_val)))
-        .execute(&mut conn)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+    if let Some(pending) = media_list.iter().find(|m| m.status != MEDIA_STATUS_READY) {
+        return Err(AttachMediaError::Invalid(format!(
+            "Media {} has not finished uploading",
+            pending.id
+        )));
+    }
+
+    let already_attached = tweet_media::table
+        .filter(tweet_media::media_id.eq_any(media_ids))
+        .select(tweet_media::media_id)
+        .first::<Uuid>(conn)
+        .optional()?;
+    if let Some(media_id) = already_attached {
+        return Err(AttachMediaError::Invalid(format!(
+            "Media {} is already attached to a tweet",
+            media_id
+        )));
+    }
+
+    let already_sent = message_media::table
+        .filter(message_media::media_id.eq_any(media_ids))
+        .select(message_media::media_id)
+        .first::<Uuid>(conn)
+        .optional()?;
+    if let Some(media_id) = already_sent {
+        return Err(AttachMediaError::Invalid(format!(
+            "Media {} is already attached to a message",
+            media_id
+        )));
+    }
+
+    let videos = media_list
+        .iter()
+        .filter(|m| m.file_type.starts_with("video/"))
+        .count();
+    let images = media_list.len() - videos;
+
+    if videos > MAX_VIDEOS_PER_TWEET || (videos > 0 && images > 0) {
+        return Err(AttachMediaError::Invalid(format!(
+            "A {} can have at most {} video, which cannot be combined with images",
+            target, MAX_VIDEOS_PER_TWEET
+        )));
+    }
+    if images > MAX_IMAGES_PER_TWEET {
+        return Err(AttachMediaError::Invalid(format!(
+            "A {} can have at most {} images",
+            target, MAX_IMAGES_PER_TWEET
+        )));
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Whether a viewer may fetch a media item
+pub struct MediaAccess {
+    /// The owner's account is protected
+    pub is_private: bool,
+    pub can_view: bool,
+}
+
+/// Checks access to a media item: media is public unless its owner is protected,
+/// in which case only the owner and their followers may see it. Media sent in a
+/// direct message is private to the sender and the conversation's current members.
+pub fn media_access_repo(
+    pool: &DbPool,
+    media_item: &Media,
+    viewer_id: Option<&Uuid>,
+) -> Result<MediaAccess, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let conversation = message_media::table
+        .inner_join(messages::table)
+        .filter(message_media::media_id.eq(media_item.id))
+        .select(messages::conversation_id)
+        .first::<Uuid>(&mut conn)
+        .optional()?;
+    if let Some(conversation) = conversation {
+        let can_view = match viewer_id {
+            Some(viewer) if *viewer == media_item.user_id => true,
+            Some(viewer) => diesel::select(diesel::dsl::exists(
+                conversation_members::table
+                    .filter(conversation_members::conversation_id.eq(conversation))
+                    .filter(conversation_members::user_id.eq(viewer))
+                    .filter(conversation_members::left_at.is_null()),
+            ))
+            .get_result(&mut conn)?,
+            None => false,
+        };
+
+        return Ok(MediaAccess {
+            is_private: true,
+            can_view,
+        });
+    }
+
+    let is_private = users::table
+        .find(media_item.user_id)
+        .select(users::is_protected)
+        .first::<bool>(&mut conn)?;
+    if !is_private {
+        return Ok(MediaAccess {
+            is_private,
+            can_view: true,
+        });
+    }
+
+    let can_view = match viewer_id {
+        Some(viewer) if *viewer == media_item.user_id => true,
+        Some(viewer) => diesel::select(diesel::dsl::exists(
+            follows::table
+                .filter(follows::follower_id.eq(viewer))
+                .filter(follows::followed_id.eq(media_item.user_id)),
+        ))
+        .get_result(&mut conn)?,
+        None => false,
+    };
+
+    Ok(MediaAccess {
+        is_private,
+        can_view,
+    })
+}
+
+/// Returns the protected users among `owners` whose media the viewer may not see
+pub fn hidden_media_owners<'a>(
+    conn: &mut PgConnection,
+    owners: impl Iterator<Item = &'a UserPublic>,
+    viewer_id: Option<&Uuid>,
+) -> Result<HashSet<Uuid>, diesel::result::Error> {
+    let mut protected: HashSet<Uuid> = owners
+        .filter(|u| u.is_protected && Some(&u.id) != viewer_id)
+        .map(|u| u.id)
+        .collect();
+
+    if let (Some(viewer), false) = (viewer_id, protected.is_empty()) {
+        let followed: Vec<Uuid> = follows::table
+            .filter(follows::follower_id.eq(viewer))
+            .filter(follows::followed_id.eq_any(&protected))
+            .select(follows::followed_id)
+            .load(conn)?;
+        for user in followed {
+            protected.remove(&user);
+        }
+    }
+
+    Ok(protected)
+}
+
+/// The viewer's `sensitive_media` preference; anonymous viewers get blurred media
+pub fn sensitive_media_preference(
+    conn: &mut PgConnection,
+    viewer_id: Option<&Uuid>,
+) -> Result<String, diesel::result::Error> {
+    let preference = match viewer_id {
+        Some(viewer) => users::table
+            .find(viewer)
+            .select(users::sensitive_media)
+            .first::<String>(conn)
+            .optional()?,
+        None => None,
+    };
+
+    Ok(preference.unwrap_or_else(|| SENSITIVE_MEDIA_BLUR.to_string()))
+}
+
+/// Builds the response for a media item as the viewer sees it: other users'
+/// sensitive media is shown, blurred or hidden (`None`) as the viewer prefers
+pub fn viewer_media_entity(
+    media_item: &Media,
+    urls: &MediaUrls,
+    viewer_id: Option<&Uuid>,
+    sensitive_media: &str,
+) -> Option<MediaEntity> {
+    let mut entity = MediaEntity::new(media_item, urls);
+    if media_item.sensitive && viewer_id != Some(&media_item.user_id) {
+        match sensitive_media {
+            SENSITIVE_MEDIA_HIDE => return None,
+            SENSITIVE_MEDIA_SHOW => {}
+            _ => entity.blurred = true,
+        }
+    }
+
+    Some(entity)
+}
+
+/// Gets the media attached to the given tweets, ordered by position
+pub fn get_tweets_media(
+    conn: &mut PgConnection,
+    tweet_ids: &[Uuid],
+) -> Result<Vec<(Uuid, Media)>, diesel::result::Error> {
+    tweet_media::table
+        .inner_join(media)
+        .filter(tweet_media::tweet_id.eq_any(tweet_ids))
+        .order((tweet_media::tweet_id, tweet_media::position))
+        .select((tweet_media::tweet_id, crate::schema::media::all_columns))
+        .load::<(Uuid, Media)>(conn)
+}
+
+/// Deletes the media attached to a tweet. Must run inside the caller's transaction.
+/// Returns the storage keys no longer referenced so the objects can be removed afterwards.
+pub fn delete_tweet_media(
+    conn: &mut PgConnection,
+    tweet_id_val: &Uuid,
+) -> Result<Vec<String>, diesel::result::Error> {
+    let attached = tweet_media::table
+        .filter(tweet_media::tweet_id.eq(tweet_id_val))
+        .select(tweet_media::media_id);
+
+    let deleted = diesel::delete(media.filter(id.eq_any(attached))).get_results::<Media>(conn)?;
+
+    release_media(conn, &deleted)
+}
+
+/// Gets ready media created before `cutoff` that is not attached to any tweet or message
+pub fn unattached_media_repo(
+    pool: &DbPool,
+    cutoff: NaiveDateTime,
+) -> Result<Vec<Media>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    media
+        .filter(status.eq(MEDIA_STATUS_READY))
+        .filter(created_at.lt(cutoff))
+        .filter(diesel::dsl::not(diesel::dsl::exists(
+            tweet_media::table.filter(tweet_media::media_id.eq(id)),
+        )))
+        .filter(diesel::dsl::not(diesel::dsl::exists(
+            message_media::table.filter(message_media::media_id.eq(id)),
+        )))
+        .order(created_at.asc())
+        .load(&mut conn)
+}
+
+/// Gets ready media created before `cutoff`, for checking that its objects still exist
+pub fn ready_media_before_repo(
+    pool: &DbPool,
+    cutoff: NaiveDateTime,
+) -> Result<Vec<Media>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    media
+        .filter(status.eq(MEDIA_STATUS_READY))
+        .filter(created_at.lt(cutoff))
+        .order(created_at.asc())
+        .load(&mut conn)
+}
+
+/// Returns every storage key referenced by a media record, blob or profile image,
+/// pending uploads included
+pub fn referenced_storage_keys_repo(
+    pool: &DbPool,
+) -> Result<HashSet<String>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let media_keys = media
+        .select((s3_key, thumb_key, small_key, large_key))
+        .load::<(String, Option<String>, Option<String>, Option<String>)>(&mut conn)?;
+    let blob_keys = media_blobs::table
+        .select((
+            media_blobs::storage_key,
+            media_blobs::thumb_key,
+            media_blobs::small_key,
+            media_blobs::large_key,
+        ))
+        .load::<(String, Option<String>, Option<String>, Option<String>)>(&mut conn)?;
+
+    let profile_keys = users::table
+        .select((users::avatar_key, users::banner_key))
+        .filter(users::avatar_key.is_not_null().or(users::banner_key.is_not_null()))
+        .load::<(Option<String>, Option<String>)>(&mut conn)?;
+
+    Ok(media_keys
+        .into_iter()
+        .chain(blob_keys)
+        .flat_map(|(original, thumb, small, large)| {
+            std::iter::once(original).chain(thumb).chain(small).chain(large)
+        })
+        .chain(profile_keys.into_iter().flat_map(|(avatar, banner)| avatar.into_iter().chain(banner)))
+        .collect())
+}
+
+/// Deletes media records that are still ready and attached to no tweet or message.
+/// Returns the storage keys whose objects must be removed.
+pub fn delete_unattached_media_repo(
+    pool: &DbPool,
+    media_ids: &[Uuid],
+) -> Result<Vec<String>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        // Lock the rows and re-check them so media attached in the meantime is kept
+        let unattached = media
+            .filter(id.eq_any(media_ids))
+            .filter(status.eq(MEDIA_STATUS_READY))
+            .filter(diesel::dsl::not(diesel::dsl::exists(
+                tweet_media::table.filter(tweet_media::media_id.eq(id)),
+            )))
+            .filter(diesel::dsl::not(diesel::dsl::exists(
+                message_media::table.filter(message_media::media_id.eq(id)),
+            )))
+            .for_update()
+            .load::<Media>(conn)?;
+
+        delete_locked_media(conn, unattached)
+    })
+}
+
+/// Deletes media records whatever they are attached to, detaching them from their tweets and messages.
+/// Returns the storage keys whose objects must be removed.
+pub fn delete_media_records_repo(
+    pool: &DbPool,
+    media_ids: &[Uuid],
+) -> Result<Vec<String>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        let locked = media
+            .filter(id.eq_any(media_ids))
+            .for_update()
+            .load::<Media>(conn)?;
+
+        delete_locked_media(conn, locked)
+    })
+}
+
+fn delete_locked_media(
+    conn: &mut PgConnection,
+    locked: Vec<Media>,
+) -> Result<Vec<String>, diesel::result::Error> {
+    let locked_ids: Vec<Uuid> = locked.iter().map(|m| m.id).collect();
+    diesel::delete(media.filter(id.eq_any(&locked_ids))).execute(conn)?;
+
+    release_media(conn, &locked)
+}
+
+/// Deletes objects from storage. Failures are queued for a background retry
+/// instead of failing the caller, since the database rows are already gone.
+pub async fn delete_storage_objects(pool: &DbPool, storage: &dyn MediaStorage, keys: Vec<String>) {
+    for key in keys {
+        if let Err(e) = storage.delete(&key).await {
+            eprintln!("Storage delete error for {}: {}", key, e);
+
+            if let Err(e) = enqueue_storage_deletion_repo(pool, &key, &e.to_string()) {
+                eprintln!("Database enqueue storage deletion error for {}: {}", key, e);
+            }
+        }
+    }
+}
+
+/// Queues a storage object for deletion retry
+pub fn enqueue_storage_deletion_repo(
+    pool: &DbPool,
+    key: &str,
+    error: &str,
+) -> Result<StorageDeletion, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let new_deletion = NewStorageDeletion {
+        id: Uuid::new_v4(),
+        storage_key: key.to_string(),
+        last_error: Some(error.to_string()),
+    };
+
+    diesel::insert_into(storage_deletions::table)
+        .values(&new_deletion)
+        .get_result(&mut conn)
+}
+
+/// Gets queued deletions whose next attempt is due
+pub fn due_storage_deletions_repo(
+    pool: &DbPool,
+    limit: i64,
+) -> Result<Vec<StorageDeletion>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    storage_deletions::table
+        .filter(storage_deletions::next_attempt_at.le(Utc::now().naive_utc()))
+        .order(storage_deletions::next_attempt_at.asc())
+        .limit(limit)
+        .load::<StorageDeletion>(&mut conn)
+}
+
+/// Removes a deletion from the queue after it succeeded
+pub fn complete_storage_deletion_repo(
+    pool: &DbPool,
+    deletion_id: &Uuid,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::delete(storage_deletions::table.find(deletion_id)).execute(&mut conn)
+}
+
+/// Records a failed retry and schedules the next one with exponential backoff (capped at 6 hours)
+pub fn reschedule_storage_deletion_repo(
+    pool: &DbPool,
+    deletion: &StorageDeletion,
+    error: &str,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let backoff_minutes = 2i64.saturating_pow(deletion.attempts.clamp(0, 16) as u32).min(6 * 60);
+
+    diesel::update(storage_deletions::table.find(deletion.id))
+        .set((
+            storage_deletions::attempts.eq(deletion.attempts + 1),
+            storage_deletions::last_error.eq(error),
+            storage_deletions::next_attempt_at
+                .eq(Utc::now().naive_utc() + Duration::minutes(backoff_minutes)),
+        ))
+        .execute(&mut conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BlobSource, CreateMediaError, DeleteMediaResult, create_pending_media_repo,
+        delete_media_repo, delete_storage_objects, get_blob_repo, media_access_repo,
+        upload_media_repo,
+    };
+    use crate::database::test_pool;
+    use crate::repositories::conversations::{
+        create_group_repo, get_or_create_direct_conversation_repo, leave_conversation_repo,
+    };
+    use crate::repositories::fixtures::{create_media, create_user, new_blob, send_message_at};
+    use crate::storage::{MediaStorage, MemoryStorage};
+    use actix_web::web::Bytes;
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    #[test]
+    fn message_media_is_private_to_the_conversation() {
+        let Some(pool) = test_pool() else { return };
+        let alice = create_user(&pool);
+        let bob = create_user(&pool);
+        let carol = create_user(&pool);
+        let (conversation, _) =
+            get_or_create_direct_conversation_repo(&pool, &alice.id, &bob.id).unwrap();
+
+        let photo = create_media(&pool, &alice.id);
+        send_message_at(&pool, &conversation.id, &alice.id, &[photo.id], Utc::now().naive_utc());
+
+        let owner = media_access_repo(&pool, &photo, Some(&alice.id)).unwrap();
+        assert!(owner.is_private);
+        assert!(owner.can_view);
+        assert!(media_access_repo(&pool, &photo, Some(&bob.id)).unwrap().can_view);
+        assert!(!media_access_repo(&pool, &photo, Some(&carol.id)).unwrap().can_view);
+        assert!(!media_access_repo(&pool, &photo, None).unwrap().can_view);
+    }
+
+    #[test]
+    fn message_media_is_hidden_from_members_who_left() {
+        let Some(pool) = test_pool() else { return };
+        let alice = create_user(&pool);
+        let bob = create_user(&pool);
+        let carol = create_user(&pool);
+        let group = create_group_repo(&pool, &alice.id, None, &[bob.id, carol.id]).unwrap();
+
+        let photo = create_media(&pool, &alice.id);
+        send_message_at(&pool, &group.id, &alice.id, &[photo.id], Utc::now().naive_utc());
+        assert!(media_access_repo(&pool, &photo, Some(&carol.id)).unwrap().can_view);
+
+        leave_conversation_repo(&pool, &group.id, &carol.id).unwrap();
+        assert!(!media_access_repo(&pool, &photo, Some(&carol.id)).unwrap().can_view);
+        assert!(media_access_repo(&pool, &photo, Some(&bob.id)).unwrap().can_view);
+    }
+
+    #[test]
+    fn message_media_cannot_be_deleted() {
+        let Some(pool) = test_pool() else { return };
+        let alice = create_user(&pool);
+        let bob = create_user(&pool);
+        let (conversation, _) =
+            get_or_create_direct_conversation_repo(&pool, &alice.id, &bob.id).unwrap();
+
+        let photo = create_media(&pool, &alice.id);
+        send_message_at(&pool, &conversation.id, &alice.id, &[photo.id], Utc::now().naive_utc());
+
+        assert!(matches!(
+            delete_media_repo(&pool, &photo.id, &alice.id).unwrap(),
+            DeleteMediaResult::AttachedToMessage
+        ));
+    }
+
+    #[test]
+    fn released_blobs_are_not_referenced_again() {
+        let Some(pool) = test_pool() else { return };
+        let alice = create_user(&pool);
+        let sha256 = Uuid::new_v4().simple().to_string();
+
+        let quota = (1000, 10);
+        let first = upload_media_repo(&pool, &alice.id, "a.mp4", &new_blob(&sha256), quota).unwrap();
+        let existing = BlobSource::Existing(sha256.clone());
+        let second = upload_media_repo(&pool, &alice.id, "b.mp4", &existing, quota).unwrap();
+        assert_eq!(second.s3_key, first.s3_key);
+        assert_eq!(get_blob_repo(&pool, &sha256).unwrap().unwrap().ref_count, 2);
+
+        for item in [&first, &second] {
+            delete_media_repo(&pool, &item.id, &alice.id).unwrap();
+        }
+        assert!(get_blob_repo(&pool, &sha256).unwrap().is_none());
+
+        assert!(matches!(
+            upload_media_repo(&pool, &alice.id, "c.mp4", &existing, quota),
+            Err(CreateMediaError::Database(diesel::result::Error::NotFound))
+        ));
+        assert!(get_blob_repo(&pool, &sha256).unwrap().is_none());
+    }
+
+    #[test]
+    fn pending_uploads_reserve_quota() {
+        let Some(pool) = test_pool() else { return };
+        let alice = create_user(&pool);
+        let expires_at = Utc::now().naive_utc() + Duration::hours(1);
+        let create = |size, quota| {
+            create_pending_media_repo(
+                &pool, &alice.id, "uploads/test/a.mp4", "a.mp4", "video/mp4", size, expires_at,
+                quota,
+            )
+        };
+
+        create(600, (1000, 2)).unwrap();
+        assert!(matches!(
+            create(500, (1000, 2)),
+            Err(CreateMediaError::QuotaExceeded(400))
+        ));
+        create(400, (1000, 2)).unwrap();
+        assert!(matches!(
+            create(1, (2000, 2)),
+            Err(CreateMediaError::TooManyFiles(2))
+        ));
+    }
+
+    #[test]
+    fn deleting_a_released_blob_keeps_the_content_uploaded_again() {
+        let Some(pool) = test_pool() else { return };
+        let storage = MemoryStorage::new();
+        let alice = create_user(&pool);
+        let sha256 = Uuid::new_v4().simple().to_string();
+        let quota = (1000, 10);
+
+        actix_web::rt::System::new().block_on(async {
+            let first = upload_media_repo(&pool, &alice.id, "a.mp4", &new_blob(&sha256), quota).unwrap();
+            storage.put(&first.s3_key, Bytes::from_static(b"video"), "video/mp4").await.unwrap();
+            let DeleteMediaResult::Deleted(released) =
+                delete_media_repo(&pool, &first.id, &alice.id).unwrap()
+            else {
+                panic!("media not deleted");
+            };
+
+            // The same content is uploaded again before the released objects are deleted
+            let second = upload_media_repo(&pool, &alice.id, "b.mp4", &new_blob(&sha256), quota).unwrap();
+            storage.put(&second.s3_key, Bytes::from_static(b"video"), "video/mp4").await.unwrap();
+            delete_storage_objects(&pool, &storage, released).await;
+
+            assert!(storage.head(&first.s3_key).await.unwrap().is_none());
+            assert!(storage.head(&second.s3_key).await.unwrap().is_some());
+        });
+    }
+}
@@ -0,0 +1,101 @@
+use crate::database::{DbPool, get_db_conn, lower};
+use crate::entities::extract_mentions;
+use crate::models::tweets::{NewTweetMention, Tweet};
+use crate::pagination::{Cursor, CursorPage, Direction};
+use crate::repositories::search::visible_to;
+use crate::repositories::tweets::{keyset_page, tweet_key};
+use crate::schema::{tweet_mentions, tweets, users};
+use diesel::prelude::*;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Resolves the `@usernames` in a tweet's content and records the users mentioned.
+/// Names that match no user are left as plain text. Must run inside the caller's transaction.
+pub fn index_mentions(conn: &mut PgConnection, tweet: &Tweet) -> QueryResult<()> {
+    let usernames = extract_mentions(&tweet.content);
+    if usernames.is_empty() {
+        return Ok(());
+    }
+
+    let mentions: Vec<NewTweetMention> = users::table
+        .filter(lower(users::username).eq_any(&usernames))
+        .select(users::id)
+        .load::<Uuid>(conn)?
+        .into_iter()
+        .map(|mentioned_id| NewTweetMention {
+            tweet_id: tweet.id,
+            user_id: mentioned_id,
+            created_at: tweet.created_at,
+        })
+        .collect();
+
+    diesel::insert_into(tweet_mentions::table)
+        .values(&mentions)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Users mentioned in one tweet: lowercased username to ID and username
+pub type MentionedUsers = HashMap<String, (Uuid, String)>;
+
+/// Gets the users mentioned in each tweet
+pub fn get_tweets_mentions(
+    conn: &mut PgConnection,
+    tweet_ids: &[Uuid],
+) -> QueryResult<HashMap<Uuid, MentionedUsers>> {
+    let rows = tweet_mentions::table
+        .inner_join(users::table)
+        .filter(tweet_mentions::tweet_id.eq_any(tweet_ids))
+        .select((tweet_mentions::tweet_id, users::id, users::username))
+        .load::<(Uuid, Uuid, String)>(conn)?;
+
+    let mut mentions: HashMap<Uuid, MentionedUsers> = HashMap::new();
+    for (tweet_id, mentioned_id, username) in rows {
+        mentions
+            .entry(tweet_id)
+            .or_default()
+            .insert(username.to_lowercase(), (mentioned_id, username));
+    }
+
+    Ok(mentions)
+}
+
+/// Gets a cursor-paginated list of other users' tweets mentioning the user,
+/// limited to those the user may see
+pub fn get_mentions_timeline_repo(
+    pool: &DbPool,
+    user_id_val: &Uuid,
+    cursor: Option<&Cursor>,
+    per_page: i64,
+) -> Result<CursorPage<Tweet>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let mentioning = tweet_mentions::table
+        .filter(tweet_mentions::user_id.eq(*user_id_val))
+        .select(tweet_mentions::tweet_id);
+
+    let query = tweets::table
+        .filter(tweets::id.eq_any(mentioning))
+        .filter(tweets::user_id.ne(*user_id_val))
+        .filter(visible_to(Some(user_id_val)))
+        .into_boxed();
+    let rows = keyset_page(query, cursor, per_page).load::<Tweet>(&mut conn)?;
+
+    let direction = cursor.map(|c| c.direction).unwrap_or(Direction::Next);
+    Ok(CursorPage::from_rows(rows, per_page, direction, tweet_key))
+}
+
+/// Gets the IDs of the users mentioned in a tweet
+pub fn get_mentioned_user_ids_repo(
+    pool: &DbPool,
+    tweet_id_val: &Uuid,
+) -> Result<Vec<Uuid>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    tweet_mentions::table
+        .filter(tweet_mentions::tweet_id.eq(tweet_id_val))
+        .select(tweet_mentions::user_id)
+        .load::<Uuid>(&mut conn)
+}
@@ -1,5 +1,15 @@
+pub mod blocks;
+pub mod conversations;
+/// Rows for repository tests, created on a pool from [`crate::database::test_pool`]
+#[cfg(test)]
+pub mod fixtures;
 pub mod followers;
+pub mod hashtags;
 pub mod likes;
 pub mod media;
+pub mod mentions;
+pub mod notifications;
+pub mod refresh_tokens;
+pub mod search;
 pub mod tweets;
-pub mod users;
\ No newline at end of file
+pub mod users;
@@ -0,0 +1,214 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::notifications::{NewNotification, NotificationGroupRow};
+use crate::models::users::{User, UserPublic};
+use crate::pagination::{Cursor, CursorPage, Direction};
+use crate::schema::notifications::dsl::*;
+use crate::schema::users;
+use chrono::Utc;
+use diesel::dsl::count;
+use diesel::prelude::*;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Actors listed per notification group
+pub const GROUP_ACTORS: i64 = 3;
+
+/// Records a notification. Returns `false` if the actor was already notified in that group.
+pub fn insert_notification_repo(
+    pool: &DbPool,
+    new_notification: &NewNotification,
+) -> Result<bool, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let inserted = diesel::insert_into(notifications)
+        .values(new_notification)
+        .on_conflict_do_nothing()
+        .execute(&mut conn)?;
+
+    Ok(inserted > 0)
+}
+
+/// The newest notification of each of the recipient's groups, with the group's size
+const NOTIFICATION_GROUPS_SQL: &str = "
+    SELECT DISTINCT ON (n.group_key) n.*, COUNT(*) OVER (PARTITION BY n.group_key) AS actors_count
+    FROM notifications n
+    WHERE n.recipient_id = $1
+    ORDER BY n.group_key, n.created_at DESC, n.id DESC
+";
+
+/// Gets a cursor-paginated list of the recipient's notification groups,
+/// ordered by their newest notification
+pub fn get_notification_groups_repo(
+    pool: &DbPool,
+    recipient: &Uuid,
+    cursor: Option<&Cursor>,
+    per_page: i64,
+) -> Result<CursorPage<NotificationGroupRow>, diesel::result::Error> {
+    use diesel::sql_types::{BigInt, Timestamp, Uuid as SqlUuid};
+
+    let mut conn = get_db_conn(pool)?;
+
+    let rows = match cursor {
+        Some(c) => {
+            let (comparison, ordering) = match c.direction {
+                Direction::Next => ("<", "DESC"),
+                Direction::Prev => (">", "ASC"),
+            };
+            diesel::sql_query(format!(
+                "SELECT * FROM ({}) grouped WHERE (grouped.created_at, grouped.id) {} ($2, $3) \
+                 ORDER BY grouped.created_at {}, grouped.id {} LIMIT $4",
+                NOTIFICATION_GROUPS_SQL, comparison, ordering, ordering
+            ))
+            .bind::<SqlUuid, _>(recipient)
+            .bind::<Timestamp, _>(c.created_at)
+            .bind::<SqlUuid, _>(c.id)
+            .bind::<BigInt, _>(per_page + 1)
+            .load::<NotificationGroupRow>(&mut conn)?
+        }
+        None => diesel::sql_query(format!(
+            "SELECT * FROM ({}) grouped ORDER BY grouped.created_at DESC, grouped.id DESC LIMIT $2",
+            NOTIFICATION_GROUPS_SQL
+        ))
+        .bind::<SqlUuid, _>(recipient)
+        .bind::<BigInt, _>(per_page + 1)
+        .load::<NotificationGroupRow>(&mut conn)?,
+    };
+
+    let direction = cursor.map(|c| c.direction).unwrap_or(Direction::Next);
+    Ok(CursorPage::from_rows(rows, per_page, direction, |row| {
+        (row.created_at, row.id)
+    }))
+}
+
+/// Gets the recipient's group that a notification belongs to, as of now
+pub fn get_notification_group_repo(
+    pool: &DbPool,
+    recipient: &Uuid,
+    notification_id: &Uuid,
+) -> Result<Option<NotificationGroupRow>, diesel::result::Error> {
+    use diesel::sql_types::Uuid as SqlUuid;
+
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::sql_query(format!(
+        "SELECT * FROM ({}) grouped WHERE grouped.group_key = \
+         (SELECT n.group_key FROM notifications n WHERE n.id = $2 AND n.recipient_id = $1)",
+        NOTIFICATION_GROUPS_SQL
+    ))
+    .bind::<SqlUuid, _>(recipient)
+    .bind::<SqlUuid, _>(notification_id)
+    .get_result::<NotificationGroupRow>(&mut conn)
+    .optional()
+}
+
+#[derive(QueryableByName)]
+struct GroupActor {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    key: String,
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    actor: Uuid,
+}
+
+/// Gets the [`GROUP_ACTORS`] most recent actors of each group, newest first
+pub fn get_group_actors_repo(
+    pool: &DbPool,
+    recipient: &Uuid,
+    group_keys: &[String],
+) -> Result<HashMap<String, Vec<UserPublic>>, diesel::result::Error> {
+    use diesel::sql_types::{Array, BigInt, Text, Uuid as SqlUuid};
+
+    if group_keys.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut conn = get_db_conn(pool)?;
+
+    let rows = diesel::sql_query(
+        "SELECT ranked.group_key AS key, ranked.actor_id AS actor FROM (
+             SELECT n.group_key, n.actor_id,
+                    ROW_NUMBER() OVER (PARTITION BY n.group_key ORDER BY n.created_at DESC, n.id DESC) AS position
+             FROM notifications n
+             WHERE n.recipient_id = $1 AND n.group_key = ANY($2)
+         ) ranked
+         WHERE ranked.position <= $3
+         ORDER BY ranked.group_key, ranked.position",
+    )
+    .bind::<SqlUuid, _>(recipient)
+    .bind::<Array<Text>, _>(group_keys)
+    .bind::<BigInt, _>(GROUP_ACTORS)
+    .load::<GroupActor>(&mut conn)?;
+
+    let actor_ids: Vec<Uuid> = rows.iter().map(|row| row.actor).collect();
+    let actors: HashMap<Uuid, User> = users::table
+        .filter(users::id.eq_any(&actor_ids))
+        .load::<User>(&mut conn)?
+        .into_iter()
+        .map(|u| (u.id, u))
+        .collect();
+
+    let mut by_group: HashMap<String, Vec<UserPublic>> = HashMap::new();
+    for row in rows {
+        if let Some(actor) = actors.get(&row.actor) {
+            by_group
+                .entry(row.key)
+                .or_default()
+                .push(UserPublic::from(actor.clone()));
+        }
+    }
+
+    Ok(by_group)
+}
+
+/// Marks the recipient's notifications as read, up to and including `up_to`,
+/// or all of them when `None`. Returns `None` if `up_to` is not one of theirs.
+pub fn mark_notifications_read_repo(
+    pool: &DbPool,
+    recipient: &Uuid,
+    up_to: Option<&Uuid>,
+) -> Result<Option<usize>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let unread = notifications
+        .filter(recipient_id.eq(recipient))
+        .filter(read_at.is_null());
+
+    let updated = match up_to {
+        Some(notification_id) => {
+            let anchor = notifications
+                .filter(id.eq(notification_id))
+                .filter(recipient_id.eq(recipient))
+                .select((created_at, id))
+                .first::<(chrono::NaiveDateTime, Uuid)>(&mut conn)
+                .optional()?;
+            let Some((anchor_at, anchor_id)) = anchor else {
+                return Ok(None);
+            };
+
+            diesel::update(
+                unread.filter(
+                    created_at
+                        .lt(anchor_at)
+                        .or(created_at.eq(anchor_at).and(id.le(anchor_id))),
+                ),
+            )
+            .set(read_at.eq(Utc::now().naive_utc()))
+            .execute(&mut conn)?
+        }
+        None => diesel::update(unread)
+            .set(read_at.eq(Utc::now().naive_utc()))
+            .execute(&mut conn)?,
+    };
+
+    Ok(Some(updated))
+}
+
+/// Counts the recipient's notification groups with unread notifications
+pub fn unread_count_repo(pool: &DbPool, recipient: &Uuid) -> Result<i64, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    notifications
+        .filter(recipient_id.eq(recipient))
+        .filter(read_at.is_null())
+        .select(count(group_key).aggregate_distinct())
+        .first(&mut conn)
+}
@@ -0,0 +1,145 @@
+use crate::database::{DbPool, get_db_conn};
+use crate::models::refresh_tokens::{NewRefreshToken, RefreshToken};
+use crate::schema::refresh_tokens::dsl::*;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// Outcome of presenting a refresh token for rotation
+pub enum RotationResult {
+    /// The token was valid and has been replaced by a new one in the same family
+    Rotated(RefreshToken),
+    /// The token had already been used; the whole family has been revoked
+    ReuseDetected,
+    /// The token is unknown or expired
+    Invalid,
+}
+
+/// Stores a refresh token starting a new session (token family)
+pub fn create_refresh_token_repo(
+    pool: &DbPool,
+    user_id_val: &Uuid,
+    token_hash_val: &str,
+    ttl: Duration,
+) -> Result<RefreshToken, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let new_token = NewRefreshToken {
+        id: Uuid::new_v4(),
+        user_id: *user_id_val,
+        family_id: Uuid::new_v4(),
+        token_hash: token_hash_val.to_string(),
+        expires_at: Utc::now().naive_utc() + ttl,
+    };
+
+    diesel::insert_into(refresh_tokens)
+        .values(&new_token)
+        .get_result(&mut conn)
+}
+
+/// Rotates a refresh token: revokes the presented one and stores its replacement.
+/// Presenting an already rotated token revokes every token in its family.
+pub fn rotate_refresh_token_repo(
+    pool: &DbPool,
+    presented_hash: &str,
+    new_hash: &str,
+    ttl: Duration,
+) -> Result<RotationResult, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        let now = Utc::now().naive_utc();
+
+        let Some(token) = refresh_tokens
+            .filter(token_hash.eq(presented_hash))
+            .for_update()
+            .first::<RefreshToken>(conn)
+            .optional()?
+        else {
+            return Ok(RotationResult::Invalid);
+        };
+
+        if token.revoked_at.is_some() {
+            diesel::update(
+                refresh_tokens
+                    .filter(family_id.eq(token.family_id))
+                    .filter(revoked_at.is_null()),
+            )
+            .set(revoked_at.eq(now))
+            .execute(conn)?;
+
+            return Ok(RotationResult::ReuseDetected);
+        }
+
+        if token.expires_at <= now {
+            return Ok(RotationResult::Invalid);
+        }
+
+        let new_token = NewRefreshToken {
+            id: Uuid::new_v4(),
+            user_id: token.user_id,
+            family_id: token.family_id,
+            token_hash: new_hash.to_string(),
+            expires_at: now + ttl,
+        };
+
+        let new_token = diesel::insert_into(refresh_tokens)
+            .values(&new_token)
+            .get_result::<RefreshToken>(conn)?;
+
+        diesel::update(refresh_tokens.find(token.id))
+            .set((revoked_at.eq(now), replaced_by.eq(new_token.id)))
+            .execute(conn)?;
+
+        Ok(RotationResult::Rotated(new_token))
+    })
+}
+
+/// Revokes every token of a session
+pub fn revoke_family_repo(
+    pool: &DbPool,
+    family_id_val: &Uuid,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(
+        refresh_tokens
+            .filter(family_id.eq(family_id_val))
+            .filter(revoked_at.is_null()),
+    )
+    .set(revoked_at.eq(Utc::now().naive_utc()))
+    .execute(&mut conn)
+}
+
+/// Revokes every session of a user
+pub fn revoke_all_user_tokens_repo(
+    pool: &DbPool,
+    user_id_val: &Uuid,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    diesel::update(
+        refresh_tokens
+            .filter(user_id.eq(user_id_val))
+            .filter(revoked_at.is_null()),
+    )
+    .set(revoked_at.eq(Utc::now().naive_utc()))
+    .execute(&mut conn)
+}
+
+/// Checks whether a session still has an unrevoked token
+pub fn is_session_active_repo(
+    pool: &DbPool,
+    family_id_val: &Uuid,
+) -> Result<bool, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let active = refresh_tokens
+        .filter(family_id.eq(family_id_val))
+        .filter(revoked_at.is_null())
+        .select(id)
+        .first::<Uuid>(&mut conn)
+        .optional()?;
+
+    Ok(active.is_some())
+}
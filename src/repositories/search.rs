@@ -0,0 +1,185 @@
+use crate::database::{DbPool, get_db_conn, lower};
+use crate::models::tweets::Tweet;
+use crate::pagination::{RankCursor, RankedPage};
+use crate::schema::tweets::dsl::*;
+use crate::schema::{blocks, follows, likes, tweet_media, tweets, users};
+use crate::search_query::{Filter, Operator, SearchQuery};
+use chrono::NaiveTime;
+use diesel::dsl::{count_star, exists, not, sql};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Double, Text};
+use uuid::Uuid;
+
+/// Seconds of recency worth as much as a perfect text match (30 days).
+/// Newer tweets outrank older ones with a similar text rank.
+const RECENCY_SCALE_SECS: f64 = 30.0 * 24.0 * 60.0 * 60.0;
+
+pub type TweetFilter = Box<dyn BoxableExpression<tweets::table, Pg, SqlType = Bool>>;
+type TweetScore = Box<dyn BoxableExpression<tweets::table, Pg, SqlType = Double>>;
+
+/// Matches the `search_vector` generated column (see the migration; it is not
+/// in the Diesel schema). `websearch_to_tsquery` understands `"quoted phrases"`,
+/// `-excluded` words and `or`.
+fn matches_text(text: &str) -> TweetFilter {
+    Box::new(
+        sql::<Bool>("tweets.search_vector @@ websearch_to_tsquery('english', ")
+            .bind::<Text, _>(text.to_string())
+            .sql(")"),
+    )
+}
+
+/// Text rank normalised to `[0, 1)`, plus creation time in units of [`RECENCY_SCALE_SECS`].
+/// Without text, tweets are ranked by recency alone.
+/// Depends only on the tweet and the query, so it is stable across pages.
+fn rank_score(text: &str) -> TweetScore {
+    let recency = format!(
+        "EXTRACT(EPOCH FROM tweets.created_at)::FLOAT8 / {:.1}",
+        RECENCY_SCALE_SECS
+    );
+
+    if text.is_empty() {
+        return Box::new(sql::<Double>(&format!("({})", recency)));
+    }
+
+    Box::new(
+        sql::<Double>("(ts_rank(tweets.search_vector, websearch_to_tsquery('english', ")
+            .bind::<Text, _>(text.to_string())
+            .sql(&format!("), 32)::FLOAT8 + {})", recency)),
+    )
+}
+
+/// Users whose username matches case-insensitively
+fn user_ids_named(name: &str) -> users::BoxedQuery<'static, Pg, diesel::sql_types::Uuid> {
+    users::table
+        .filter(lower(users::username).eq(name.to_lowercase()))
+        .select(users::id)
+        .into_boxed()
+}
+
+/// Compiles one search operator to a condition on `tweets`
+fn compile_operator(operator: &Operator) -> TweetFilter {
+    let condition: TweetFilter = match &operator.filter {
+        Filter::From(name) => Box::new(user_id.eq_any(user_ids_named(name))),
+        Filter::To(name) => {
+            let parent = diesel::alias!(tweets as parent);
+            Box::new(exists(
+                parent
+                    .filter(parent.field(id).nullable().eq(reply_to_id))
+                    .filter(parent.field(user_id).eq_any(user_ids_named(name))),
+            ))
+        }
+        Filter::Since(date) => Box::new(created_at.ge(date.and_time(NaiveTime::MIN))),
+        Filter::Until(date) => Box::new(created_at.lt(date.and_time(NaiveTime::MIN))),
+        // Media is attached through `tweet_media`; older tweets may carry `media_urls`
+        Filter::HasMedia => Box::new(
+            exists(tweet_media::table.filter(tweet_media::tweet_id.eq(id))).or(sql::<Bool>(
+                "COALESCE(cardinality(tweets.media_urls), 0) > 0",
+            )),
+        ),
+        Filter::Retweets => Box::new(is_retweet.eq(true)),
+        Filter::Replies => Box::new(reply_to_id.is_not_null()),
+        Filter::MinLikes(min) => Box::new(
+            likes::table
+                .filter(likes::tweet_id.eq(id))
+                .select(count_star())
+                .single_value()
+                .assume_not_null()
+                .ge(*min),
+        ),
+        Filter::Lang(code) => Box::new(lang.is_not_distinct_from(code.clone())),
+    };
+
+    if operator.negated {
+        Box::new(not(condition))
+    } else {
+        condition
+    }
+}
+
+/// Tweets the viewer may see: authors that are not protected (unless the viewer
+/// is the author or a follower) and that neither blocked nor were blocked by the viewer.
+pub fn visible_to(viewer_id: Option<&Uuid>) -> TweetFilter {
+    let public_author = exists(
+        users::table
+            .filter(users::id.eq(user_id))
+            .filter(users::is_protected.eq(false)),
+    );
+
+    let Some(viewer) = viewer_id else {
+        return Box::new(public_author);
+    };
+
+    let followed_author = exists(
+        follows::table
+            .filter(follows::follower_id.eq(*viewer))
+            .filter(follows::followed_id.eq(user_id)),
+    );
+    let blocked = exists(
+        blocks::table.filter(
+            blocks::blocker_id
+                .eq(*viewer)
+                .and(blocks::blocked_id.eq(user_id))
+                .or(blocks::blocker_id
+                    .eq(user_id)
+                    .and(blocks::blocked_id.eq(*viewer))),
+        ),
+    );
+
+    Box::new(
+        public_author
+            .or(user_id.eq(*viewer))
+            .or(followed_author)
+            .and(not(blocked)),
+    )
+}
+
+/// Searches tweets with free text and operators, best match first, with keyset
+/// pagination on the score. Retweets are left out unless the query asks for them
+/// with `filter:retweets`; deleted tweets are gone from the index.
+pub fn search_tweets_repo(
+    pool: &DbPool,
+    search: &SearchQuery,
+    viewer_id: Option<&Uuid>,
+    cursor: Option<&RankCursor>,
+    per_page: i64,
+) -> Result<RankedPage<Tweet>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+    let text = search.text.as_str();
+
+    let mut query = tweets
+        .filter(visible_to(viewer_id))
+        .select((tweets::all_columns, rank_score(text)))
+        .into_boxed();
+
+    if !text.is_empty() {
+        query = query.filter(matches_text(text));
+    }
+
+    let mentions_retweets = search
+        .operators
+        .iter()
+        .any(|operator| operator.filter == Filter::Retweets);
+    if !mentions_retweets {
+        query = query.filter(is_retweet.eq(false));
+    }
+
+    for operator in &search.operators {
+        query = query.filter(compile_operator(operator));
+    }
+
+    if let Some(c) = cursor {
+        query = query.filter(
+            rank_score(text)
+                .lt(c.score)
+                .or(rank_score(text).eq(c.score).and(id.lt(c.id))),
+        );
+    }
+
+    let rows = query
+        .order((rank_score(text).desc(), id.desc()))
+        .limit(per_page + 1)
+        .load::<(Tweet, f64)>(&mut conn)?;
+
+    Ok(RankedPage::from_rows(rows, per_page, |tweet| tweet.id))
+}
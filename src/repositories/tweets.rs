@@ -1,34 +1,56 @@
 use crate::database::{DbPool, get_db_conn};
-use crate::models::tweets::{NewTweet, Tweet};
+use crate::models::media::MediaEntity;
+use crate::entities::{hashtag_spans, mention_spans};
+use crate::models::tweets::{
+    HashtagEntity, MentionEntity, NewTweet, Tweet, TweetEntities, TweetWithStats,
+};
+use crate::models::users::{User, UserPublic};
+use crate::pagination::{self, Cursor, CursorPage, Direction};
+use crate::repositories::hashtags::index_hashtags;
+use crate::repositories::mentions::{MentionedUsers, get_tweets_mentions, index_mentions};
 use crate::schema::tweets::dsl::*;
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
-use crate::repositories::media::delete_media_by_user_id;
-use crate::storage::S3Storage;
+use crate::repositories::media::{
+    hidden_media_owners, sensitive_media_preference, viewer_media_entity,
+    AttachMediaError, attach_media, delete_storage_objects, delete_tweet_media, get_tweets_media,
+};
+use crate::media_urls::MediaUrls;
+use crate::storage::MediaStorage;
 
-/// Create a tweet
+/// Create a tweet with the given media attached
 pub fn create_tweet_repo(
     pool: &DbPool,
     user_id_val: &Uuid,
     content_val: &str,
-    media_urls_val: Option<Vec<Option<String>>>,
-) -> Result<Tweet, diesel::result::Error> {
+    lang_val: Option<&str>,
+    media_ids: &[Uuid],
+) -> Result<Tweet, AttachMediaError> {
     let mut conn = get_db_conn(pool)?;
 
     let new_tweet = NewTweet {
         user_id: *user_id_val,
         content: content_val.to_string(),
-        media_urls: media_urls_val,
+        media_urls: None,
         reply_to_id: None,
         is_retweet: false,
-        original_tweet_id: None,    
+        original_tweet_id: None,
+        lang: lang_val.map(str::to_string),
     };
 
-    let tweet = diesel::insert_into(tweets)
-        .values(&new_tweet)
-        .get_result::<Tweet>(&mut conn)?;
+    conn.transaction(|conn| {
+        let tweet = diesel::insert_into(tweets)
+            .values(&new_tweet)
+            .get_result::<Tweet>(conn)?;
 
-    Ok(tweet)
+        attach_media(conn, &tweet.id, user_id_val, media_ids)?;
+        index_hashtags(conn, &tweet)?;
+        index_mentions(conn, &tweet)?;
+
+        Ok(tweet)
+    })
 }
 
 /// Gest a tweet
@@ -40,6 +62,20 @@ pub fn get_tweet_repo(pool: &DbPool, tweet_id_val: &Uuid) -> Result<Tweet, diese
     Ok(tweet)
 }
 
+/// Gets the tweets with the given IDs, in no particular order
+pub fn get_tweets_by_ids_repo(
+    pool: &DbPool,
+    tweet_ids: &[Uuid],
+) -> Result<Vec<Tweet>, diesel::result::Error> {
+    if tweet_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut conn = get_db_conn(pool)?;
+
+    tweets.filter(id.eq_any(tweet_ids)).load::<Tweet>(&mut conn)
+}
+
 /// Gets paginated list of tweets
 pub fn get_tweets_repo(
     pool: &DbPool,
@@ -61,38 +97,69 @@ pub fn get_tweets_repo(
     Ok((tweets_list, total_count))
 }
 
-/// Deletes a tweet
+/// Restricts a boxed tweets query to one keyset page around the cursor.
+/// Fetches one extra row so the caller can tell whether more pages exist.
+pub fn keyset_page<'a>(
+    query: crate::schema::tweets::BoxedQuery<'a, Pg>,
+    cursor: Option<&Cursor>,
+    per_page: i64,
+) -> crate::schema::tweets::BoxedQuery<'a, Pg> {
+    pagination::keyset_page(query, created_at, id, cursor, per_page)
+}
+
+pub fn tweet_key(tweet: &Tweet) -> (chrono::NaiveDateTime, Uuid) {
+    (tweet.created_at, tweet.id)
+}
+
+/// Gets a cursor-paginated list of tweets
+pub fn get_tweets_cursor_repo(
+    pool: &DbPool,
+    cursor: Option<&Cursor>,
+    per_page: i64,
+) -> Result<CursorPage<Tweet>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let rows = keyset_page(tweets.into_boxed(), cursor, per_page).load::<Tweet>(&mut conn)?;
+
+    let direction = cursor.map(|c| c.direction).unwrap_or(Direction::Next);
+    Ok(CursorPage::from_rows(rows, per_page, direction, tweet_key))
+}
+
+/// Deletes a tweet together with the media attached to it.
+/// Rows are removed in one transaction; storage objects are removed afterwards,
+/// with failures queued for retry.
 pub async fn delete_tweet_repo(
     pool: &DbPool,
     tweet_id_val: &Uuid,
-    s3_client: &S3Storage,
+    storage: &dyn MediaStorage,
 ) -> Result<bool, actix_web::Error> {
     let mut conn = get_db_conn(pool)
         .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
 
-    // Получаем твит для user_id
-    let tweet: Tweet = tweets
-        .filter(id.eq(tweet_id_val))
-        .first(&mut conn)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+    let media_keys = conn
+        .transaction(|conn| {
+            let media_keys = delete_tweet_media(conn, tweet_id_val)?;
 
-    // Удаляем все медиа пользователя
-    delete_media_by_user_id(pool, &tweet.user_id, s3_client).await?;
+            diesel::delete(tweets.filter(id.eq(tweet_id_val))).execute(conn)?;
 
-    diesel::delete(tweets.filter(id.eq(tweet_id_val)))
-        .execute(&mut conn)
+            Ok::<_, diesel::result::Error>(media_keys)
+        })
         .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
 
+    delete_storage_objects(pool, storage, media_keys).await;
+
     Ok(true)
 }
 
-/// Creates a reply
+/// Creates a reply with the given media attached
 pub fn create_reply_repo(
     pool: &DbPool,
     tweet_id_val: &Uuid,
     user_id_val: &Uuid,
     content_val: &str,
-) -> Result<Tweet, diesel::result::Error> {
+    lang_val: Option<&str>,
+    media_ids: &[Uuid],
+) -> Result<Tweet, AttachMediaError> {
     let mut conn = get_db_conn(pool)?;
 
     let new_reply = NewTweet {
@@ -102,25 +169,36 @@ pub fn create_reply_repo(
         reply_to_id: Some(*tweet_id_val),
         is_retweet: false,
         original_tweet_id: None,
+        lang: lang_val.map(str::to_string),
     };
 
-    let reply = diesel::insert_into(tweets)
-        .values(&new_reply)
-        .get_result::<Tweet>(&mut conn)?;
+    conn.transaction(|conn| {
+        let reply = diesel::insert_into(tweets)
+            .values(&new_reply)
+            .get_result::<Tweet>(conn)?;
 
-    Ok(reply)
+        attach_media(conn, &reply.id, user_id_val, media_ids)?;
+        index_hashtags(conn, &reply)?;
+        index_mentions(conn, &reply)?;
+
+        Ok(reply)
+    })
 }
 
-/// Gets replies to a tweet
-pub fn get_replies_repo(pool: &DbPool, tweet_id_val: &Uuid) -> Result<Vec<Tweet>, diesel::result::Error> {
+/// Gets a cursor-paginated list of replies to a tweet
+pub fn get_replies_repo(
+    pool: &DbPool,
+    tweet_id_val: &Uuid,
+    cursor: Option<&Cursor>,
+    per_page: i64,
+) -> Result<CursorPage<Tweet>, diesel::result::Error> {
     let mut conn = get_db_conn(pool)?;
 
-    let replies = tweets
-        .filter(reply_to_id.eq(tweet_id_val))
-        .order(created_at.desc())
-        .load::<Tweet>(&mut conn)?;
+    let query = tweets.filter(reply_to_id.eq(*tweet_id_val)).into_boxed();
+    let rows = keyset_page(query, cursor, per_page).load::<Tweet>(&mut conn)?;
 
-    Ok(replies)
+    let direction = cursor.map(|c| c.direction).unwrap_or(Direction::Next);
+    Ok(CursorPage::from_rows(rows, per_page, direction, tweet_key))
 }
 
 /// Creates a retweet
@@ -139,11 +217,259 @@ pub fn create_retweet_repo(
         reply_to_id: None,
         is_retweet: true,
         original_tweet_id: Some(*tweet_id_val),
+        lang: None,
     };
 
-    let retweet = diesel::insert_into(tweets)
-        .values(&new_retweet)
-        .get_result::<Tweet>(&mut conn)?;
+    conn.transaction(|conn| {
+        let retweet = diesel::insert_into(tweets)
+            .values(&new_retweet)
+            .get_result::<Tweet>(conn)?;
+
+        // Quote retweets may carry hashtags and mentions of their own
+        index_hashtags(conn, &retweet)?;
+        index_mentions(conn, &retweet)?;
+
+        Ok(retweet)
+    })
+}
+
+#[derive(QueryableByName)]
+struct TimelineCount {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    count: i64,
+}
 
-    Ok(retweet)
-}
\ No newline at end of file
+/// Tweets by the user and everyone they follow, with retweets of the same
+/// original collapsed into the most recent one.
+const HOME_TIMELINE_SQL: &str = "
+    SELECT DISTINCT ON (COALESCE(t.original_tweet_id, t.id)) t.*
+    FROM tweets t
+    WHERE t.user_id = $1
+       OR t.user_id IN (SELECT f.followed_id FROM follows f WHERE f.follower_id = $1)
+    ORDER BY COALESCE(t.original_tweet_id, t.id), t.created_at DESC, t.id DESC
+";
+
+/// Gets a paginated home timeline for a user
+pub fn get_home_timeline_repo(
+    pool: &DbPool,
+    user_id_val: &Uuid,
+    page: i64,
+    per_page: i64,
+) -> Result<(Vec<Tweet>, i64), diesel::result::Error> {
+    use diesel::sql_types::{BigInt, Uuid as SqlUuid};
+
+    let mut conn = get_db_conn(pool)?;
+
+    let offset = (page - 1) * per_page;
+
+    let total_count = diesel::sql_query(format!(
+        "SELECT COUNT(*) AS count FROM ({}) feed",
+        HOME_TIMELINE_SQL
+    ))
+    .bind::<SqlUuid, _>(user_id_val)
+    .get_result::<TimelineCount>(&mut conn)?
+    .count;
+
+    let tweets_list = diesel::sql_query(format!(
+        "SELECT * FROM ({}) feed ORDER BY feed.created_at DESC, feed.id DESC OFFSET $2 LIMIT $3",
+        HOME_TIMELINE_SQL
+    ))
+    .bind::<SqlUuid, _>(user_id_val)
+    .bind::<BigInt, _>(offset)
+    .bind::<BigInt, _>(per_page)
+    .load::<Tweet>(&mut conn)?;
+
+    Ok((tweets_list, total_count))
+}
+
+/// Gets a cursor-paginated home timeline for a user
+pub fn get_home_timeline_cursor_repo(
+    pool: &DbPool,
+    user_id_val: &Uuid,
+    cursor: Option<&Cursor>,
+    per_page: i64,
+) -> Result<CursorPage<Tweet>, diesel::result::Error> {
+    use diesel::sql_types::{BigInt, Timestamp, Uuid as SqlUuid};
+
+    let mut conn = get_db_conn(pool)?;
+
+    let rows = match cursor {
+        Some(c) => {
+            let (comparison, ordering) = match c.direction {
+                Direction::Next => ("<", "DESC"),
+                Direction::Prev => (">", "ASC"),
+            };
+            diesel::sql_query(format!(
+                "SELECT * FROM ({}) feed WHERE (feed.created_at, feed.id) {} ($2, $3) \
+                 ORDER BY feed.created_at {}, feed.id {} LIMIT $4",
+                HOME_TIMELINE_SQL, comparison, ordering, ordering
+            ))
+            .bind::<SqlUuid, _>(user_id_val)
+            .bind::<Timestamp, _>(c.created_at)
+            .bind::<SqlUuid, _>(c.id)
+            .bind::<BigInt, _>(per_page + 1)
+            .load::<Tweet>(&mut conn)?
+        }
+        None => diesel::sql_query(format!(
+            "SELECT * FROM ({}) feed ORDER BY feed.created_at DESC, feed.id DESC LIMIT $2",
+            HOME_TIMELINE_SQL
+        ))
+        .bind::<SqlUuid, _>(user_id_val)
+        .bind::<BigInt, _>(per_page + 1)
+        .load::<Tweet>(&mut conn)?,
+    };
+
+    let direction = cursor.map(|c| c.direction).unwrap_or(Direction::Next);
+    Ok(CursorPage::from_rows(rows, per_page, direction, tweet_key))
+}
+
+/// Locates the hashtags and mentions in a tweet's content.
+/// `mentioned` holds the users resolved when the tweet was written.
+fn tweet_entities(content_val: &str, mentioned: &MentionedUsers) -> TweetEntities {
+    let hashtags = hashtag_spans(content_val)
+        .into_iter()
+        .map(|span| HashtagEntity {
+            tag: span.text,
+            start: span.start,
+            end: span.end,
+        })
+        .collect();
+
+    let mentions = mention_spans(content_val)
+        .into_iter()
+        .filter_map(|span| {
+            let (mentioned_id, username) = mentioned.get(&span.text.to_lowercase())?;
+            Some(MentionEntity {
+                user_id: *mentioned_id,
+                username: username.clone(),
+                start: span.start,
+                end: span.end,
+            })
+        })
+        .collect();
+
+    TweetEntities { hashtags, mentions }
+}
+
+/// Hydrates tweets with authors, media, entities, counts and the viewer's like/retweet flags.
+/// Uses a fixed number of batched queries regardless of the number of tweets.
+pub fn hydrate_tweets_repo(
+    pool: &DbPool,
+    urls: &MediaUrls,
+    tweets_list: Vec<Tweet>,
+    viewer_id: Option<&Uuid>,
+) -> Result<Vec<TweetWithStats>, diesel::result::Error> {
+    use crate::schema::{likes, users};
+    use diesel::dsl::count_star;
+
+    if tweets_list.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut conn = get_db_conn(pool)?;
+
+    let tweet_ids: Vec<Uuid> = tweets_list.iter().map(|t| t.id).collect();
+    let author_ids: Vec<Uuid> = tweets_list.iter().map(|t| t.user_id).collect();
+
+    let authors: HashMap<Uuid, UserPublic> = users::table
+        .filter(users::id.eq_any(&author_ids))
+        .load::<User>(&mut conn)?
+        .into_iter()
+        .map(|u| (u.id, UserPublic::from(u)))
+        .collect();
+
+    // Media of protected authors is only listed for the author and their followers
+    let hidden_media_owners = hidden_media_owners(&mut conn, authors.values(), viewer_id)?;
+
+    let sensitive_media = sensitive_media_preference(&mut conn, viewer_id)?;
+
+    let mut media_by_tweet: HashMap<Uuid, Vec<MediaEntity>> = HashMap::new();
+    for (tweet_id, media_item) in get_tweets_media(&mut conn, &tweet_ids)? {
+        if hidden_media_owners.contains(&media_item.user_id) {
+            continue;
+        }
+
+        if let Some(entity) = viewer_media_entity(&media_item, urls, viewer_id, &sensitive_media) {
+            media_by_tweet.entry(tweet_id).or_default().push(entity);
+        }
+    }
+
+    let mut mentions_by_tweet = get_tweets_mentions(&mut conn, &tweet_ids)?;
+
+    let likes_counts: HashMap<Uuid, i64> = likes::table
+        .filter(likes::tweet_id.eq_any(&tweet_ids))
+        .group_by(likes::tweet_id)
+        .select((likes::tweet_id, count_star()))
+        .load::<(Uuid, i64)>(&mut conn)?
+        .into_iter()
+        .collect();
+
+    let retweets_counts: HashMap<Uuid, i64> = tweets
+        .filter(is_retweet.eq(true))
+        .filter(original_tweet_id.eq_any(&tweet_ids))
+        .group_by(original_tweet_id)
+        .select((original_tweet_id, count_star()))
+        .load::<(Option<Uuid>, i64)>(&mut conn)?
+        .into_iter()
+        .filter_map(|(tweet_id, count)| tweet_id.map(|t| (t, count)))
+        .collect();
+
+    let replies_counts: HashMap<Uuid, i64> = tweets
+        .filter(reply_to_id.eq_any(&tweet_ids))
+        .group_by(reply_to_id)
+        .select((reply_to_id, count_star()))
+        .load::<(Option<Uuid>, i64)>(&mut conn)?
+        .into_iter()
+        .filter_map(|(tweet_id, count)| tweet_id.map(|t| (t, count)))
+        .collect();
+
+    let (liked, retweeted): (HashSet<Uuid>, HashSet<Uuid>) = match viewer_id {
+        Some(viewer) => {
+            let liked = likes::table
+                .filter(likes::user_id.eq(viewer))
+                .filter(likes::tweet_id.eq_any(&tweet_ids))
+                .select(likes::tweet_id)
+                .load::<Uuid>(&mut conn)?
+                .into_iter()
+                .collect();
+
+            let retweeted = tweets
+                .filter(user_id.eq(viewer))
+                .filter(is_retweet.eq(true))
+                .filter(original_tweet_id.eq_any(&tweet_ids))
+                .select(original_tweet_id)
+                .load::<Option<Uuid>>(&mut conn)?
+                .into_iter()
+                .flatten()
+                .collect();
+
+            (liked, retweeted)
+        }
+        None => (HashSet::new(), HashSet::new()),
+    };
+
+    let hydrated = tweets_list
+        .into_iter()
+        .filter_map(|tweet| {
+            // Tweets whose author is gone are dropped rather than failing the whole page
+            let user = authors.get(&tweet.user_id)?.clone();
+
+            Some(TweetWithStats {
+                media: media_by_tweet.remove(&tweet.id).unwrap_or_default(),
+                entities: tweet_entities(
+                    &tweet.content,
+                    &mentions_by_tweet.remove(&tweet.id).unwrap_or_default(),
+                ),
+                likes_count: likes_counts.get(&tweet.id).copied().unwrap_or(0),
+                retweets_count: retweets_counts.get(&tweet.id).copied().unwrap_or(0),
+                replies_count: replies_counts.get(&tweet.id).copied().unwrap_or(0),
+                is_liked_by_current_user: liked.contains(&tweet.id),
+                is_retweeted_by_current_user: retweeted.contains(&tweet.id),
+                user,
+                tweet,
+            })
+        })
+        .collect();
+
+    Ok(hydrated)
+}
@@ -1,5 +1,9 @@
 use crate::database::{DbPool, get_db_conn};
-use crate::models::users::{NewUser, User, UserUpdate};
+use crate::models::media::Media;
+use crate::models::users::{NewUser, ProfileImage, User, UserUpdate};
+use crate::pagination::{Cursor, CursorPage, Direction, keyset_page};
+use crate::repositories::media::release_media;
+use crate::schema::media;
 use crate::schema::users::dsl::*;
 use chrono::Utc;
 use diesel::prelude::*;
@@ -116,6 +120,35 @@ pub fn get_users(
     Ok((users_list, total_count))
 }
 
+/// Gets a cursor-paginated list of users with optional search
+pub fn get_users_cursor(
+    pool: &DbPool,
+    cursor: Option<&Cursor>,
+    per_page: i64,
+    search_query: Option<&str>,
+) -> Result<CursorPage<User>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    let mut query = users.into_boxed();
+
+    if let Some(search) = search_query {
+        let search_pattern = format!("%{}%", search);
+        query = query.filter(
+            username
+                .ilike(search_pattern.clone())
+                .or(name.ilike(search_pattern.clone()))
+                .or(bio.ilike(search_pattern)),
+        );
+    }
+
+    let rows = keyset_page(query, created_at, id, cursor, per_page).load::<User>(&mut conn)?;
+
+    let direction = cursor.map(|c| c.direction).unwrap_or(Direction::Next);
+    Ok(CursorPage::from_rows(rows, per_page, direction, |u| {
+        (u.created_at, u.id)
+    }))
+}
+
 /// Updates a user's information
 pub fn update_user_repo(
     pool: &DbPool,
@@ -141,10 +174,26 @@ pub fn update_user_repo(
         was_updated = true;
     }
 
-    // Update avatar_url if provided
-    if let Some(ref new_avatar_url) = user_update.avatar_url {
+    // Update is_protected if provided
+    if let Some(new_is_protected) = user_update.is_protected {
+        diesel::update(users.filter(id.eq(user_id)))
+            .set(is_protected.eq(new_is_protected))
+            .execute(&mut conn)?;
+        was_updated = true;
+    }
+
+    // Update sensitive_media if provided
+    if let Some(ref new_sensitive_media) = user_update.sensitive_media {
+        diesel::update(users.filter(id.eq(user_id)))
+            .set(sensitive_media.eq(new_sensitive_media))
+            .execute(&mut conn)?;
+        was_updated = true;
+    }
+
+    // Update dm_policy if provided
+    if let Some(ref new_dm_policy) = user_update.dm_policy {
         diesel::update(users.filter(id.eq(user_id)))
-            .set(avatar_url.eq(new_avatar_url))
+            .set(dm_policy.eq(new_dm_policy))
             .execute(&mut conn)?;
         was_updated = true;
     }
@@ -160,10 +209,100 @@ pub fn update_user_repo(
     users.filter(id.eq(user_id)).first::<User>(&mut conn)
 }
 
-/// Deletes a user from DB.
-pub fn delete_user_repo(pool: &DbPool, user_id: &Uuid) -> Result<bool, diesel::result::Error> {
+/// Deletes a user from DB along with their media, dropping the references it held on blobs.
+/// Returns the storage keys whose objects must be removed (released blobs, pending uploads,
+/// avatar and banner), or `None` if there is no such user.
+pub fn delete_user_repo(
+    pool: &DbPool,
+    user_id: &Uuid,
+) -> Result<Option<Vec<String>>, diesel::result::Error> {
+    let mut conn = get_db_conn(pool)?;
+
+    conn.transaction(|conn| {
+        let Some(user) = users
+            .find(user_id)
+            .for_update()
+            .first::<User>(conn)
+            .optional()?
+        else {
+            return Ok(None);
+        };
+
+        // Media rows would go with the user anyway; delete them first to release their blobs
+        let deleted = diesel::delete(media::table.filter(media::user_id.eq(user_id)))
+            .get_results::<Media>(conn)?;
+        let mut keys = release_media(conn, &deleted)?;
+        keys.extend(user.avatar_key);
+        keys.extend(user.banner_key);
+
+        diesel::delete(users.find(user_id)).execute(conn)?;
+        Ok(Some(keys))
+    })
+}
+
+/// Points a user's avatar or banner at a newly stored image.
+/// Returns the updated user and the key of the image it replaced, if any.
+pub fn replace_profile_image_repo(
+    pool: &DbPool,
+    user_id: &Uuid,
+    kind: ProfileImage,
+    new_key: &str,
+    new_url: &str,
+) -> Result<(User, Option<String>), diesel::result::Error> {
     let mut conn = get_db_conn(pool)?;
 
-    diesel::delete(users.filter(id.eq(user_id))).execute(&mut conn)?;
-    Ok(true)
+    conn.transaction(|conn| {
+        // Lock the row so concurrent uploads cannot both keep the same old key
+        let current = users.find(user_id).for_update().first::<User>(conn)?;
+        let old_key = kind.key(&current).map(str::to_string);
+
+        let target = users.find(user_id);
+        let now = Utc::now().naive_utc();
+        let updated = match kind {
+            ProfileImage::Avatar => diesel::update(target)
+                .set((avatar_key.eq(new_key), avatar_url.eq(new_url), updated_at.eq(now)))
+                .get_result::<User>(conn)?,
+            ProfileImage::Banner => diesel::update(target)
+                .set((banner_key.eq(new_key), banner_url.eq(new_url), updated_at.eq(now)))
+                .get_result::<User>(conn)?,
+        };
+
+        Ok((updated, old_key))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{delete_user_repo, replace_profile_image_repo};
+    use crate::database::test_pool;
+    use crate::models::users::ProfileImage;
+    use crate::repositories::fixtures::{create_user, new_blob};
+    use crate::repositories::media::{BlobSource, get_blob_repo, upload_media_repo};
+    use uuid::Uuid;
+
+    #[test]
+    fn deleting_a_user_releases_their_media_and_profile_images() {
+        let Some(pool) = test_pool() else { return };
+        let alice = create_user(&pool);
+        let bob = create_user(&pool);
+        let sha256 = Uuid::new_v4().simple().to_string();
+        let quota = (1000, 10);
+
+        let video = upload_media_repo(&pool, &alice.id, "a.mp4", &new_blob(&sha256), quota).unwrap();
+        let existing = BlobSource::Existing(sha256.clone());
+        upload_media_repo(&pool, &bob.id, "b.mp4", &existing, quota).unwrap();
+        let avatar = "uploads/profiles/test/avatar.jpg";
+        replace_profile_image_repo(&pool, &alice.id, ProfileImage::Avatar, avatar, "/avatar").unwrap();
+
+        // Bob still references the blob; only the avatar goes
+        let keys = delete_user_repo(&pool, &alice.id).unwrap().unwrap();
+        assert_eq!(keys, vec![avatar.to_string()]);
+        assert_eq!(get_blob_repo(&pool, &sha256).unwrap().unwrap().ref_count, 1);
+
+        let keys = delete_user_repo(&pool, &bob.id).unwrap().unwrap();
+        assert_eq!(keys, vec![video.s3_key]);
+        assert!(get_blob_repo(&pool, &sha256).unwrap().is_none());
+
+        assert!(delete_user_repo(&pool, &alice.id).unwrap().is_none());
+    }
 }
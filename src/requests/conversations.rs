@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateConversationRequest {
+    /// The other members: one user starts a one-to-one conversation, more a group
+    pub participant_ids: Vec<Uuid>,
+    /// Name of a group
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SendMessageRequest {
+    #[serde(default)]
+    pub content: String,
+    /// IDs returned by `/media/upload`, in display order
+    #[serde(default)]
+    pub media_ids: Vec<Uuid>,
+}
+
+/// Body of `POST /conversations/{id}/read`
+#[derive(Debug, Deserialize)]
+pub struct MarkConversationReadRequest {
+    /// Newest message read; the latest message when omitted
+    #[serde(default)]
+    pub up_to: Option<Uuid>,
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+/// Starts a direct upload; the file type is derived from the extension of `file_name`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateUploadRequest {
+    pub file_name: String,
+    pub size_bytes: i64,
+}
+
+/// Updates a media item's description; omitted fields are left unchanged.
+/// An empty `alt_text` clears it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateMediaRequest {
+    #[serde(default)]
+    pub alt_text: Option<String>,
+    #[serde(default)]
+    pub sensitive: Option<bool>,
+}
+
+/// Signature parameters of a signed media URL
+#[derive(Debug, Deserialize)]
+pub struct SignedMediaQuery {
+    #[serde(default)]
+    pub expires: Option<i64>,
+    #[serde(default)]
+    pub signature: Option<String>,
+}
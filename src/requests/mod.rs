@@ -1,2 +1,21 @@
+pub mod conversations;
+pub mod media;
+pub mod notifications;
+pub mod search;
 pub mod tweets;
 pub mod users;
+
+use actix_web::Error;
+use serde::de::DeserializeOwned;
+
+/// Parses an optional JSON request body. Only an empty body is `None`; any
+/// other body must be valid JSON for `T`, or the request fails with 400.
+pub fn optional_json<T: DeserializeOwned>(body: &[u8]) -> Result<Option<T>, Error> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+
+    serde_json::from_slice(body)
+        .map(Some)
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Json deserialize error: {}", e)))
+}
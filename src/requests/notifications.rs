@@ -0,0 +1,10 @@
+use serde::Deserialize;
+use uuid::Uuid;
+
+/// Body of `POST /notifications/read`
+#[derive(Debug, Deserialize)]
+pub struct MarkReadRequest {
+    /// Newest notification to mark as read; all notifications when omitted
+    #[serde(default)]
+    pub up_to: Option<Uuid>,
+}
@@ -0,0 +1,16 @@
+use serde::Deserialize;
+
+/// Query for `GET /search/tweets`
+#[derive(Debug, Deserialize)]
+pub struct SearchTweetsQuery {
+    #[serde(default)]
+    pub q: String,
+    #[serde(default = "default_per_page")]
+    pub per_page: i64,
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+fn default_per_page() -> i64 {
+    20
+}
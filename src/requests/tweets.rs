@@ -1,9 +1,16 @@
+use crate::pagination::PaginationMode;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateTweetRequest {
     pub content: String,
-    pub media_urls: Option<Vec<Option<String>>>,
+    /// IDs returned by `/media/upload`, in display order
+    #[serde(default)]
+    pub media_ids: Vec<Uuid>,
+    /// ISO 639-1 language code, e.g. `en`
+    #[serde(default)]
+    pub lang: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -11,17 +18,36 @@ pub struct CreateRetweetRequest {
     pub content: Option<String>,
 }
 
+/// Query for tweet listings that predate cursor pagination.
+/// They keep the legacy offset-based pagination unless `pagination=cursor`
+/// or a `cursor` is passed.
 #[derive(Deserialize)]
 pub struct TweetsQuery {
-    #[serde(default = "default_page")]
-    pub page: i64,
+    #[serde(default)]
+    pub page: Option<i64>,
     #[serde(default = "default_per_page")]
     pub per_page: i64,
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub pagination: Option<PaginationMode>,
 }
 
-fn default_page() -> i64 {
-    1
+impl TweetsQuery {
+    pub fn pagination_mode(&self) -> PaginationMode {
+        PaginationMode::resolve(self.pagination, self.page, self.cursor.as_deref())
+    }
 }
+
+/// Query for cursor-only listings
+#[derive(Deserialize)]
+pub struct CursorQuery {
+    #[serde(default = "default_per_page")]
+    pub per_page: i64,
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
 fn default_per_page() -> i64 {
     20
 }
@@ -1,9 +1,20 @@
+use crate::pagination::PaginationMode;
 use serde::Deserialize;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct LoginRequest {
     pub username_or_email: String,
     pub password: String,
+    /// Browser clients: deliver tokens as HttpOnly cookies instead of in the body
+    #[serde(default)]
+    pub use_cookies: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RefreshRequest {
+    /// Omitted by browser clients that received the refresh token as a cookie
+    #[serde(default)]
+    pub refresh_token: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -15,22 +26,36 @@ pub struct RegisterRequest {
     pub name: Option<String>,
     #[serde(default)]
     pub bio: Option<String>,
+}
+
+/// Version of a profile image, as found in its URL
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProfileImageQuery {
     #[serde(default)]
-    pub avatar_url: Option<String>,
+    pub v: Option<String>,
 }
 
+/// Query for the user listing.
+/// It keeps the legacy offset-based pagination unless `pagination=cursor`
+/// or a `cursor` is passed.
 #[derive(Debug, Clone, Deserialize)]
 pub struct UsersQuery {
-    #[serde(default = "default_page")]
-    pub page: i64,
+    #[serde(default)]
+    pub page: Option<i64>,
     #[serde(default = "default_per_page")]
     pub per_page: i64,
     #[serde(default)]
     pub search: Option<String>,
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub pagination: Option<PaginationMode>,
 }
 
-fn default_page() -> i64 {
-    1
+impl UsersQuery {
+    pub fn pagination_mode(&self) -> PaginationMode {
+        PaginationMode::resolve(self.pagination, self.page, self.cursor.as_deref())
+    }
 }
 fn default_per_page() -> i64 {
     20
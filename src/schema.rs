@@ -1,5 +1,36 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    blocks (blocker_id, blocked_id) {
+        blocker_id -> Uuid,
+        blocked_id -> Uuid,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    conversation_members (conversation_id, user_id) {
+        conversation_id -> Uuid,
+        user_id -> Uuid,
+        joined_at -> Timestamp,
+        left_at -> Nullable<Timestamp>,
+        muted -> Bool,
+        last_read_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    conversations (id) {
+        id -> Uuid,
+        is_group -> Bool,
+        name -> Nullable<Text>,
+        creator_id -> Nullable<Uuid>,
+        direct_key -> Nullable<Text>,
+        created_at -> Timestamp,
+        last_message_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     follows (follower_id, followed_id) {
         follower_id -> Uuid,
@@ -8,6 +39,14 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    hashtags (id) {
+        id -> Uuid,
+        tag -> Text,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     likes (id) {
         id -> Uuid,
@@ -17,6 +56,96 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    message_media (message_id, media_id) {
+        message_id -> Uuid,
+        media_id -> Uuid,
+        position -> Int2,
+    }
+}
+
+diesel::table! {
+    messages (id) {
+        id -> Uuid,
+        conversation_id -> Uuid,
+        sender_id -> Uuid,
+        content -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    notifications (id) {
+        id -> Uuid,
+        recipient_id -> Uuid,
+        actor_id -> Uuid,
+        kind -> Text,
+        tweet_id -> Nullable<Uuid>,
+        group_key -> Text,
+        read_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    refresh_tokens (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        family_id -> Uuid,
+        token_hash -> Text,
+        expires_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+        replaced_by -> Nullable<Uuid>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    storage_deletions (id) {
+        id -> Uuid,
+        storage_key -> Text,
+        attempts -> Int4,
+        last_error -> Nullable<Text>,
+        next_attempt_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    trends (hashtag_id) {
+        hashtag_id -> Uuid,
+        rank -> Int4,
+        score -> Float8,
+        recent_authors -> Int8,
+        baseline_authors -> Int8,
+        computed_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    tweet_hashtags (tweet_id, hashtag_id) {
+        tweet_id -> Uuid,
+        hashtag_id -> Uuid,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    tweet_media (tweet_id, media_id) {
+        tweet_id -> Uuid,
+        media_id -> Uuid,
+        position -> Int2,
+    }
+}
+
+diesel::table! {
+    tweet_mentions (tweet_id, user_id) {
+        tweet_id -> Uuid,
+        user_id -> Uuid,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     tweets (id) {
         id -> Uuid,
@@ -27,6 +156,7 @@ diesel::table! {
         is_retweet -> Bool,
         original_tweet_id -> Nullable<Uuid>,
         created_at -> Timestamp,
+        lang -> Nullable<Text>,
     }
 }
 
@@ -41,6 +171,12 @@ diesel::table! {
         avatar_url -> Nullable<Text>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        is_protected -> Bool,
+        sensitive_media -> Text,
+        avatar_key -> Nullable<Text>,
+        banner_key -> Nullable<Text>,
+        banner_url -> Nullable<Text>,
+        dm_policy -> Text,
     }
 }
 
@@ -52,11 +188,75 @@ diesel::table! {
         file_name -> Text,
         file_type -> Text,
         created_at -> Timestamp,
+        size_bytes -> Int8,
+        alt_text -> Nullable<Text>,
+        width -> Nullable<Int4>,
+        height -> Nullable<Int4>,
+        thumb_key -> Nullable<Text>,
+        small_key -> Nullable<Text>,
+        large_key -> Nullable<Text>,
+        status -> Text,
+        expires_at -> Nullable<Timestamp>,
+        content_hash -> Nullable<Text>,
+        sensitive -> Bool,
+    }
+}
+
+diesel::table! {
+    media_blobs (sha256) {
+        sha256 -> Text,
+        storage_key -> Text,
+        file_type -> Text,
+        size_bytes -> Int8,
+        width -> Nullable<Int4>,
+        height -> Nullable<Int4>,
+        thumb_key -> Nullable<Text>,
+        small_key -> Nullable<Text>,
+        large_key -> Nullable<Text>,
+        ref_count -> Int4,
+        created_at -> Timestamp,
     }
 }
 
+diesel::joinable!(conversation_members -> conversations (conversation_id));
+diesel::joinable!(conversation_members -> users (user_id));
+diesel::joinable!(conversations -> users (creator_id));
 diesel::joinable!(likes -> tweets (tweet_id));
+diesel::joinable!(media -> media_blobs (content_hash));
 diesel::joinable!(likes -> users (user_id));
+diesel::joinable!(message_media -> media (media_id));
+diesel::joinable!(message_media -> messages (message_id));
+diesel::joinable!(messages -> conversations (conversation_id));
+diesel::joinable!(messages -> users (sender_id));
+diesel::joinable!(notifications -> tweets (tweet_id));
+diesel::joinable!(refresh_tokens -> users (user_id));
+diesel::joinable!(trends -> hashtags (hashtag_id));
+diesel::joinable!(tweet_hashtags -> hashtags (hashtag_id));
+diesel::joinable!(tweet_hashtags -> tweets (tweet_id));
+diesel::joinable!(tweet_media -> media (media_id));
+diesel::joinable!(tweet_media -> tweets (tweet_id));
+diesel::joinable!(tweet_mentions -> tweets (tweet_id));
+diesel::joinable!(tweet_mentions -> users (user_id));
 diesel::joinable!(tweets -> users (user_id));
 
-diesel::allow_tables_to_appear_in_same_query!(follows, likes, tweets, users, media);
+diesel::allow_tables_to_appear_in_same_query!(
+    blocks,
+    conversation_members,
+    conversations,
+    follows,
+    hashtags,
+    likes,
+    message_media,
+    messages,
+    notifications,
+    refresh_tokens,
+    storage_deletions,
+    trends,
+    tweet_hashtags,
+    tweet_media,
+    tweet_mentions,
+    tweets,
+    users,
+    media,
+    media_blobs,
+);
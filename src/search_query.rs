@@ -0,0 +1,339 @@
+use crate::models::tweets::normalize_lang;
+use chrono::NaiveDate;
+use std::fmt;
+
+/// A parsed tweet search: free text for full-text matching plus operators
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchQuery {
+    /// Everything that is not an operator, in `websearch_to_tsquery` syntax.
+    /// Empty when the search only uses operators.
+    pub text: String,
+    pub operators: Vec<Operator>,
+}
+
+/// An operator such as `from:alice`, or `-filter:retweets` when negated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operator {
+    pub negated: bool,
+    pub filter: Filter,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    /// `from:username` — tweets by the user
+    From(String),
+    /// `to:username` — replies to the user's tweets
+    To(String),
+    /// `since:YYYY-MM-DD` — tweets from that day on
+    Since(NaiveDate),
+    /// `until:YYYY-MM-DD` — tweets before that day
+    Until(NaiveDate),
+    /// `has:media` — tweets with media attached
+    HasMedia,
+    /// `filter:retweets` — only retweets
+    Retweets,
+    /// `filter:replies` — only replies
+    Replies,
+    /// `min_likes:N` — tweets liked at least N times
+    MinLikes(i64),
+    /// `lang:xx` — tweets in the ISO 639-1 language
+    Lang(String),
+}
+
+impl Filter {
+    /// Whether a leading `-` may exclude matches of this filter
+    fn can_negate(&self) -> bool {
+        !matches!(self, Self::Since(_) | Self::Until(_) | Self::MinLikes(_))
+    }
+}
+
+/// Why a search query could not be parsed. Messages are safe to return to the client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchParseError {
+    UnknownOperator(String),
+    MissingValue(String),
+    InvalidValue {
+        operator: String,
+        value: String,
+        expected: &'static str,
+    },
+    CannotNegate(String),
+    UnclosedQuote,
+}
+
+impl fmt::Display for SearchParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownOperator(name) => write!(f, "Unknown search operator '{}:'", name),
+            Self::MissingValue(name) => write!(f, "Search operator '{}:' needs a value", name),
+            Self::InvalidValue {
+                operator,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Invalid value '{}' for search operator '{}:', expected {}",
+                value, operator, expected
+            ),
+            Self::CannotNegate(name) => {
+                write!(f, "Search operator '{}:' cannot be negated", name)
+            }
+            Self::UnclosedQuote => write!(f, "Unclosed quote in search query"),
+        }
+    }
+}
+
+impl SearchQuery {
+    /// Parses a query such as `rust "web server" -java from:alice since:2024-01-01`.
+    ///
+    /// A word is an operator when it looks like `name:value` with a lowercase
+    /// name; anything else, including quoted phrases and `-exclusions`, is free text.
+    pub fn parse(input: &str) -> Result<Self, SearchParseError> {
+        let mut text = Vec::new();
+        let mut operators = Vec::new();
+
+        for token in tokenize(input)? {
+            match parse_operator(&token)? {
+                Some(operator) => operators.push(operator),
+                None => text.push(token),
+            }
+        }
+
+        Ok(Self {
+            text: text.join(" "),
+            operators,
+        })
+    }
+}
+
+/// Splits on whitespace, keeping quoted phrases (with their quotes) together
+fn tokenize(input: &str) -> Result<Vec<String>, SearchParseError> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quote = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => {
+                in_quote = !in_quote;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !in_quote => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if in_quote {
+        return Err(SearchParseError::UnclosedQuote);
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+/// Expected format of `since:` and `until:` values, for error messages
+const DATE_FORMAT: &str = "a date like 2024-01-31";
+
+/// Parses `name:value` or `-name:value`; returns `None` for free-text words
+fn parse_operator(token: &str) -> Result<Option<Operator>, SearchParseError> {
+    let (negated, body) = match token.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, token),
+    };
+
+    let Some((name, value)) = body.split_once(':') else {
+        return Ok(None);
+    };
+    // Leave URLs and other words that merely contain a colon as text
+    let is_operator_name = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_lowercase() || c == '_')
+        && !value.starts_with("//");
+    if !is_operator_name {
+        return Ok(None);
+    }
+
+    if value.is_empty() {
+        return Err(SearchParseError::MissingValue(name.to_string()));
+    }
+
+    let invalid = |expected| SearchParseError::InvalidValue {
+        operator: name.to_string(),
+        value: value.to_string(),
+        expected,
+    };
+
+    let filter = match name {
+        "from" => Filter::From(parse_username(value).ok_or_else(|| invalid("a username"))?),
+        "to" => Filter::To(parse_username(value).ok_or_else(|| invalid("a username"))?),
+        "since" => Filter::Since(parse_date(value).ok_or_else(|| invalid(DATE_FORMAT))?),
+        "until" => Filter::Until(parse_date(value).ok_or_else(|| invalid(DATE_FORMAT))?),
+        "has" => match value {
+            "media" => Filter::HasMedia,
+            _ => return Err(invalid("'media'")),
+        },
+        "filter" => match value {
+            "retweets" => Filter::Retweets,
+            "replies" => Filter::Replies,
+            _ => return Err(invalid("'retweets' or 'replies'")),
+        },
+        "min_likes" => Filter::MinLikes(
+            value
+                .parse()
+                .ok()
+                .filter(|n: &i64| *n >= 0)
+                .ok_or_else(|| invalid("a non-negative number"))?,
+        ),
+        "lang" => Filter::Lang(
+            normalize_lang(value).ok_or_else(|| invalid("a two-letter language code"))?,
+        ),
+        _ => return Err(SearchParseError::UnknownOperator(name.to_string())),
+    };
+
+    if negated && !filter.can_negate() {
+        return Err(SearchParseError::CannotNegate(name.to_string()));
+    }
+
+    Ok(Some(Operator { negated, filter }))
+}
+
+/// Accepts `alice` or `@alice`
+fn parse_username(value: &str) -> Option<String> {
+    let name = value.strip_prefix('@').unwrap_or(value);
+    (!name.is_empty() && !name.contains('"')).then(|| name.to_string())
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn operator(filter: Filter) -> Operator {
+        Operator {
+            negated: false,
+            filter,
+        }
+    }
+
+    #[test]
+    fn free_text_keeps_quoted_phrases_and_exclusions() {
+        let query = SearchQuery::parse(r#"  rust "web server"   -java "#).unwrap();
+        assert_eq!(query.text, r#"rust "web server" -java"#);
+        assert!(query.operators.is_empty());
+    }
+
+    #[test]
+    fn operators_inside_quotes_are_text() {
+        let query = SearchQuery::parse(r#""from:alice said" hi"#).unwrap();
+        assert_eq!(query.text, r#""from:alice said" hi"#);
+        assert!(query.operators.is_empty());
+    }
+
+    #[test]
+    fn unclosed_quote_is_an_error() {
+        assert_eq!(
+            SearchQuery::parse(r#"rust "web server"#),
+            Err(SearchParseError::UnclosedQuote)
+        );
+    }
+
+    #[test]
+    fn parses_operators_and_text() {
+        let query = SearchQuery::parse(
+            "rust from:@alice to:bob since:2024-01-01 until:2024-02-01 has:media",
+        )
+        .unwrap();
+        assert_eq!(query.text, "rust");
+        assert_eq!(
+            query.operators,
+            vec![
+                operator(Filter::From("alice".to_string())),
+                operator(Filter::To("bob".to_string())),
+                operator(Filter::Since(date("2024-01-01"))),
+                operator(Filter::Until(date("2024-02-01"))),
+                operator(Filter::HasMedia),
+            ]
+        );
+    }
+
+    #[test]
+    fn operators_only_leave_empty_text() {
+        let query = SearchQuery::parse("filter:replies min_likes:10").unwrap();
+        assert_eq!(query.text, "");
+        assert_eq!(
+            query.operators,
+            vec![operator(Filter::Replies), operator(Filter::MinLikes(10))]
+        );
+    }
+
+    #[test]
+    fn negated_operators() {
+        let query = SearchQuery::parse("-from:alice -has:media -filter:retweets").unwrap();
+        assert!(query.operators.iter().all(|operator| operator.negated));
+        assert_eq!(
+            query.operators.iter().map(|o| o.filter.clone()).collect::<Vec<_>>(),
+            vec![Filter::From("alice".to_string()), Filter::HasMedia, Filter::Retweets]
+        );
+    }
+
+    #[test]
+    fn range_operators_cannot_be_negated() {
+        for input in ["-since:2024-01-01", "-until:2024-01-01", "-min_likes:5"] {
+            assert!(matches!(
+                SearchQuery::parse(input),
+                Err(SearchParseError::CannotNegate(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn unknown_operator_is_an_error() {
+        assert_eq!(
+            SearchQuery::parse("rust near:paris"),
+            Err(SearchParseError::UnknownOperator("near".to_string()))
+        );
+    }
+
+    #[test]
+    fn words_with_colons_that_are_not_operators_are_text() {
+        let query = SearchQuery::parse("https://example.com Note:this 12:30").unwrap();
+        assert_eq!(query.text, "https://example.com Note:this 12:30");
+        assert!(query.operators.is_empty());
+    }
+
+    #[test]
+    fn malformed_dates_are_rejected() {
+        for input in ["since:2024-13-01", "until:yesterday", "since:01/02/2024", "since:2024-02-30"] {
+            assert!(matches!(
+                SearchQuery::parse(input),
+                Err(SearchParseError::InvalidValue { expected: DATE_FORMAT, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert_eq!(
+            SearchQuery::parse("from:"),
+            Err(SearchParseError::MissingValue("from".to_string()))
+        );
+        for input in ["has:links", "filter:quotes", "min_likes:-1", "min_likes:many", "from:@"] {
+            assert!(matches!(
+                SearchQuery::parse(input),
+                Err(SearchParseError::InvalidValue { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use super::{MediaStorage, ObjectInfo, ObjectSummary, UploadSink, presign_unsupported};
+use actix_web::Error;
+use actix_web::web::Bytes;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::io::{ErrorKind, SeekFrom};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+/// Stores objects as files under a local directory. Intended for development and CI.
+#[derive(Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+        }
+    }
+
+    /// Resolves a key to a path under the root, rejecting keys that could escape it
+    fn path_for(&self, key: &str) -> Result<PathBuf, Error> {
+        let relative = Path::new(key);
+        let is_safe = relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+
+        if key.is_empty() || !is_safe {
+            return Err(actix_web::error::ErrorBadRequest("Invalid storage key"));
+        }
+
+        Ok(self.root.join(relative))
+    }
+}
+
+fn io_error(action: &str, e: std::io::Error) -> Error {
+    actix_web::error::ErrorInternalServerError(format!("Local storage {} error: {}", action, e))
+}
+
+#[async_trait(?Send)]
+impl MediaStorage for LocalStorage {
+    async fn put(&self, key: &str, data: Bytes, _content_type: &str) -> Result<(), Error> {
+        let path = self.path_for(key)?;
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| io_error("write", e))?;
+        }
+
+        tokio::fs::write(&path, &data)
+            .await
+            .map_err(|e| io_error("write", e))
+    }
+
+    async fn begin_upload(
+        &self,
+        key: &str,
+        _content_type: &str,
+    ) -> Result<Box<dyn UploadSink>, Error> {
+        let path = self.path_for(key)?;
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| io_error("write", e))?;
+        }
+
+        // Write to a temporary file so partial uploads are never visible under the key
+        let mut temp_path = path.clone().into_os_string();
+        temp_path.push(".part");
+        let temp_path = PathBuf::from(temp_path);
+
+        let file = tokio::fs::File::create(&temp_path)
+            .await
+            .map_err(|e| io_error("write", e))?;
+
+        Ok(Box::new(LocalUploadSink {
+            file,
+            temp_path,
+            path,
+        }))
+    }
+
+    async fn get(&self, key: &str) -> Result<Bytes, Error> {
+        let path = self.path_for(key)?;
+
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(Bytes::from(data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                Err(actix_web::error::ErrorNotFound("File not found"))
+            }
+            Err(e) => Err(io_error("read", e)),
+        }
+    }
+
+    async fn get_range(&self, key: &str, range: Range<u64>) -> Result<Bytes, Error> {
+        let path = self.path_for(key)?;
+
+        let mut file = match tokio::fs::File::open(&path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Err(actix_web::error::ErrorNotFound("File not found"));
+            }
+            Err(e) => return Err(io_error("read", e)),
+        };
+
+        file.seek(SeekFrom::Start(range.start))
+            .await
+            .map_err(|e| io_error("read", e))?;
+
+        let mut data = Vec::new();
+        file.take(range.end.saturating_sub(range.start))
+            .read_to_end(&mut data)
+            .await
+            .map_err(|e| io_error("read", e))?;
+
+        Ok(Bytes::from(data))
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), Error> {
+        let from_path = self.path_for(from)?;
+        let to_path = self.path_for(to)?;
+
+        if let Some(parent) = to_path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| io_error("write", e))?;
+        }
+
+        match tokio::fs::copy(&from_path, &to_path).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                Err(actix_web::error::ErrorNotFound("File not found"))
+            }
+            Err(e) => Err(io_error("write", e)),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        let path = self.path_for(key)?;
+
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(io_error("delete", e)),
+        }
+    }
+
+    async fn presign_upload(
+        &self,
+        _key: &str,
+        _content_type: &str,
+        _content_length: i64,
+        _expires_in: Duration,
+    ) -> Result<String, Error> {
+        Err(presign_unsupported())
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ObjectInfo>, Error> {
+        let path = self.path_for(key)?;
+
+        match tokio::fs::metadata(&path).await {
+            Ok(metadata) => Ok(Some(ObjectInfo {
+                size: metadata.len() as i64,
+                content_type: None,
+            })),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error("read", e)),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectSummary>, Error> {
+        let mut objects = Vec::new();
+        let mut dirs = vec![self.root.clone()];
+
+        while let Some(dir) = dirs.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(io_error("list", e)),
+            };
+
+            while let Some(entry) = entries.next_entry().await.map_err(|e| io_error("list", e))? {
+                let metadata = entry.metadata().await.map_err(|e| io_error("list", e))?;
+                if metadata.is_dir() {
+                    dirs.push(entry.path());
+                    continue;
+                }
+
+                // Keys always use `/`, whatever the platform's separator
+                let path = entry.path();
+                let Ok(relative) = path.strip_prefix(&self.root) else {
+                    continue;
+                };
+                let key = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+
+                if key.starts_with(prefix) {
+                    objects.push(ObjectSummary {
+                        key,
+                        last_modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+                    });
+                }
+            }
+        }
+
+        Ok(objects)
+    }
+}
+
+struct LocalUploadSink {
+    file: tokio::fs::File,
+    temp_path: PathBuf,
+    path: PathBuf,
+}
+
+#[async_trait(?Send)]
+impl UploadSink for LocalUploadSink {
+    async fn write(&mut self, chunk: Bytes) -> Result<(), Error> {
+        self.file
+            .write_all(&chunk)
+            .await
+            .map_err(|e| io_error("write", e))
+    }
+
+    async fn finish(mut self: Box<Self>) -> Result<(), Error> {
+        let result = match self.file.flush().await {
+            Ok(()) => tokio::fs::rename(&self.temp_path, &self.path).await,
+            Err(e) => Err(e),
+        };
+
+        // Don't leave the temporary file behind when the upload can't be completed
+        if let Err(e) = result {
+            if let Err(abort_error) = self.abort().await {
+                eprintln!("Local storage abort error: {}", abort_error);
+            }
+            return Err(io_error("write", e));
+        }
+
+        Ok(())
+    }
+
+    async fn abort(self: Box<Self>) -> Result<(), Error> {
+        drop(self.file);
+
+        match tokio::fs::remove_file(&self.temp_path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(io_error("delete", e)),
+        }
+    }
+}
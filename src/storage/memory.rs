@@ -0,0 +1,157 @@
+use super::{MediaStorage, ObjectInfo, ObjectSummary, UploadSink, presign_unsupported};
+use actix_web::Error;
+use actix_web::web::Bytes;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Keeps objects in process memory. Everything is lost on restart; meant for tests.
+pub struct MemoryStorage {
+    objects: Arc<Mutex<HashMap<String, StoredObject>>>,
+}
+
+#[derive(Clone)]
+struct StoredObject {
+    data: Bytes,
+    last_modified: DateTime<Utc>,
+}
+
+impl StoredObject {
+    fn new(data: Bytes) -> Self {
+        Self {
+            data,
+            last_modified: Utc::now(),
+        }
+    }
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self {
+            objects: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl MediaStorage for MemoryStorage {
+    async fn put(&self, key: &str, data: Bytes, _content_type: &str) -> Result<(), Error> {
+        self.objects
+            .lock()
+            .expect("memory storage lock poisoned")
+            .insert(key.to_string(), StoredObject::new(data));
+        Ok(())
+    }
+
+    async fn begin_upload(
+        &self,
+        key: &str,
+        _content_type: &str,
+    ) -> Result<Box<dyn UploadSink>, Error> {
+        Ok(Box::new(MemoryUploadSink {
+            objects: self.objects.clone(),
+            key: key.to_string(),
+            buffer: Vec::new(),
+        }))
+    }
+
+    async fn get(&self, key: &str) -> Result<Bytes, Error> {
+        self.objects
+            .lock()
+            .expect("memory storage lock poisoned")
+            .get(key)
+            .map(|object| object.data.clone())
+            .ok_or_else(|| actix_web::error::ErrorNotFound("File not found"))
+    }
+
+    async fn get_range(&self, key: &str, range: Range<u64>) -> Result<Bytes, Error> {
+        let data = self.get(key).await?;
+        let len = data.len() as u64;
+
+        Ok(data.slice(range.start.min(len) as usize..range.end.min(len) as usize))
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), Error> {
+        let mut objects = self.objects.lock().expect("memory storage lock poisoned");
+
+        let data = objects
+            .get(from)
+            .map(|object| object.data.clone())
+            .ok_or_else(|| actix_web::error::ErrorNotFound("File not found"))?;
+        objects.insert(to.to_string(), StoredObject::new(data));
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.objects
+            .lock()
+            .expect("memory storage lock poisoned")
+            .remove(key);
+        Ok(())
+    }
+
+    async fn presign_upload(
+        &self,
+        _key: &str,
+        _content_type: &str,
+        _content_length: i64,
+        _expires_in: Duration,
+    ) -> Result<String, Error> {
+        Err(presign_unsupported())
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ObjectInfo>, Error> {
+        Ok(self
+            .objects
+            .lock()
+            .expect("memory storage lock poisoned")
+            .get(key)
+            .map(|object| ObjectInfo {
+                size: object.data.len() as i64,
+                content_type: None,
+            }))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectSummary>, Error> {
+        Ok(self
+            .objects
+            .lock()
+            .expect("memory storage lock poisoned")
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, object)| ObjectSummary {
+                key: key.clone(),
+                last_modified: Some(object.last_modified),
+            })
+            .collect())
+    }
+}
+
+struct MemoryUploadSink {
+    objects: Arc<Mutex<HashMap<String, StoredObject>>>,
+    key: String,
+    buffer: Vec<u8>,
+}
+
+#[async_trait(?Send)]
+impl UploadSink for MemoryUploadSink {
+    async fn write(&mut self, chunk: Bytes) -> Result<(), Error> {
+        self.buffer.extend_from_slice(&chunk);
+        Ok(())
+    }
+
+    async fn finish(self: Box<Self>) -> Result<(), Error> {
+        self.objects
+            .lock()
+            .expect("memory storage lock poisoned")
+            .insert(self.key, StoredObject::new(Bytes::from(self.buffer)));
+        Ok(())
+    }
+
+    async fn abort(self: Box<Self>) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -1,93 +1,413 @@
+use actix_web::web::{Bytes, BytesMut};
 use actix_web::{Error, HttpRequest};
-use aws_sdk_s3::Client;
-use aws_sdk_s3::primitives::ByteStream;
-use futures_util::StreamExt;
-use mime_guess::from_path;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::{Stream, StreamExt};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::ops::Range;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
-#[derive(Clone)]
-pub struct S3Storage {
-    client: Client,
-    pub bucket_name: String,
+mod local;
+mod memory;
+mod s3;
+mod sniff;
+
+pub use local::LocalStorage;
+pub use memory::MemoryStorage;
+pub use s3::S3Storage;
+pub use sniff::{SNIFF_LEN, detect_media_type, expected_media_type, extension_for};
+
+/// Backend-agnostic object storage for uploaded media.
+/// Handlers receive it as `web::Data<dyn MediaStorage>`.
+#[async_trait(?Send)]
+pub trait MediaStorage: Send + Sync {
+    /// Stores an object under `key`, replacing any existing one
+    async fn put(&self, key: &str, data: Bytes, content_type: &str) -> Result<(), Error>;
+
+    /// Starts a streaming upload to `key`. The object only becomes visible
+    /// once [`UploadSink::finish`] succeeds.
+    async fn begin_upload(
+        &self,
+        key: &str,
+        content_type: &str,
+    ) -> Result<Box<dyn UploadSink>, Error>;
+
+    /// Reads a whole object
+    async fn get(&self, key: &str) -> Result<Bytes, Error>;
+
+    /// Reads the bytes of an object in `range`, truncated at the end of the object
+    async fn get_range(&self, key: &str, range: Range<u64>) -> Result<Bytes, Error>;
+
+    /// Copies an object to another key, replacing any existing one
+    async fn copy(&self, from: &str, to: &str) -> Result<(), Error>;
+
+    /// Deletes an object; deleting a missing object is not an error
+    async fn delete(&self, key: &str) -> Result<(), Error>;
+
+    /// Returns a time-limited URL the client can `PUT` an object to directly.
+    /// The signature binds the content type and exact length.
+    async fn presign_upload(
+        &self,
+        key: &str,
+        content_type: &str,
+        content_length: i64,
+        expires_in: Duration,
+    ) -> Result<String, Error>;
+
+    /// Returns an object's size and content type, or `None` if it does not exist
+    async fn head(&self, key: &str) -> Result<Option<ObjectInfo>, Error>;
+
+    /// Lists every object whose key starts with `prefix`
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectSummary>, Error>;
+}
+
+/// Metadata of a stored object
+#[derive(Debug, Clone)]
+pub struct ObjectInfo {
+    pub size: i64,
+    pub content_type: Option<String>,
 }
 
-impl S3Storage {
-    pub fn new(client: Client, bucket_name: impl Into<String>) -> Self {
-        Self {
-            client,
-            bucket_name: bucket_name.into(),
+/// An object returned by [`MediaStorage::list`]
+#[derive(Debug, Clone)]
+pub struct ObjectSummary {
+    pub key: String,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// An in-progress streaming upload
+#[async_trait(?Send)]
+pub trait UploadSink {
+    /// Appends a chunk to the object
+    async fn write(&mut self, chunk: Bytes) -> Result<(), Error>;
+
+    /// Completes the upload
+    async fn finish(self: Box<Self>) -> Result<(), Error>;
+
+    /// Discards everything written so far
+    async fn abort(self: Box<Self>) -> Result<(), Error>;
+}
+
+/// Size of each ranged read when streaming an object
+const STREAM_CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// Streams the bytes of an object in `range` as a sequence of ranged reads,
+/// so large objects are never held in memory at once.
+pub fn stream_object(
+    storage: Arc<dyn MediaStorage>,
+    key: String,
+    range: Range<u64>,
+) -> impl Stream<Item = Result<Bytes, Error>> {
+    futures_util::stream::try_unfold(range.start, move |position| {
+        let storage = storage.clone();
+        let key = key.clone();
+        let end = range.end;
+
+        async move {
+            if position >= end {
+                return Ok(None);
+            }
+
+            let chunk = storage
+                .get_range(&key, position..(position + STREAM_CHUNK_SIZE).min(end))
+                .await?;
+            if chunk.is_empty() {
+                // The object is shorter than expected
+                return Ok(None);
+            }
+
+            let next = position + chunk.len() as u64;
+            Ok(Some((chunk, next)))
         }
+    })
+}
+
+/// Computes the hex SHA-256 of the first `size` bytes of a stored object
+pub async fn hash_object(
+    storage: Arc<dyn MediaStorage>,
+    key: &str,
+    size: i64,
+) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+
+    let mut chunks = std::pin::pin!(stream_object(storage, key.to_string(), 0..size as u64));
+    while let Some(chunk) = chunks.next().await {
+        hasher.update(&chunk?);
     }
 
-    pub async fn save_file(
-        &self,
-        req: &HttpRequest,
-        mut payload: actix_web::web::Payload,
-        user_id: Uuid,
-    ) -> Result<(String, String, i64, Option<String>), Error> {
-        // Get original filename from header
-        let original_name = req
-            .headers()
-            .get("X-Filename")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("file");
-
-        // Generate unique file ID and key with extension
-        let file_id = Uuid::new_v4();
-        let ext = Path::new(original_name)
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("");
-        let key = if ext.is_empty() {
-            format!("uploads/{}/{}", user_id, file_id)
-        } else {
-            format!("uploads/{}/{}.{}", user_id, file_id, ext)
-        };
+    Ok(hex::encode(hasher.finalize()))
+}
 
-        let mut bytes: i64 = 0;
-        let mut chunks = Vec::new();
+/// Builds the storage backend selected by `STORAGE_BACKEND` (`s3`, `local` or `memory`).
+pub async fn from_env() -> Arc<dyn MediaStorage> {
+    let backend = env::var("STORAGE_BACKEND").unwrap_or_else(|_| "s3".to_string());
 
-        // Collect the payload stream into memory
-        while let Some(chunk) = payload.next().await {
-            let chunk = chunk.map_err(|e| {
-                actix_web::error::ErrorInternalServerError(format!("Stream error: {}", e))
-            })?;
-            bytes += chunk.len() as i64;
-            chunks.push(chunk); 
+    match backend.as_str() {
+        "s3" => Arc::new(S3Storage::from_env().await),
+        "local" => {
+            let root = env::var("STORAGE_LOCAL_DIR").unwrap_or_else(|_| "./storage".to_string());
+            Arc::new(LocalStorage::new(root))
         }
+        "memory" => Arc::new(MemoryStorage::new()),
+        other => panic!(
+            "Unknown STORAGE_BACKEND '{}', expected s3, local or memory",
+            other
+        ),
+    }
+}
+
+/// Maximum upload size for a MIME type, configurable with
+/// `MAX_IMAGE_UPLOAD_BYTES` (default 10 MiB) and `MAX_VIDEO_UPLOAD_BYTES` (default 512 MiB).
+pub fn max_upload_bytes(mime_type: &str) -> i64 {
+    let (var, default) = if mime_type.starts_with("video/") {
+        ("MAX_VIDEO_UPLOAD_BYTES", 512 * 1024 * 1024)
+    } else {
+        ("MAX_IMAGE_UPLOAD_BYTES", 10 * 1024 * 1024)
+    };
+
+    env::var(var)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Error for backends that cannot hand out presigned URLs
+fn presign_unsupported() -> Error {
+    actix_web::error::ErrorNotImplemented("Presigned URLs require the s3 storage backend")
+}
+
+/// Per-user storage quota: total bytes (`MEDIA_QUOTA_BYTES`, default 1 GiB)
+/// and number of files (`MEDIA_QUOTA_FILES`, default 1000)
+pub fn media_quota() -> (i64, i64) {
+    let var = |name: &str, default: i64| {
+        env::var(name)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    };
+
+    (
+        var("MEDIA_QUOTA_BYTES", 1024 * 1024 * 1024),
+        var("MEDIA_QUOTA_FILES", 1000),
+    )
+}
+
+fn payload_too_large(max_bytes: i64) -> Error {
+    actix_web::error::ErrorPayloadTooLarge(format!(
+        "File exceeds the maximum size of {} bytes",
+        max_bytes
+    ))
+}
+
+/// Builds `uploads/{user_id}/{uuid}.{ext}` for a new upload
+pub fn upload_key(user_id: Uuid, original_name: &str) -> String {
+    let file_id = Uuid::new_v4();
+    let ext = Path::new(original_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("");
+
+    if ext.is_empty() {
+        format!("uploads/{}/{}", user_id, file_id)
+    } else {
+        format!("uploads/{}/{}.{}", user_id, file_id, ext)
+    }
+}
+
+/// Builds `uploads/sha256/{aa}/{sha256}-{uuid}.{ext}` for a new blob. Every blob stored gets
+/// its own key, so a delete queued for a released blob can never remove the objects of a
+/// blob stored later for the same content.
+pub fn blob_key(sha256: &str, ext: &str) -> String {
+    format!("uploads/sha256/{}/{}-{}.{}", &sha256[..2], sha256, Uuid::new_v4(), ext)
+}
+
+/// Builds `uploads/profiles/{user_id}/{kind}_{version}.{ext}` for a profile image
+pub fn profile_image_key(user_id: Uuid, kind: &str, version: &str, ext: &str) -> String {
+    format!("uploads/profiles/{}/{}_{}.{}", user_id, kind, version, ext)
+}
 
-        // Combine chunks into a single ByteStream
-        let body = ByteStream::from(chunks.concat());
-
-        // Upload to S3
-        let _ = self
-            .client
-            .put_object()
-            .bucket(&self.bucket_name)
-            .key(&key)
-            .body(body)
-            .send()
-            .await
-            .map_err(|e| {
-                actix_web::error::ErrorInternalServerError(format!("S3 upload error: {}", e))
-            })?;
-
-        // Get MIME type from headers or guess from filename
-        let mime_type_val = req
-            .headers()
-            .get(actix_web::http::header::CONTENT_TYPE)
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s.to_string())
-            .or_else(|| from_path(original_name).first().map(|m| m.to_string()));
-
-        Ok((original_name.to_string(), key, bytes, mime_type_val))
+/// Reads a whole request body into memory, failing with 413 past `max_bytes`.
+/// Only for small uploads such as profile images.
+pub async fn read_payload(
+    req: &HttpRequest,
+    mut payload: actix_web::web::Payload,
+    max_bytes: i64,
+) -> Result<Bytes, Error> {
+    let declared_length = req
+        .headers()
+        .get(actix_web::http::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<i64>().ok());
+    if declared_length.is_some_and(|len| len > max_bytes) {
+        return Err(payload_too_large(max_bytes));
     }
 
-    pub async fn delete_file(&self, key: &str) -> Result<(), Error> {
-        self.client.delete_object().bucket(&self.bucket_name).key(key).send().await.map_err(|e| {
-            actix_web::error::ErrorInternalServerError(format!("S3 delete error: {}", e))
+    let mut body = BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| {
+            actix_web::error::ErrorInternalServerError(format!("Stream error: {}", e))
         })?;
-        Ok(())
+        if (body.len() + chunk.len()) as i64 > max_bytes {
+            return Err(payload_too_large(max_bytes));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(body.freeze())
+}
+
+/// An upload written to storage by [`save_file`]
+pub struct SavedFile {
+    pub key: String,
+    pub size: i64,
+    /// MIME type detected from the content
+    pub content_type: &'static str,
+    /// Hex SHA-256 of the uploaded bytes
+    pub sha256: String,
+}
+
+/// Streams an upload from the request payload into the backend,
+/// aborting with 413 as soon as it grows past `max_bytes`.
+///
+/// The leading bytes are sniffed before anything is stored: the upload is
+/// rejected with 400 unless its real format matches `declared_type` (and the
+/// `Content-Type` header, when one is sent).
+/// The content is hashed with SHA-256 while it streams.
+pub async fn save_file(
+    storage: &dyn MediaStorage,
+    req: &HttpRequest,
+    mut payload: actix_web::web::Payload,
+    user_id: Uuid,
+    original_name: &str,
+    declared_type: &str,
+    max_bytes: i64,
+) -> Result<SavedFile, Error> {
+    // Reject early when the declared length is already too large
+    let declared_length = req
+        .headers()
+        .get(actix_web::http::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<i64>().ok());
+    if declared_length.is_some_and(|len| len > max_bytes) {
+        return Err(payload_too_large(max_bytes));
+    }
+
+    // A Content-Type header other than the generic binary type must agree with the extension
+    let content_type = req
+        .headers()
+        .get(actix_web::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_ascii_lowercase());
+    if content_type.is_some_and(|ct| ct != "application/octet-stream" && ct != declared_type) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Content-Type does not match the file extension",
+        ));
+    }
+
+    // Buffer just enough of the payload to detect the real format
+    let mut head = BytesMut::new();
+    while head.len() < SNIFF_LEN {
+        match payload.next().await {
+            Some(Ok(chunk)) => head.extend_from_slice(&chunk),
+            Some(Err(e)) => {
+                return Err(actix_web::error::ErrorInternalServerError(format!(
+                    "Stream error: {}",
+                    e
+                )));
+            }
+            None => break,
+        }
+    }
+
+    let mut bytes = head.len() as i64;
+    if bytes > max_bytes {
+        return Err(payload_too_large(max_bytes));
+    }
+
+    let detected_type = detect_media_type(&head)
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("Unrecognized file format"))?;
+    if detected_type != declared_type {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "File content is {} but was declared as {}",
+            detected_type, declared_type
+        )));
     }
-}
\ No newline at end of file
+
+    let key = upload_key(user_id, original_name);
+
+    let mut sink = storage.begin_upload(&key, detected_type).await?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(&head);
+    if let Err(e) = sink.write(head.freeze()).await {
+        abort_upload(sink, &key).await;
+        return Err(e);
+    }
+
+    // Stream the rest of the payload to the backend chunk by chunk
+    while let Some(chunk) = payload.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                abort_upload(sink, &key).await;
+                return Err(actix_web::error::ErrorInternalServerError(format!(
+                    "Stream error: {}",
+                    e
+                )));
+            }
+        };
+
+        bytes += chunk.len() as i64;
+        if bytes > max_bytes {
+            abort_upload(sink, &key).await;
+            return Err(payload_too_large(max_bytes));
+        }
+
+        hasher.update(&chunk);
+        if let Err(e) = sink.write(chunk).await {
+            abort_upload(sink, &key).await;
+            return Err(e);
+        }
+    }
+
+    sink.finish().await?;
+
+    Ok(SavedFile {
+        key,
+        size: bytes,
+        content_type: detected_type,
+        sha256: hex::encode(hasher.finalize()),
+    })
+}
+
+/// Aborts an upload, logging failures since the original error matters more
+async fn abort_upload(sink: Box<dyn UploadSink>, key: &str) {
+    if let Err(e) = sink.abort().await {
+        eprintln!("Failed to abort upload of {}: {}", key, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::blob_key;
+    use crate::images::rendition_key;
+
+    #[test]
+    fn copies_of_the_same_content_never_share_keys() {
+        let sha256 = "ab".repeat(32);
+        let first = blob_key(&sha256, "png");
+        let second = blob_key(&sha256, "png");
+
+        assert!(first.starts_with(&format!("uploads/sha256/ab/{}-", sha256)));
+        assert_ne!(first, second);
+        assert_ne!(
+            rendition_key(&first, "thumb", "jpg"),
+            rendition_key(&second, "thumb", "jpg")
+        );
+    }
+}
@@ -0,0 +1,378 @@
+use super::{MediaStorage, ObjectInfo, ObjectSummary, UploadSink};
+use actix_web::Error;
+use actix_web::web::Bytes;
+use async_trait::async_trait;
+use aws_config::BehaviorVersion;
+use aws_config::meta::region::RegionProviderChain;
+use aws_sdk_s3::Client;
+use aws_sdk_s3::config::Region;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use chrono::DateTime;
+use std::env;
+use std::ops::Range;
+use std::time::Duration;
+
+/// Size of each multipart upload part. S3 requires at least 5 MiB for all but the last part.
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+#[derive(Clone)]
+pub struct S3Storage {
+    client: Client,
+    pub bucket_name: String,
+}
+
+impl S3Storage {
+    pub fn new(client: Client, bucket_name: impl Into<String>) -> Self {
+        Self {
+            client,
+            bucket_name: bucket_name.into(),
+        }
+    }
+
+    /// Builds a client from the AWS environment (`AWS_REGION`, `AWS_BUCKET_NAME`, credentials).
+    pub async fn from_env() -> Self {
+        let region_provider = RegionProviderChain::default_provider().or_else(Region::new(
+            env::var("AWS_REGION").unwrap_or_else(|_| "eu-north-1".to_string()),
+        ));
+
+        let config = aws_config::defaults(BehaviorVersion::latest())
+            .region(region_provider)
+            .load()
+            .await;
+
+        let client = Client::new(&config);
+        let bucket_name =
+            env::var("AWS_BUCKET_NAME").unwrap_or_else(|_| "file-storage".to_string());
+
+        Self::new(client, bucket_name)
+    }
+}
+
+#[async_trait(?Send)]
+impl MediaStorage for S3Storage {
+    async fn put(&self, key: &str, data: Bytes, content_type: &str) -> Result<(), Error> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .content_type(content_type)
+            .body(ByteStream::from(data))
+            .send()
+            .await
+            .map_err(|e| {
+                actix_web::error::ErrorInternalServerError(format!("S3 upload error: {}", e))
+            })?;
+        Ok(())
+    }
+
+    async fn begin_upload(
+        &self,
+        key: &str,
+        content_type: &str,
+    ) -> Result<Box<dyn UploadSink>, Error> {
+        Ok(Box::new(S3UploadSink {
+            storage: self.clone(),
+            key: key.to_string(),
+            content_type: content_type.to_string(),
+            buffer: Vec::new(),
+            upload_id: None,
+            parts: Vec::new(),
+        }))
+    }
+
+    async fn get(&self, key: &str) -> Result<Bytes, Error> {
+        let object = self
+            .client
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| {
+                if e.as_service_error().is_some_and(|e| e.is_no_such_key()) {
+                    actix_web::error::ErrorNotFound("File not found")
+                } else {
+                    actix_web::error::ErrorInternalServerError(format!("S3 download error: {}", e))
+                }
+            })?;
+
+        let data = object.body.collect().await.map_err(|e| {
+            actix_web::error::ErrorInternalServerError(format!("S3 download error: {}", e))
+        })?;
+
+        Ok(data.into_bytes())
+    }
+
+    async fn get_range(&self, key: &str, range: Range<u64>) -> Result<Bytes, Error> {
+        if range.is_empty() {
+            return Ok(Bytes::new());
+        }
+
+        let object = self
+            .client
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .range(format!("bytes={}-{}", range.start, range.end - 1))
+            .send()
+            .await
+            .map_err(|e| {
+                if e.as_service_error().is_some_and(|e| e.is_no_such_key()) {
+                    actix_web::error::ErrorNotFound("File not found")
+                } else {
+                    actix_web::error::ErrorInternalServerError(format!("S3 download error: {}", e))
+                }
+            })?;
+
+        let data = object.body.collect().await.map_err(|e| {
+            actix_web::error::ErrorInternalServerError(format!("S3 download error: {}", e))
+        })?;
+
+        Ok(data.into_bytes())
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), Error> {
+        self.client
+            .copy_object()
+            .bucket(&self.bucket_name)
+            .copy_source(format!("{}/{}", self.bucket_name, from))
+            .key(to)
+            .send()
+            .await
+            .map_err(|e| {
+                actix_web::error::ErrorInternalServerError(format!("S3 copy error: {}", e))
+            })?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| {
+                actix_web::error::ErrorInternalServerError(format!("S3 delete error: {}", e))
+            })?;
+        Ok(())
+    }
+
+    async fn presign_upload(
+        &self,
+        key: &str,
+        content_type: &str,
+        content_length: i64,
+        expires_in: Duration,
+    ) -> Result<String, Error> {
+        let config = PresigningConfig::expires_in(expires_in).map_err(|e| {
+            actix_web::error::ErrorInternalServerError(format!("S3 presign error: {}", e))
+        })?;
+
+        // Content-Type and Content-Length are signed, so the client cannot change them
+        let request = self
+            .client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .content_type(content_type)
+            .content_length(content_length)
+            .presigned(config)
+            .await
+            .map_err(|e| {
+                actix_web::error::ErrorInternalServerError(format!("S3 presign error: {}", e))
+            })?;
+
+        Ok(request.uri().to_string())
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ObjectInfo>, Error> {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(output) => Ok(Some(ObjectInfo {
+                size: output.content_length().unwrap_or(0),
+                content_type: output.content_type().map(str::to_string),
+            })),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(None),
+            Err(e) => Err(actix_web::error::ErrorInternalServerError(format!(
+                "S3 head error: {}",
+                e
+            ))),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectSummary>, Error> {
+        let mut pages = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket_name)
+            .prefix(prefix)
+            .into_paginator()
+            .send();
+
+        let mut objects = Vec::new();
+        while let Some(page) = pages.next().await {
+            let page = page.map_err(|e| {
+                actix_web::error::ErrorInternalServerError(format!("S3 list error: {}", e))
+            })?;
+
+            objects.extend(page.contents().iter().filter_map(|object| {
+                Some(ObjectSummary {
+                    key: object.key()?.to_string(),
+                    last_modified: object
+                        .last_modified()
+                        .and_then(|t| DateTime::from_timestamp(t.secs(), t.subsec_nanos())),
+                })
+            }));
+        }
+
+        Ok(objects)
+    }
+}
+
+fn multipart_error(e: impl std::fmt::Display) -> Error {
+    actix_web::error::ErrorInternalServerError(format!("S3 multipart upload error: {}", e))
+}
+
+/// Buffers up to one part in memory. Small files are sent with a single `PutObject`;
+/// larger ones switch to a multipart upload once the first part is full.
+struct S3UploadSink {
+    storage: S3Storage,
+    key: String,
+    content_type: String,
+    buffer: Vec<u8>,
+    upload_id: Option<String>,
+    parts: Vec<CompletedPart>,
+}
+
+impl S3UploadSink {
+    /// Uploads the buffered bytes as the next part, starting the multipart upload if needed
+    async fn flush_part(&mut self) -> Result<(), Error> {
+        let upload_id = match &self.upload_id {
+            Some(upload_id) => upload_id.clone(),
+            None => {
+                let output = self
+                    .storage
+                    .client
+                    .create_multipart_upload()
+                    .bucket(&self.storage.bucket_name)
+                    .key(&self.key)
+                    .content_type(&self.content_type)
+                    .send()
+                    .await
+                    .map_err(multipart_error)?;
+
+                let upload_id = output
+                    .upload_id()
+                    .ok_or_else(|| multipart_error("missing upload id"))?
+                    .to_string();
+                self.upload_id = Some(upload_id.clone());
+                upload_id
+            }
+        };
+
+        let part_number = self.parts.len() as i32 + 1;
+        let body = std::mem::take(&mut self.buffer);
+
+        let output = self
+            .storage
+            .client
+            .upload_part()
+            .bucket(&self.storage.bucket_name)
+            .key(&self.key)
+            .upload_id(&upload_id)
+            .part_number(part_number)
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(multipart_error)?;
+
+        self.parts.push(
+            CompletedPart::builder()
+                .part_number(part_number)
+                .set_e_tag(output.e_tag().map(str::to_string))
+                .build(),
+        );
+
+        Ok(())
+    }
+
+    /// Uploads the remaining bytes and completes the multipart upload
+    async fn complete(&mut self) -> Result<(), Error> {
+        if !self.buffer.is_empty() {
+            self.flush_part().await?;
+        }
+
+        self.storage
+            .client
+            .complete_multipart_upload()
+            .bucket(&self.storage.bucket_name)
+            .key(&self.key)
+            .set_upload_id(self.upload_id.clone())
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(std::mem::take(&mut self.parts)))
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(multipart_error)?;
+
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl UploadSink for S3UploadSink {
+    async fn write(&mut self, chunk: Bytes) -> Result<(), Error> {
+        self.buffer.extend_from_slice(&chunk);
+
+        if self.buffer.len() >= PART_SIZE {
+            self.flush_part().await?;
+        }
+        Ok(())
+    }
+
+    async fn finish(mut self: Box<Self>) -> Result<(), Error> {
+        if self.upload_id.is_none() {
+            let body = Bytes::from(std::mem::take(&mut self.buffer));
+            return self.storage.put(&self.key, body, &self.content_type).await;
+        }
+
+        // Abort on failure so the parts already uploaded don't linger in the bucket
+        if let Err(e) = self.complete().await {
+            if let Err(abort_error) = self.abort().await {
+                eprintln!("S3 multipart abort error: {}", abort_error);
+            }
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    async fn abort(self: Box<Self>) -> Result<(), Error> {
+        let Some(upload_id) = &self.upload_id else {
+            return Ok(());
+        };
+
+        self.storage
+            .client
+            .abort_multipart_upload()
+            .bucket(&self.storage.bucket_name)
+            .key(&self.key)
+            .upload_id(upload_id)
+            .send()
+            .await
+            .map_err(multipart_error)?;
+
+        Ok(())
+    }
+}
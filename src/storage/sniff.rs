@@ -0,0 +1,140 @@
+/// Number of leading bytes needed to recognise every supported format
+pub const SNIFF_LEN: usize = 64;
+
+/// Largest `ftyp` box accepted: the major brand, minor version and up to 58 compatible brands
+const MAX_FTYP_SIZE: usize = 256;
+
+/// Major brands of MP4 video. Other ISO base media files, such as HEIC, AVIF
+/// or 3GP, share the `ftyp` box but are not accepted.
+const MP4_BRANDS: [&[u8; 4]; 11] = [
+    b"isom", b"iso2", b"iso3", b"iso4", b"iso5", b"iso6", b"mp41", b"mp42", b"avc1", b"dash",
+    b"M4V ",
+];
+
+/// Detects the MIME type of a media file from its leading bytes.
+/// Returns `None` for anything that is not PNG, JPEG, GIF, MP4, MOV or WebM.
+pub fn detect_media_type(head: &[u8]) -> Option<&'static str> {
+    if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some("image/png");
+    }
+    if head.starts_with(b"\xff\xd8\xff") {
+        return Some("image/jpeg");
+    }
+    if head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a") {
+        return Some("image/gif");
+    }
+
+    // Matroska EBML header whose DocType is "webm"
+    if head.starts_with(b"\x1a\x45\xdf\xa3") {
+        return head
+            .windows(4)
+            .any(|window| window == b"webm")
+            .then_some("video/webm");
+    }
+
+    // ISO base media file: the brand in the `ftyp` box tells MP4 and QuickTime apart.
+    // QuickTime files without an `ftyp` box are not accepted: nothing in their first
+    // bytes tells them apart from arbitrary data.
+    if head.len() >= 12 && &head[4..8] == b"ftyp" && is_valid_ftyp_size(head) {
+        let brand = &head[8..12];
+        if brand == b"qt  " {
+            return Some("video/quicktime");
+        }
+        return MP4_BRANDS
+            .iter()
+            .any(|mp4| brand == *mp4)
+            .then_some("video/mp4");
+    }
+
+    None
+}
+
+/// An `ftyp` box holds an 8 byte header, the major brand, the minor version and
+/// a list of 4 byte compatible brands
+fn is_valid_ftyp_size(head: &[u8]) -> bool {
+    let size = u32::from_be_bytes([head[0], head[1], head[2], head[3]]) as usize;
+    (16..=MAX_FTYP_SIZE).contains(&size) && size.is_multiple_of(4)
+}
+
+/// Maps a file extension to the MIME type its content must have
+pub fn expected_media_type(ext: &str) -> Option<&'static str> {
+    match ext {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "mp4" => Some("video/mp4"),
+        "mov" => Some("video/quicktime"),
+        "webm" => Some("video/webm"),
+        _ => None,
+    }
+}
+
+/// File extension used when storing content of a detected MIME type
+pub fn extension_for(media_type: &str) -> &'static str {
+    match media_type {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "video/mp4" => "mp4",
+        "video/quicktime" => "mov",
+        "video/webm" => "webm",
+        _ => "bin",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::detect_media_type;
+
+    fn ftyp(brand: &[u8; 4]) -> Vec<u8> {
+        let mut head = b"\x00\x00\x00\x18ftyp".to_vec();
+        head.extend_from_slice(brand);
+        head.extend_from_slice(b"\x00\x00\x02\x00");
+        head
+    }
+
+    #[test]
+    fn mp4_and_quicktime_brands_are_videos() {
+        for brand in [b"isom", b"iso6", b"mp42", b"avc1", b"dash", b"M4V "] {
+            assert_eq!(detect_media_type(&ftyp(brand)), Some("video/mp4"));
+        }
+        assert_eq!(detect_media_type(&ftyp(b"qt  ")), Some("video/quicktime"));
+    }
+
+    #[test]
+    fn other_iso_media_brands_are_rejected() {
+        for brand in [b"heic", b"mif1", b"avif", b"3gp4", b"crx "] {
+            assert_eq!(detect_media_type(&ftyp(brand)), None);
+        }
+    }
+
+    #[test]
+    fn quicktime_without_ftyp_is_rejected() {
+        for atom in [b"moov", b"mdat", b"wide", b"free", b"skip", b"pnot"] {
+            let mut head = b"\x00\x00\x00\x08".to_vec();
+            head.extend_from_slice(atom);
+            head.extend_from_slice(b"\x00\x00\x00\x10mvhd");
+            assert_eq!(detect_media_type(&head), None);
+        }
+    }
+
+    #[test]
+    fn text_spelling_a_box_type_is_rejected() {
+        for text in [
+            &b"<!--free--><html><script>alert(1)</script>"[..],
+            b"<!--ftypisom--><html>",
+            b"abcdftypqt  <html>",
+        ] {
+            assert_eq!(detect_media_type(text), None);
+        }
+    }
+
+    #[test]
+    fn ftyp_boxes_with_invalid_sizes_are_rejected() {
+        for size in [0u32, 8, 18, 4096] {
+            let mut head = ftyp(b"isom");
+            head[..4].copy_from_slice(&size.to_be_bytes());
+            assert_eq!(detect_media_type(&head), None);
+        }
+    }
+}